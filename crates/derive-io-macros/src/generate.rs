@@ -0,0 +1,4218 @@
+//! Generates the trait impls for each derive directly from the parsed input.
+
+use proc_macro::*;
+
+use crate::parse::{join, Attr, Case, Field, Input};
+use crate::{template, Error};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Receiver {
+    /// `&self`
+    Ref,
+    /// `&mut self`
+    Mut,
+    /// `self: Pin<&mut Self>`
+    Pin,
+    /// `self`, which moves the delegate field out.
+    Owned,
+}
+
+/// The result type of a method, which determines the `#[io]` hooks called.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Output {
+    /// No hooks are called (ie: the result borrows from `self`).
+    None,
+    /// `io::Result<T>`
+    Io,
+    /// `Poll<io::Result<T>>`
+    Poll,
+    /// `Result<T, Self::Error>` of an `embedded-io` trait. No hooks are
+    /// called, but errors are converted into the `#[io(error = ...)]` type.
+    Embedded,
+    /// `(io::Result<T>, B)` of a completion-based trait, which returns the
+    /// owned buffer alongside the result.
+    Rent,
+}
+
+/// The bytes passed to an `inspect` hook when a method succeeds.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Inspect {
+    /// The first `n` bytes of `buf`, which are read or written.
+    Buf(Direction),
+    /// The bytes filled in the `ReadBuf` by this call.
+    ReadBuf,
+    /// The first `n` bytes of the `IoSlice`s in `bufs`.
+    IoSlices,
+    /// The first `n` bytes of the owned buffer returned with the result.
+    Rent,
+}
+
+impl Inspect {
+    /// The direction of the inspected bytes, as recorded in transcripts.
+    fn direction(self) -> Direction {
+        match self {
+            Inspect::Buf(direction) => direction,
+            Inspect::ReadBuf => Direction::Read,
+            Inspect::IoSlices | Inspect::Rent => Direction::Write,
+        }
+    }
+}
+
+/// The direction of the bytes passed to an `inspect` hook.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Read,
+    Write,
+}
+
+pub(crate) struct Method {
+    pub name: &'static str,
+    /// The full method signature, including any attributes.
+    pub signature: &'static str,
+    pub receiver: Receiver,
+    /// The arguments passed through to the delegate, after the receiver.
+    pub args: &'static str,
+    /// For `duck` delegation, the inherent method this method is derived from
+    /// (ie: `as_raw_fd` from `as_fd`).
+    pub duck_via: Option<&'static str>,
+    /// The method body when the delegate has a buffer, or `None` to use the
+    /// trait's default method. `#buffer` is the buffer field, and the raw
+    /// calls of the impl are available by method name (ie: `#read`).
+    pub buffered: Option<&'static str>,
+    pub output: Output,
+    pub inspect: Option<Inspect>,
+    /// The result borrows from `self`, so only `#[io(context)]` applies.
+    pub borrowed: bool,
+    /// The method is an `async fn`, so the delegated call is awaited.
+    pub awaited: bool,
+    /// For `from_io` delegation, the `io::Result<()>` call that adapts the
+    /// `io::Write` delegate `#target`.
+    pub from_io: Option<&'static str>,
+    /// For traits with a delegate per direction, the role of the delegate:
+    /// the first field marked with the role (ie: `#[descriptor(read)]`) or
+    /// with no role.
+    pub role: Option<&'static str>,
+    /// The trait and method called on the delegate, if not this method (ie:
+    /// `AsFd::as_fd` for `read_fd`).
+    pub delegate: Option<(&'static str, &'static str)>,
+    /// The result for cases without a delegate, if the delegate is optional
+    /// (ie: `None` for `try_as_fd`).
+    pub absent: Option<&'static str>,
+    /// The result for `#[descriptor(unsafe(raw))]` delegates, given the raw
+    /// descriptor `#fd` and the `#message` of the panic if it is `-1`.
+    pub from_raw: Option<&'static str>,
+    /// For `duck` delegation, the conversion of the inherent method's result
+    /// into the trait's result (ie: `Into::into` for `local_addr`).
+    pub duck_into: Option<&'static str>,
+    /// The result is a new delegate (ie: `try_clone`), which is rebuilt into
+    /// the same case of `Self`.
+    pub rebuilds: bool,
+}
+
+/// A call to the unbuffered delegate, used by buffered methods.
+pub(crate) struct Raw {
+    pub path: &'static str,
+    pub method: Method,
+}
+
+pub(crate) struct Impl {
+    pub cfg: Option<&'static str>,
+    /// The trait path, which may refer to `#krate`.
+    pub path: &'static str,
+    /// A type parameter of the trait that the impl is generic over (ie: the
+    /// item type of `Sink<Item>`).
+    pub param: Option<&'static str>,
+    /// The associated types of the trait, which are taken from the first
+    /// delegate's trait impl.
+    pub assoc: &'static [&'static str],
+    pub methods: &'static [Method],
+    /// The raw calls available to buffered methods.
+    pub raw: &'static [Raw],
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Buffer {
+    /// A `#[buffer]` or `#[buffer(read)]` field.
+    Read,
+    /// A `#[buffer(write)]` field, used with `#[write(buffered)]`.
+    Write,
+}
+
+pub(crate) struct Derive {
+    /// The field attribute that selects the delegate, ie: `read`.
+    pub attr: &'static str,
+    /// The kind of buffer field that this derive can use, if any.
+    pub buffer: Option<Buffer>,
+    pub impls: &'static [Impl],
+    /// The flush for `#[write(flush_on_drop)]`, which evaluates to an
+    /// `io::Result<()>` given `self: Pin<&mut Self>` and may refer to `#krate`,
+    /// if supported.
+    pub flush_on_drop: Option<&'static str>,
+}
+
+const fn method(
+    name: &'static str,
+    receiver: Receiver,
+    args: &'static str,
+    signature: &'static str,
+) -> Method {
+    Method {
+        name,
+        signature,
+        receiver,
+        args,
+        duck_via: None,
+        buffered: None,
+        output: Output::None,
+        inspect: None,
+        borrowed: false,
+        awaited: false,
+        from_io: None,
+        role: None,
+        delegate: None,
+        absent: None,
+        from_raw: None,
+        duck_into: None,
+        rebuilds: false,
+    }
+}
+
+const fn buffered(method: Method, buffered: &'static str) -> Method {
+    Method {
+        buffered: Some(buffered),
+        ..method
+    }
+}
+
+const fn hooked(method: Method, output: Output, inspect: Option<Inspect>) -> Method {
+    Method {
+        output,
+        inspect,
+        ..method
+    }
+}
+
+const READ_READ: Method = hooked(
+    method(
+        "read",
+        Receiver::Mut,
+        "buf",
+        "fn read(&mut self, buf: &mut [u8]) -> ::std::io::Result<usize>",
+    ),
+    Output::Io,
+    Some(Inspect::Buf(Direction::Read)),
+);
+
+const WRITE_WRITE: Method = hooked(
+    method(
+        "write",
+        Receiver::Mut,
+        "buf",
+        "fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize>",
+    ),
+    Output::Io,
+    Some(Inspect::Buf(Direction::Write)),
+);
+
+const WRITE_FLUSH: Method = hooked(
+    method(
+        "flush",
+        Receiver::Mut,
+        "",
+        "fn flush(&mut self) -> ::std::io::Result<()>",
+    ),
+    Output::Io,
+    None,
+);
+
+const ASYNC_READ_POLL_READ: Method = hooked(
+    method(
+        "poll_read",
+        Receiver::Pin,
+        "cx, buf",
+        "#[inline] fn poll_read(
+            self: ::core::pin::Pin<&mut Self>,
+            cx: &mut ::core::task::Context<'_>,
+            buf: &mut ::tokio::io::ReadBuf<'_>,
+        ) -> ::core::task::Poll<::std::io::Result<()>>",
+    ),
+    Output::Poll,
+    Some(Inspect::ReadBuf),
+);
+
+const ASYNC_WRITE_POLL_WRITE: Method = hooked(
+    method(
+        "poll_write",
+        Receiver::Pin,
+        "cx, buf",
+        "#[inline] fn poll_write(
+            self: ::core::pin::Pin<&mut Self>,
+            cx: &mut ::core::task::Context<'_>,
+            buf: &[u8],
+        ) -> ::core::task::Poll<::std::io::Result<usize>>",
+    ),
+    Output::Poll,
+    Some(Inspect::Buf(Direction::Write)),
+);
+
+const ASYNC_WRITE_POLL_FLUSH: Method = hooked(
+    method(
+        "poll_flush",
+        Receiver::Pin,
+        "cx",
+        "#[inline] fn poll_flush(
+            self: ::core::pin::Pin<&mut Self>,
+            cx: &mut ::core::task::Context<'_>,
+        ) -> ::core::task::Poll<::std::io::Result<()>>",
+    ),
+    Output::Poll,
+    None,
+);
+
+const ASYNC_WRITE_POLL_SHUTDOWN: Method = hooked(
+    method(
+        "poll_shutdown",
+        Receiver::Pin,
+        "cx",
+        "#[inline] fn poll_shutdown(
+            self: ::core::pin::Pin<&mut Self>,
+            cx: &mut ::core::task::Context<'_>,
+        ) -> ::core::task::Poll<::std::io::Result<()>>",
+    ),
+    Output::Poll,
+    None,
+);
+
+const ASYNC_WRITE_IS_WRITE_VECTORED: Method = method(
+    "is_write_vectored",
+    Receiver::Ref,
+    "",
+    "#[inline] fn is_write_vectored(&self) -> bool",
+);
+
+const ASYNC_WRITE_POLL_WRITE_VECTORED: Method = hooked(
+    method(
+        "poll_write_vectored",
+        Receiver::Pin,
+        "cx, bufs",
+        "#[inline] fn poll_write_vectored(
+            self: ::core::pin::Pin<&mut Self>,
+            cx: &mut ::core::task::Context<'_>,
+            bufs: &[::std::io::IoSlice<'_>],
+        ) -> ::core::task::Poll<::std::io::Result<usize>>",
+    ),
+    Output::Poll,
+    Some(Inspect::IoSlices),
+);
+
+const RAW_READ: &[Raw] = &[Raw {
+    path: "::std::io::Read",
+    method: READ_READ,
+}];
+
+const RAW_WRITE: &[Raw] = &[
+    Raw {
+        path: "::std::io::Write",
+        method: WRITE_WRITE,
+    },
+    Raw {
+        path: "::std::io::Write",
+        method: WRITE_FLUSH,
+    },
+];
+
+const RAW_ASYNC_READ: &[Raw] = &[Raw {
+    path: "::tokio::io::AsyncRead",
+    method: ASYNC_READ_POLL_READ,
+}];
+
+const RAW_ASYNC_WRITE: &[Raw] = &[
+    Raw {
+        path: "::tokio::io::AsyncWrite",
+        method: ASYNC_WRITE_POLL_WRITE,
+    },
+    Raw {
+        path: "::tokio::io::AsyncWrite",
+        method: ASYNC_WRITE_POLL_FLUSH,
+    },
+    Raw {
+        path: "::tokio::io::AsyncWrite",
+        method: ASYNC_WRITE_POLL_SHUTDOWN,
+    },
+];
+
+pub(crate) const READ: Derive = Derive {
+    attr: "read",
+    buffer: Some(Buffer::Read),
+    impls: &[Impl {
+        cfg: None,
+        path: "::std::io::Read",
+        param: None,
+        assoc: &[],
+        methods: &[buffered(
+            READ_READ,
+            "#krate::__support::buffered_read(#buffer, buf, |buf| #read)",
+        )],
+        raw: RAW_READ,
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const BUF_READ: Derive = Derive {
+    attr: "read",
+    buffer: Some(Buffer::Read),
+    impls: &[Impl {
+        cfg: None,
+        path: "::std::io::BufRead",
+        param: None,
+        assoc: &[],
+        methods: &[
+            buffered(
+                Method {
+                    borrowed: true,
+                    ..hooked(
+                        method(
+                            "fill_buf",
+                            Receiver::Mut,
+                            "",
+                            "fn fill_buf(&mut self) -> ::std::io::Result<&[u8]>",
+                        ),
+                        Output::Io,
+                        None,
+                    )
+                },
+                "#krate::__support::buffered_fill_buf(#buffer, |buf| #read)",
+            ),
+            buffered(
+                method(
+                    "consume",
+                    Receiver::Mut,
+                    "amt",
+                    "fn consume(&mut self, amt: usize)",
+                ),
+                "#krate::__support::buffered_consume(#buffer, amt)",
+            ),
+            // `has_data_left` is not yet stable, and `split`/`lines` cannot be
+            // implemented because we cannot construct our own `Split`/`Lines`.
+            hooked(
+                method(
+                    "read_until",
+                    Receiver::Mut,
+                    "byte, buf",
+                    "fn read_until(&mut self, byte: u8, buf: &mut Vec<u8>) -> ::std::io::Result<usize>",
+                ),
+                Output::Io,
+                None,
+            ),
+            hooked(
+                method(
+                    "skip_until",
+                    Receiver::Mut,
+                    "byte",
+                    "fn skip_until(&mut self, byte: u8) -> ::std::io::Result<usize>",
+                ),
+                Output::Io,
+                None,
+            ),
+            hooked(
+                method(
+                    "read_line",
+                    Receiver::Mut,
+                    "buf",
+                    "fn read_line(&mut self, buf: &mut String) -> ::std::io::Result<usize>",
+                ),
+                Output::Io,
+                None,
+            ),
+        ],
+        raw: RAW_READ,
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const WRITE: Derive = Derive {
+    attr: "write",
+    buffer: Some(Buffer::Write),
+    impls: &[Impl {
+        cfg: None,
+        path: "::std::io::Write",
+        param: None,
+        assoc: &[],
+        methods: &[
+            buffered(
+                WRITE_WRITE,
+                "#krate::__support::buffered_write(#buffer, buf, |buf| #write)",
+            ),
+            buffered(
+                WRITE_FLUSH,
+                "#krate::__support::buffered_flush_buf(#buffer, |buf| #write).and_then(|()| #flush)",
+            ),
+        ],
+        raw: RAW_WRITE,
+    }],
+    // SAFETY: `Write` only borrows unpinned fields mutably (`#[pin]` fields
+    // are rejected for `flush_on_drop`).
+    flush_on_drop: Some(
+        "::std::io::Write::flush(unsafe { ::core::pin::Pin::get_unchecked_mut(self) })",
+    ),
+};
+
+pub(crate) const ASYNC_READ: Derive = Derive {
+    attr: "read",
+    buffer: Some(Buffer::Read),
+    impls: &[Impl {
+        cfg: None,
+        path: "::tokio::io::AsyncRead",
+        param: None,
+        assoc: &[],
+        methods: &[buffered(
+            ASYNC_READ_POLL_READ,
+            "#krate::__support::buffered_poll_read(#buffer, buf, |buf| #poll_read)",
+        )],
+        raw: RAW_ASYNC_READ,
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const ASYNC_BUF_READ: Derive = Derive {
+    attr: "read",
+    buffer: Some(Buffer::Read),
+    impls: &[Impl {
+        cfg: None,
+        path: "::tokio::io::AsyncBufRead",
+        param: None,
+        assoc: &[],
+        methods: &[
+            buffered(
+                Method {
+                    borrowed: true,
+                    ..hooked(
+                        method(
+                            "poll_fill_buf",
+                            Receiver::Pin,
+                            "cx",
+                            "#[inline] fn poll_fill_buf(
+                                self: ::core::pin::Pin<&mut Self>,
+                                cx: &mut ::core::task::Context<'_>,
+                            ) -> ::core::task::Poll<::std::io::Result<&[u8]>>",
+                        ),
+                        Output::Poll,
+                        None,
+                    )
+                },
+                "#krate::__support::buffered_poll_fill_buf(#buffer, |buf| #poll_read)",
+            ),
+            buffered(
+                method(
+                    "consume",
+                    Receiver::Pin,
+                    "amt",
+                    "#[inline] fn consume(self: ::core::pin::Pin<&mut Self>, amt: usize)",
+                ),
+                "#krate::__support::buffered_consume(#buffer, amt)",
+            ),
+        ],
+        raw: RAW_ASYNC_READ,
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const ASYNC_WRITE: Derive = Derive {
+    attr: "write",
+    buffer: Some(Buffer::Write),
+    impls: &[Impl {
+        cfg: None,
+        path: "::tokio::io::AsyncWrite",
+        param: None,
+        assoc: &[],
+        methods: &[
+            buffered(
+                ASYNC_WRITE_POLL_WRITE,
+                "#krate::__support::buffered_poll_write(#buffer, buf, |buf| #poll_write)",
+            ),
+            buffered(
+                ASYNC_WRITE_POLL_FLUSH,
+                "match #krate::__support::buffered_poll_flush_buf(#buffer, |buf| #poll_write) {
+                    ::core::task::Poll::Ready(::core::result::Result::Ok(())) => #poll_flush,
+                    __derive_io_poll => __derive_io_poll,
+                }",
+            ),
+            buffered(
+                ASYNC_WRITE_POLL_SHUTDOWN,
+                "match #krate::__support::buffered_poll_flush_buf(#buffer, |buf| #poll_write) {
+                    ::core::task::Poll::Ready(::core::result::Result::Ok(())) => #poll_shutdown,
+                    __derive_io_poll => __derive_io_poll,
+                }",
+            ),
+            ASYNC_WRITE_IS_WRITE_VECTORED,
+            ASYNC_WRITE_POLL_WRITE_VECTORED,
+        ],
+        raw: RAW_ASYNC_WRITE,
+    }],
+    // There is no way to wait in `Drop`, so the flush is polled exactly once.
+    flush_on_drop: Some(
+        "match ::tokio::io::AsyncWrite::poll_flush(
+            self,
+            &mut ::core::task::Context::from_waker(&#krate::__support::noop_waker()),
+        ) {
+            ::core::task::Poll::Ready(result) => result,
+            ::core::task::Poll::Pending => ::core::result::Result::Err(::std::io::Error::new(
+                ::std::io::ErrorKind::WouldBlock,
+                \"flush on drop did not complete\",
+            )),
+        }",
+    ),
+};
+
+pub(crate) const FMT_WRITE: Derive = Derive {
+    attr: "fmt_write",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "::core::fmt::Write",
+        param: None,
+        assoc: &[],
+        methods: &[
+            Method {
+                from_io: Some("::std::io::Write::write_all(#target, s.as_bytes())"),
+                ..method(
+                    "write_str",
+                    Receiver::Mut,
+                    "s",
+                    "fn write_str(&mut self, s: &str) -> ::core::fmt::Result",
+                )
+            },
+            Method {
+                from_io: Some(
+                    "::std::io::Write::write_all(#target, c.encode_utf8(&mut [0; 4]).as_bytes())",
+                ),
+                ..method(
+                    "write_char",
+                    Receiver::Mut,
+                    "c",
+                    "fn write_char(&mut self, c: char) -> ::core::fmt::Result",
+                )
+            },
+            Method {
+                from_io: Some("::std::io::Write::write_fmt(#target, args)"),
+                ..method(
+                    "write_fmt",
+                    Receiver::Mut,
+                    "args",
+                    "fn write_fmt(&mut self, args: ::core::fmt::Arguments<'_>) -> ::core::fmt::Result",
+                )
+            },
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+const POSITIONAL_READ_AT: Method = hooked(
+    method(
+        "read_at",
+        Receiver::Ref,
+        "buf, offset",
+        "fn read_at(&self, buf: &mut [u8], offset: u64) -> ::std::io::Result<usize>",
+    ),
+    Output::Io,
+    Some(Inspect::Buf(Direction::Read)),
+);
+
+const POSITIONAL_READ_EXACT_AT: Method = hooked(
+    method(
+        "read_exact_at",
+        Receiver::Ref,
+        "buf, offset",
+        "fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> ::std::io::Result<()>",
+    ),
+    Output::Io,
+    None,
+);
+
+pub(crate) const POSITIONAL_IO: Derive = Derive {
+    attr: "positional",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::PositionalIo",
+        param: None,
+        assoc: &[],
+        methods: &[
+            POSITIONAL_READ_AT,
+            hooked(
+                method(
+                    "write_at",
+                    Receiver::Mut,
+                    "buf, offset",
+                    "fn write_at(&mut self, buf: &[u8], offset: u64) -> ::std::io::Result<usize>",
+                ),
+                Output::Io,
+                Some(Inspect::Buf(Direction::Write)),
+            ),
+            POSITIONAL_READ_EXACT_AT,
+            hooked(
+                method(
+                    "write_all_at",
+                    Receiver::Mut,
+                    "buf, offset",
+                    "fn write_all_at(&mut self, buf: &[u8], offset: u64) -> ::std::io::Result<()>",
+                ),
+                Output::Io,
+                None,
+            ),
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const FILE_EXT: Derive = Derive {
+    attr: "positional",
+    buffer: None,
+    impls: &[Impl {
+        cfg: Some("unix"),
+        path: "::std::os::unix::fs::FileExt",
+        param: None,
+        assoc: &[],
+        methods: &[
+            POSITIONAL_READ_AT,
+            hooked(
+                method(
+                    "write_at",
+                    Receiver::Ref,
+                    "buf, offset",
+                    "fn write_at(&self, buf: &[u8], offset: u64) -> ::std::io::Result<usize>",
+                ),
+                Output::Io,
+                Some(Inspect::Buf(Direction::Write)),
+            ),
+            POSITIONAL_READ_EXACT_AT,
+            hooked(
+                method(
+                    "write_all_at",
+                    Receiver::Ref,
+                    "buf, offset",
+                    "fn write_all_at(&self, buf: &[u8], offset: u64) -> ::std::io::Result<()>",
+                ),
+                Output::Io,
+                None,
+            ),
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+/// Borrows the raw descriptor of a `#[descriptor(unsafe(raw))]` delegate,
+/// which must not be `-1` as it cannot be borrowed.
+///
+/// SAFETY: `#[descriptor(unsafe(raw))]` asserts that the descriptor remains
+/// open while the type is borrowed.
+const BORROW_RAW_FD: &str = "match #fd {
+    -1 => ::core::panic!(#message),
+    __derive_io_fd => unsafe { ::std::os::fd::BorrowedFd::borrow_raw(__derive_io_fd) },
+}";
+
+const AS_FD: Impl = Impl {
+    cfg: Some("unix"),
+    path: "::std::os::fd::AsFd",
+    param: None,
+    assoc: &[],
+    methods: &[Method {
+        from_raw: Some(BORROW_RAW_FD),
+        ..method(
+            "as_fd",
+            Receiver::Ref,
+            "",
+            "fn as_fd(&self) -> ::std::os::fd::BorrowedFd<'_>",
+        )
+    }],
+    raw: &[],
+};
+
+const AS_RAW_FD: Impl = Impl {
+    cfg: Some("unix"),
+    path: "::std::os::fd::AsRawFd",
+    param: None,
+    assoc: &[],
+    methods: &[Method {
+        duck_via: Some("as_fd"),
+        from_raw: Some("#fd"),
+        ..method(
+            "as_raw_fd",
+            Receiver::Ref,
+            "",
+            "fn as_raw_fd(&self) -> ::std::os::fd::RawFd",
+        )
+    }],
+    raw: &[],
+};
+
+pub(crate) const AS_FILE_DESCRIPTOR: Derive = Derive {
+    attr: "descriptor",
+    buffer: None,
+    impls: &[
+        AS_FD,
+        AS_RAW_FD,
+        Impl {
+            cfg: Some("windows"),
+            path: "::std::os::windows::io::AsHandle",
+            param: None,
+            assoc: &[],
+            methods: &[method(
+                "as_handle",
+                Receiver::Ref,
+                "",
+                "fn as_handle(&self) -> ::std::os::windows::io::BorrowedHandle<'_>",
+            )],
+            raw: &[],
+        },
+        Impl {
+            cfg: Some("windows"),
+            path: "::std::os::windows::io::AsRawHandle",
+            param: None,
+            assoc: &[],
+            methods: &[Method {
+                duck_via: Some("as_handle"),
+                ..method(
+                    "as_raw_handle",
+                    Receiver::Ref,
+                    "",
+                    "fn as_raw_handle(&self) -> ::std::os::windows::io::RawHandle",
+                )
+            }],
+            raw: &[],
+        },
+    ],
+    flush_on_drop: None,
+};
+
+const AS_SOCKET: Impl = Impl {
+    cfg: Some("windows"),
+    path: "::std::os::windows::io::AsSocket",
+    param: None,
+    assoc: &[],
+    methods: &[method(
+        "as_socket",
+        Receiver::Ref,
+        "",
+        "fn as_socket(&self) -> ::std::os::windows::io::BorrowedSocket<'_>",
+    )],
+    raw: &[],
+};
+
+const AS_RAW_SOCKET: Impl = Impl {
+    cfg: Some("windows"),
+    path: "::std::os::windows::io::AsRawSocket",
+    param: None,
+    assoc: &[],
+    methods: &[Method {
+        duck_via: Some("as_socket"),
+        ..method(
+            "as_raw_socket",
+            Receiver::Ref,
+            "",
+            "fn as_raw_socket(&self) -> ::std::os::windows::io::RawSocket",
+        )
+    }],
+    raw: &[],
+};
+
+pub(crate) const AS_SOCKET_DESCRIPTOR: Derive = Derive {
+    attr: "descriptor",
+    buffer: None,
+    impls: &[AS_FD, AS_RAW_FD, AS_SOCKET, AS_RAW_SOCKET],
+    flush_on_drop: None,
+};
+
+pub(crate) const MAYBE_AS_FD: Derive = Derive {
+    attr: "descriptor",
+    buffer: None,
+    impls: &[
+        Impl {
+            cfg: Some("unix"),
+            path: "#krate::MaybeAsFd",
+            param: None,
+            assoc: &[],
+            methods: &[Method {
+                absent: Some("::core::option::Option::None"),
+                // SAFETY: as for `BORROW_RAW_FD`, except that `-1` is absent
+                from_raw: Some(
+                    "match #fd {
+                        -1 => ::core::option::Option::None,
+                        __derive_io_fd => ::core::option::Option::Some(unsafe {
+                            ::std::os::fd::BorrowedFd::borrow_raw(__derive_io_fd)
+                        }),
+                    }",
+                ),
+                ..method(
+                    "try_as_fd",
+                    Receiver::Ref,
+                    "",
+                    "fn try_as_fd(&self) -> ::core::option::Option<::std::os::fd::BorrowedFd<'_>>",
+                )
+            }],
+            raw: &[],
+        },
+        Impl {
+            cfg: Some("unix"),
+            path: "#krate::MaybeAsRawFd",
+            param: None,
+            assoc: &[],
+            methods: &[Method {
+                absent: Some("::core::option::Option::None"),
+                from_raw: Some(
+                    "match #fd {
+                        -1 => ::core::option::Option::None,
+                        __derive_io_fd => ::core::option::Option::Some(__derive_io_fd),
+                    }",
+                ),
+                ..method(
+                    "try_as_raw_fd",
+                    Receiver::Ref,
+                    "",
+                    "fn try_as_raw_fd(&self) -> ::core::option::Option<::std::os::fd::RawFd>",
+                )
+            }],
+            raw: &[],
+        },
+    ],
+    flush_on_drop: None,
+};
+
+pub(crate) const STREAM: Derive = Derive {
+    attr: "stream",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::__support::futures_core::Stream",
+        param: None,
+        assoc: &["Item"],
+        methods: &[
+            method(
+                "poll_next",
+                Receiver::Pin,
+                "cx",
+                "#[inline] fn poll_next(
+                    self: ::core::pin::Pin<&mut Self>,
+                    cx: &mut ::core::task::Context<'_>,
+                ) -> ::core::task::Poll<::core::option::Option<Self::Item>>",
+            ),
+            method(
+                "size_hint",
+                Receiver::Ref,
+                "",
+                "#[inline] fn size_hint(&self) -> (usize, ::core::option::Option<usize>)",
+            ),
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const SINK: Derive = Derive {
+    attr: "sink",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::__support::futures_sink::Sink",
+        param: Some("__DeriveIoItem"),
+        assoc: &["Error"],
+        methods: &[
+            method(
+                "poll_ready",
+                Receiver::Pin,
+                "cx",
+                "#[inline] fn poll_ready(
+                    self: ::core::pin::Pin<&mut Self>,
+                    cx: &mut ::core::task::Context<'_>,
+                ) -> ::core::task::Poll<::core::result::Result<(), Self::Error>>",
+            ),
+            method(
+                "start_send",
+                Receiver::Pin,
+                "item",
+                "#[inline] fn start_send(
+                    self: ::core::pin::Pin<&mut Self>,
+                    item: __DeriveIoItem,
+                ) -> ::core::result::Result<(), Self::Error>",
+            ),
+            method(
+                "poll_flush",
+                Receiver::Pin,
+                "cx",
+                "#[inline] fn poll_flush(
+                    self: ::core::pin::Pin<&mut Self>,
+                    cx: &mut ::core::task::Context<'_>,
+                ) -> ::core::task::Poll<::core::result::Result<(), Self::Error>>",
+            ),
+            method(
+                "poll_close",
+                Receiver::Pin,
+                "cx",
+                "#[inline] fn poll_close(
+                    self: ::core::pin::Pin<&mut Self>,
+                    cx: &mut ::core::task::Context<'_>,
+                ) -> ::core::task::Poll<::core::result::Result<(), Self::Error>>",
+            ),
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const HYPER_READ: Derive = Derive {
+    attr: "read",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::__support::hyper::rt::Read",
+        param: None,
+        assoc: &[],
+        // The cursor is moved into the delegate, so the bytes read cannot be
+        // inspected
+        methods: &[hooked(
+            method(
+                "poll_read",
+                Receiver::Pin,
+                "cx, buf",
+                "#[inline] fn poll_read(
+                    self: ::core::pin::Pin<&mut Self>,
+                    cx: &mut ::core::task::Context<'_>,
+                    buf: #krate::__support::hyper::rt::ReadBufCursor<'_>,
+                ) -> ::core::task::Poll<::std::io::Result<()>>",
+            ),
+            Output::Poll,
+            None,
+        )],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const HYPER_WRITE: Derive = Derive {
+    attr: "write",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::__support::hyper::rt::Write",
+        param: None,
+        assoc: &[],
+        methods: &[
+            ASYNC_WRITE_POLL_WRITE,
+            ASYNC_WRITE_POLL_FLUSH,
+            ASYNC_WRITE_POLL_SHUTDOWN,
+            ASYNC_WRITE_IS_WRITE_VECTORED,
+            ASYNC_WRITE_POLL_WRITE_VECTORED,
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const AS_READ_WRITE_FDS: Derive = Derive {
+    attr: "descriptor",
+    buffer: None,
+    impls: &[Impl {
+        cfg: Some("unix"),
+        path: "#krate::AsReadWriteFds",
+        param: None,
+        assoc: &[],
+        methods: &[
+            Method {
+                role: Some("read"),
+                delegate: Some(("::std::os::fd::AsFd", "as_fd")),
+                from_raw: Some(BORROW_RAW_FD),
+                ..method(
+                    "read_fd",
+                    Receiver::Ref,
+                    "",
+                    "fn read_fd(&self) -> ::std::os::fd::BorrowedFd<'_>",
+                )
+            },
+            Method {
+                role: Some("write"),
+                delegate: Some(("::std::os::fd::AsFd", "as_fd")),
+                from_raw: Some(BORROW_RAW_FD),
+                ..method(
+                    "write_fd",
+                    Receiver::Ref,
+                    "",
+                    "fn write_fd(&self) -> ::std::os::fd::BorrowedFd<'_>",
+                )
+            },
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const INTO_FILE_DESCRIPTOR: Derive = Derive {
+    attr: "descriptor",
+    buffer: None,
+    impls: &[Impl {
+        cfg: Some("unix"),
+        path: "::std::os::fd::IntoRawFd",
+        param: None,
+        assoc: &[],
+        methods: &[method(
+            "into_raw_fd",
+            Receiver::Owned,
+            "",
+            "fn into_raw_fd(self) -> ::std::os::fd::RawFd",
+        )],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const MIO_SOURCE: Derive = Derive {
+    attr: "descriptor",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::__support::mio::event::Source",
+        param: None,
+        assoc: &[],
+        methods: &[
+            hooked(
+                method(
+                    "register",
+                    Receiver::Mut,
+                    "registry, token, interests",
+                    "fn register(
+                    &mut self,
+                    registry: &#krate::__support::mio::Registry,
+                    token: #krate::__support::mio::Token,
+                    interests: #krate::__support::mio::Interest,
+                ) -> ::std::io::Result<()>",
+                ),
+                Output::Io,
+                None,
+            ),
+            hooked(
+                method(
+                    "reregister",
+                    Receiver::Mut,
+                    "registry, token, interests",
+                    "fn reregister(
+                    &mut self,
+                    registry: &#krate::__support::mio::Registry,
+                    token: #krate::__support::mio::Token,
+                    interests: #krate::__support::mio::Interest,
+                ) -> ::std::io::Result<()>",
+                ),
+                Output::Io,
+                None,
+            ),
+            hooked(
+                method(
+                    "deregister",
+                    Receiver::Mut,
+                    "registry",
+                    "fn deregister(
+                    &mut self,
+                    registry: &#krate::__support::mio::Registry,
+                ) -> ::std::io::Result<()>",
+                ),
+                Output::Io,
+                None,
+            ),
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+/// The `Listener` impl of `#[derive(Listener)]`, which is generated by
+/// `listener` as each case wraps the accepted stream in its own variant.
+const LISTENER_IMPL: Impl = Impl {
+    cfg: None,
+    path: "#krate::Listener",
+    param: None,
+    assoc: &[],
+    methods: &[
+        method(
+            "poll_accept",
+            Receiver::Ref,
+            "cx",
+            "fn poll_accept(
+                &self,
+                cx: &mut ::core::task::Context<'_>,
+            ) -> ::core::task::Poll<::std::io::Result<(Self::Stream, #krate::SocketAddrAny)>>",
+        ),
+        method(
+            "local_addr",
+            Receiver::Ref,
+            "",
+            "fn local_addr(&self) -> ::std::io::Result<#krate::SocketAddrAny>",
+        ),
+    ],
+    raw: &[],
+};
+
+/// A `SocketAddrs` method. Inherent methods return the address of their own
+/// transport, which is converted into a `SocketAddrAny`.
+const fn socket_addr(name: &'static str, signature: &'static str) -> Method {
+    Method {
+        duck_into: Some("::core::convert::Into::into"),
+        ..hooked(method(name, Receiver::Ref, "", signature), Output::Io, None)
+    }
+}
+
+pub(crate) const SOCKET_ADDRS: Derive = Derive {
+    attr: "descriptor",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::SocketAddrs",
+        param: None,
+        assoc: &[],
+        methods: &[
+            socket_addr(
+                "local_addr",
+                "fn local_addr(&self) -> ::std::io::Result<#krate::SocketAddrAny>",
+            ),
+            socket_addr(
+                "peer_addr",
+                "fn peer_addr(&self) -> ::std::io::Result<#krate::SocketAddrAny>",
+            ),
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+/// A `SocketControl` method.
+const fn socket_control(name: &'static str, args: &'static str, signature: &'static str) -> Method {
+    hooked(
+        method(name, Receiver::Ref, args, signature),
+        Output::Io,
+        None,
+    )
+}
+
+pub(crate) const SOCKET_CONTROL: Derive = Derive {
+    attr: "descriptor",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::SocketControl",
+        param: None,
+        assoc: &[],
+        methods: &[
+            socket_control(
+                "set_nodelay",
+                "nodelay",
+                "fn set_nodelay(&self, nodelay: bool) -> ::std::io::Result<()>",
+            ),
+            socket_control("nodelay", "", "fn nodelay(&self) -> ::std::io::Result<bool>"),
+            socket_control(
+                "set_ttl",
+                "ttl",
+                "fn set_ttl(&self, ttl: u32) -> ::std::io::Result<()>",
+            ),
+            socket_control(
+                "shutdown",
+                "how",
+                "fn shutdown(&self, how: ::std::net::Shutdown) -> ::std::io::Result<()>",
+            ),
+            Method {
+                rebuilds: true,
+                ..socket_control(
+                    "try_clone",
+                    "",
+                    "fn try_clone(&self) -> ::std::io::Result<Self>",
+                )
+            },
+            socket_control(
+                "set_nonblocking",
+                "nonblocking",
+                "fn set_nonblocking(&self, nonblocking: bool) -> ::std::io::Result<()>",
+            ),
+            socket_control(
+                "set_read_timeout",
+                "timeout",
+                "fn set_read_timeout(
+                    &self,
+                    timeout: ::core::option::Option<::core::time::Duration>,
+                ) -> ::std::io::Result<()>",
+            ),
+            socket_control(
+                "set_write_timeout",
+                "timeout",
+                "fn set_write_timeout(
+                    &self,
+                    timeout: ::core::option::Option<::core::time::Duration>,
+                ) -> ::std::io::Result<()>",
+            ),
+            socket_control(
+                "take_error",
+                "",
+                "fn take_error(&self) -> ::std::io::Result<::core::option::Option<::std::io::Error>>",
+            ),
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const LISTENER: Derive = Derive {
+    attr: "listener",
+    buffer: None,
+    impls: &[LISTENER_IMPL, AS_FD, AS_RAW_FD, AS_SOCKET, AS_RAW_SOCKET],
+    flush_on_drop: None,
+};
+
+/// A method of a completion-based trait, which is an `async fn` that returns
+/// the owned buffer with the result.
+const fn rented(method: Method, inspect: Option<Inspect>) -> Method {
+    Method {
+        awaited: true,
+        ..hooked(method, Output::Rent, inspect)
+    }
+}
+
+pub(crate) const ASYNC_READ_RENT: Derive = Derive {
+    attr: "read",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::__support::monoio::io::AsyncReadRent",
+        param: None,
+        assoc: &[],
+        // The bytes read are only known to be initialized by the delegate, so
+        // they cannot be inspected
+        methods: &[
+            rented(
+                method(
+                    "read",
+                    Receiver::Mut,
+                    "buf",
+                    "async fn read<__DeriveIoBuf: #krate::__support::monoio::buf::IoBufMut>(
+                        &mut self,
+                        buf: __DeriveIoBuf,
+                    ) -> #krate::__support::monoio::BufResult<usize, __DeriveIoBuf>",
+                ),
+                None,
+            ),
+            rented(
+                method(
+                    "readv",
+                    Receiver::Mut,
+                    "buf",
+                    "async fn readv<__DeriveIoBuf: #krate::__support::monoio::buf::IoVecBufMut>(
+                        &mut self,
+                        buf: __DeriveIoBuf,
+                    ) -> #krate::__support::monoio::BufResult<usize, __DeriveIoBuf>",
+                ),
+                None,
+            ),
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const ASYNC_WRITE_RENT: Derive = Derive {
+    attr: "write",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::__support::monoio::io::AsyncWriteRent",
+        param: None,
+        assoc: &[],
+        methods: &[
+            rented(
+                method(
+                    "write",
+                    Receiver::Mut,
+                    "buf",
+                    "async fn write<__DeriveIoBuf: #krate::__support::monoio::buf::IoBuf>(
+                        &mut self,
+                        buf: __DeriveIoBuf,
+                    ) -> #krate::__support::monoio::BufResult<usize, __DeriveIoBuf>",
+                ),
+                Some(Inspect::Rent),
+            ),
+            rented(
+                method(
+                    "writev",
+                    Receiver::Mut,
+                    "buf_vec",
+                    "async fn writev<__DeriveIoBuf: #krate::__support::monoio::buf::IoVecBuf>(
+                        &mut self,
+                        buf_vec: __DeriveIoBuf,
+                    ) -> #krate::__support::monoio::BufResult<usize, __DeriveIoBuf>",
+                ),
+                None,
+            ),
+            Method {
+                awaited: true,
+                ..hooked(
+                    method(
+                        "flush",
+                        Receiver::Mut,
+                        "",
+                        "async fn flush(&mut self) -> ::std::io::Result<()>",
+                    ),
+                    Output::Io,
+                    None,
+                )
+            },
+            Method {
+                awaited: true,
+                ..hooked(
+                    method(
+                        "shutdown",
+                        Receiver::Mut,
+                        "",
+                        "async fn shutdown(&mut self) -> ::std::io::Result<()>",
+                    ),
+                    Output::Io,
+                    None,
+                )
+            },
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+/// A method of an `embedded-io` trait, which returns `Self::Error`.
+const fn embedded(method: Method) -> Method {
+    Method {
+        output: Output::Embedded,
+        ..method
+    }
+}
+
+/// A method of an `embedded-io-async` trait.
+const fn awaited(method: Method) -> Method {
+    Method {
+        awaited: true,
+        ..embedded(method)
+    }
+}
+
+pub(crate) const EMBEDDED_READ: Derive = Derive {
+    attr: "read",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::__support::embedded_io::Read",
+        param: None,
+        assoc: &[],
+        methods: &[embedded(method(
+            "read",
+            Receiver::Mut,
+            "buf",
+            "fn read(&mut self, buf: &mut [u8]) -> ::core::result::Result<usize, Self::Error>",
+        ))],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const EMBEDDED_BUF_READ: Derive = Derive {
+    attr: "read",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::__support::embedded_io::BufRead",
+        param: None,
+        assoc: &[],
+        methods: &[
+            embedded(method(
+                "fill_buf",
+                Receiver::Mut,
+                "",
+                "fn fill_buf(&mut self) -> ::core::result::Result<&[u8], Self::Error>",
+            )),
+            method(
+                "consume",
+                Receiver::Mut,
+                "amt",
+                "fn consume(&mut self, amt: usize)",
+            ),
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const EMBEDDED_WRITE: Derive = Derive {
+    attr: "write",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::__support::embedded_io::Write",
+        param: None,
+        assoc: &[],
+        methods: &[
+            embedded(method(
+                "write",
+                Receiver::Mut,
+                "buf",
+                "fn write(&mut self, buf: &[u8]) -> ::core::result::Result<usize, Self::Error>",
+            )),
+            embedded(method(
+                "flush",
+                Receiver::Mut,
+                "",
+                "fn flush(&mut self) -> ::core::result::Result<(), Self::Error>",
+            )),
+            embedded(method(
+                "write_all",
+                Receiver::Mut,
+                "buf",
+                "fn write_all(&mut self, buf: &[u8]) -> ::core::result::Result<(), Self::Error>",
+            )),
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const EMBEDDED_SEEK: Derive = Derive {
+    attr: "seek",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::__support::embedded_io::Seek",
+        param: None,
+        assoc: &[],
+        methods: &[
+            embedded(method(
+                "seek",
+                Receiver::Mut,
+                "pos",
+                "fn seek(
+                    &mut self,
+                    pos: #krate::__support::embedded_io::SeekFrom,
+                ) -> ::core::result::Result<u64, Self::Error>",
+            )),
+            embedded(method(
+                "rewind",
+                Receiver::Mut,
+                "",
+                "fn rewind(&mut self) -> ::core::result::Result<(), Self::Error>",
+            )),
+            embedded(method(
+                "stream_position",
+                Receiver::Mut,
+                "",
+                "fn stream_position(&mut self) -> ::core::result::Result<u64, Self::Error>",
+            )),
+            embedded(method(
+                "seek_relative",
+                Receiver::Mut,
+                "offset",
+                "fn seek_relative(&mut self, offset: i64) -> ::core::result::Result<(), Self::Error>",
+            )),
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const EMBEDDED_ASYNC_READ: Derive = Derive {
+    attr: "read",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::__support::embedded_io_async::Read",
+        param: None,
+        assoc: &[],
+        methods: &[awaited(method(
+            "read",
+            Receiver::Mut,
+            "buf",
+            "async fn read(&mut self, buf: &mut [u8]) -> ::core::result::Result<usize, Self::Error>",
+        ))],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const EMBEDDED_ASYNC_BUF_READ: Derive = Derive {
+    attr: "read",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::__support::embedded_io_async::BufRead",
+        param: None,
+        assoc: &[],
+        methods: &[
+            awaited(method(
+                "fill_buf",
+                Receiver::Mut,
+                "",
+                "async fn fill_buf(&mut self) -> ::core::result::Result<&[u8], Self::Error>",
+            )),
+            method(
+                "consume",
+                Receiver::Mut,
+                "amt",
+                "fn consume(&mut self, amt: usize)",
+            ),
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const EMBEDDED_ASYNC_WRITE: Derive = Derive {
+    attr: "write",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::__support::embedded_io_async::Write",
+        param: None,
+        assoc: &[],
+        methods: &[
+            awaited(method(
+                "write",
+                Receiver::Mut,
+                "buf",
+                "async fn write(&mut self, buf: &[u8]) -> ::core::result::Result<usize, Self::Error>",
+            )),
+            awaited(method(
+                "flush",
+                Receiver::Mut,
+                "",
+                "async fn flush(&mut self) -> ::core::result::Result<(), Self::Error>",
+            )),
+            awaited(method(
+                "write_all",
+                Receiver::Mut,
+                "buf",
+                "async fn write_all(&mut self, buf: &[u8]) -> ::core::result::Result<(), Self::Error>",
+            )),
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+pub(crate) const EMBEDDED_ASYNC_SEEK: Derive = Derive {
+    attr: "seek",
+    buffer: None,
+    impls: &[Impl {
+        cfg: None,
+        path: "#krate::__support::embedded_io_async::Seek",
+        param: None,
+        assoc: &[],
+        methods: &[
+            awaited(method(
+                "seek",
+                Receiver::Mut,
+                "pos",
+                "async fn seek(
+                    &mut self,
+                    pos: #krate::__support::embedded_io::SeekFrom,
+                ) -> ::core::result::Result<u64, Self::Error>",
+            )),
+            awaited(method(
+                "rewind",
+                Receiver::Mut,
+                "",
+                "async fn rewind(&mut self) -> ::core::result::Result<(), Self::Error>",
+            )),
+            awaited(method(
+                "stream_position",
+                Receiver::Mut,
+                "",
+                "async fn stream_position(&mut self) -> ::core::result::Result<u64, Self::Error>",
+            )),
+        ],
+        raw: &[],
+    }],
+    flush_on_drop: None,
+};
+
+/// The roles of `Method::role`.
+const ROLES: &[&str] = &["read", "write"];
+
+const DERIVES: &[&Derive] = &[
+    &READ,
+    &BUF_READ,
+    &WRITE,
+    &ASYNC_READ,
+    &ASYNC_BUF_READ,
+    &ASYNC_WRITE,
+    &FMT_WRITE,
+    &POSITIONAL_IO,
+    &FILE_EXT,
+    &AS_FILE_DESCRIPTOR,
+    &AS_SOCKET_DESCRIPTOR,
+    &MAYBE_AS_FD,
+    &AS_READ_WRITE_FDS,
+    &INTO_FILE_DESCRIPTOR,
+    &STREAM,
+    &SINK,
+    &HYPER_READ,
+    &HYPER_WRITE,
+    &ASYNC_READ_RENT,
+    &ASYNC_WRITE_RENT,
+    &MIO_SOURCE,
+    &SOCKET_ADDRS,
+    &SOCKET_CONTROL,
+    &LISTENER,
+    &EMBEDDED_READ,
+    &EMBEDDED_BUF_READ,
+    &EMBEDDED_WRITE,
+    &EMBEDDED_SEEK,
+    &EMBEDDED_ASYNC_READ,
+    &EMBEDDED_ASYNC_BUF_READ,
+    &EMBEDDED_ASYNC_WRITE,
+    &EMBEDDED_ASYNC_SEEK,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Delegate to the trait impl of the target.
+    Plain,
+    /// Delegate via `AsRef`/`AsMut`.
+    AsRef,
+    /// Delegate via `Deref`/`DerefMut`.
+    Deref,
+    /// Delegate to inherent methods of the same name.
+    Duck,
+    /// Delegate to a `mio::unix::SourceFd` of the raw descriptor.
+    SourceFd,
+    /// Delegate to the raw descriptor of a `#[descriptor(unsafe(raw))]` field.
+    Raw,
+    /// There is no delegate, so the method returns its `absent` result.
+    Absent,
+}
+
+/// The delegation target for one case of the type.
+struct Delegate<'a> {
+    /// The cfg attributes of the enum variant, if any.
+    cfgs: TokenStream,
+    /// The case path and annotated field, or `None` to delegate to `self`.
+    field: Option<(TokenStream, &'a Field)>,
+    /// The case pattern without bindings, ie: `Self::Variant { .. }`.
+    variant: TokenStream,
+    /// The enum variant name, if any.
+    variant_name: Option<String>,
+    mode: Mode,
+    overrides: Vec<(String, TokenStream)>,
+    /// True if the case has a buffer field for this derive.
+    buffered: bool,
+    /// `#[write(flush_on_drop)]`, with the optional error sink.
+    flush_on_drop: Option<Option<TokenStream>>,
+    /// `#[read(inspect = path)]`
+    inspect: Option<TokenStream>,
+    /// `#[read(map_err = path)]`
+    map_err: Option<TokenStream>,
+    /// `#[fmt_write(from_io)]`, with the optional member that stores the last
+    /// `io::Error`.
+    from_io: Option<Option<TokenStream>>,
+    /// `#[descriptor(from)]`
+    from: bool,
+    /// `#[descriptor(unsafe(raw))]` or `#[descriptor(unsafe(raw_via = method))]`
+    raw: Option<Option<TokenStream>>,
+    /// The case built from `__derive_io_value` for methods that rebuild the
+    /// delegate, if the case has no other fields.
+    rebuild: Option<TokenStream>,
+}
+
+impl Delegate<'_> {
+    fn is_pinned(&self) -> bool {
+        self.field
+            .as_ref()
+            .is_some_and(|(_, field)| field.attrs.iter().any(|attr| attr.name == "pin"))
+    }
+}
+
+/// The options of a `#[read(...)]`-style attribute.
+struct Options {
+    mode: Mode,
+    overrides: Vec<(String, TokenStream)>,
+    /// `#[write(buffered)]`
+    buffered: bool,
+    /// `#[write(flush_on_drop)]` or `#[write(flush_on_drop = sink)]`
+    flush_on_drop: Option<Option<TokenStream>>,
+    /// `#[read(inspect = path)]`
+    inspect: Option<TokenStream>,
+    /// `#[read(map_err = path)]`
+    map_err: Option<TokenStream>,
+    /// `#[fmt_write(from_io)]` or `#[fmt_write(from_io = member)]`
+    from_io: Option<Option<TokenStream>>,
+    /// `#[descriptor(from)]`
+    from: bool,
+    /// `#[descriptor(unsafe(raw))]` or `#[descriptor(unsafe(raw_via = method))]`
+    raw: Option<Option<TokenStream>>,
+}
+
+/// Parses the options of a `#[read(...)]`-style attribute.
+fn parse_options(derive: &Derive, attr: &Attr, pinned: bool) -> Result<Options, Error> {
+    let mut mode = Mode::Plain;
+    let mut source_fd = false;
+    let mut none = false;
+    let mut from = false;
+    let mut raw = None;
+    let mut overrides = vec![];
+    let mut buffered = false;
+    let mut flush_on_drop = None;
+    let mut inspect = None;
+    let mut map_err = None;
+    let mut from_io = None;
+    for meta in attr.meta_with_unsafe()? {
+        let key = meta.key.to_string();
+        // A raw descriptor is borrowed without checking that it is open, so
+        // the attribute asserts that it is with `unsafe(...)`
+        let raw_key = matches!(key.as_str(), "raw" | "raw_via")
+            && any_method(derive.attr, |m| m.from_raw.is_some());
+        if let (Some(span), false) = (meta.unsafety, raw_key) {
+            return Err(Error::new(
+                format!("Invalid #[{}] attribute, unexpected `unsafe`", derive.attr),
+                span,
+            ));
+        }
+        if raw_key && meta.unsafety.is_none() {
+            return Err(Error::new(
+                format!(
+                    "Invalid #[{}] attribute, `{key}` must be marked `unsafe`, ie: `unsafe({key})`, \
+                    as the descriptor must stay open while the type is borrowed",
+                    derive.attr
+                ),
+                meta.key.span(),
+            ));
+        }
+        match (key.as_str(), meta.value) {
+            ("as_ref" | "deref" | "duck", None) => {
+                if mode != Mode::Plain {
+                    return Err(Error::new(
+                        format!(
+                            "Invalid #[{}] attribute, only one of `as_ref`, `deref` or `duck` may be specified",
+                            derive.attr
+                        ),
+                        meta.key.span(),
+                    ));
+                }
+                mode = match key.as_str() {
+                    "as_ref" => Mode::AsRef,
+                    "deref" => Mode::Deref,
+                    _ => Mode::Duck,
+                };
+            }
+            // Other derives use the descriptor of the field as usual
+            ("source_fd", None) if any_method(derive.attr, |m| m.name == "register") => {
+                source_fd = true
+            }
+            // Other derives use the field as usual
+            ("none", None) if any_method(derive.attr, |m| m.absent.is_some()) => none = true,
+            // Other derives use the first field as usual
+            ("read" | "write", None) if any_method(derive.attr, |m| m.role.is_some()) => {}
+            // The accepted stream's variant, used by `listener`
+            ("stream", Some(_)) if is_method(derive.attr, "poll_accept") => {}
+            ("from", None) if any_method(derive.attr, |m| m.receiver == Receiver::Owned) => {
+                from = true
+            }
+            (key @ ("raw" | "raw_via"), value)
+                if (key == "raw") == value.is_none()
+                    && any_method(derive.attr, |m| m.from_raw.is_some()) =>
+            {
+                if raw.is_some() {
+                    return Err(Error::new(
+                        format!(
+                            "Invalid #[{}] attribute, only one of `raw` or `raw_via` may be specified",
+                            derive.attr
+                        ),
+                        meta.key.span(),
+                    ));
+                }
+                raw = Some(value)
+            }
+            ("buffered", None) if derive.buffer == Some(Buffer::Write) => buffered = true,
+            ("flush_on_drop", value) if derive.flush_on_drop.is_some() => {
+                flush_on_drop = Some(value)
+            }
+            ("inspect", Some(value)) if any_method(derive.attr, |m| m.inspect.is_some()) => {
+                inspect = Some(value)
+            }
+            ("from_io", value) if any_method(derive.attr, |m| m.from_io.is_some()) => {
+                from_io = Some(value)
+            }
+            ("map_err", Some(value)) if any_method(derive.attr, |m| m.output != Output::None) => {
+                map_err = Some(value)
+            }
+            (key, Some(value)) if is_method(derive.attr, key) => {
+                overrides.push((key.to_string(), value));
+            }
+            _ => {
+                return Err(Error::new(
+                    format!(
+                        "Invalid #[{}] attribute, unknown option `{key}`",
+                        derive.attr
+                    ),
+                    meta.key.span(),
+                ))
+            }
+        }
+    }
+    let mio_source = derive
+        .impls
+        .iter()
+        .flat_map(|imp| imp.methods)
+        .any(|method| method.name == "register");
+    if source_fd && mode != Mode::Plain {
+        return Err(Error::new(
+            format!(
+                "Invalid #[{}] attribute, `source_fd` cannot be combined with `as_ref`, `deref` or `duck`",
+                derive.attr
+            ),
+            attr.span,
+        ));
+    }
+    if raw.is_some() && (source_fd || mode != Mode::Plain) {
+        return Err(Error::new(
+            format!(
+                "Invalid #[{}] attribute, `raw` and `raw_via` cannot be combined with `as_ref`, `deref`, `duck` or `source_fd`",
+                derive.attr
+            ),
+            attr.span,
+        ));
+    }
+    // mio registers the raw descriptor with `SourceFd`
+    if (source_fd || raw.is_some()) && mio_source {
+        mode = Mode::SourceFd;
+    } else if raw.is_some() {
+        mode = Mode::Raw;
+    }
+    if none && (mode != Mode::Plain || !overrides.is_empty()) {
+        return Err(Error::new(
+            format!(
+                "Invalid #[{}] attribute, `none` cannot be combined with other options",
+                derive.attr
+            ),
+            attr.span,
+        ));
+    }
+    if none && has_absent(derive) {
+        mode = Mode::Absent;
+    }
+    let owned = derive
+        .impls
+        .iter()
+        .flat_map(|imp| imp.methods)
+        .any(|method| method.receiver == Receiver::Owned);
+    if owned && matches!(mode, Mode::AsRef | Mode::Deref | Mode::Duck) {
+        return Err(Error::new(
+            format!(
+                "#[{}] fields are moved out, so `as_ref`, `deref` and `duck` are not supported",
+                derive.attr
+            ),
+            attr.span,
+        ));
+    }
+    if owned && raw.is_some() {
+        return Err(Error::new(
+            format!(
+                "#[{}(unsafe(raw))] fields do not own the descriptor, so it cannot be moved out",
+                derive.attr
+            ),
+            attr.span,
+        ));
+    }
+    let from_raw = derive
+        .impls
+        .iter()
+        .flat_map(|imp| imp.methods)
+        .any(|method| method.from_raw.is_some());
+    if raw.is_some() && !from_raw {
+        return Err(Error::new(
+            format!(
+                "#[{}(unsafe(raw))] fields are not supported by `{}`",
+                derive.attr,
+                derive.impls[0].path.replace("#krate", "derive_io")
+            ),
+            attr.span,
+        ));
+    }
+    let assoc = derive.impls.iter().any(|imp| !imp.assoc.is_empty());
+    if assoc && matches!(mode, Mode::AsRef | Mode::Duck) {
+        return Err(Error::new(
+            format!(
+                "#[{}] does not support `as_ref` or `duck`, as the associated types cannot be inferred",
+                derive.attr
+            ),
+            attr.span,
+        ));
+    }
+    if from_io.is_some() && mode == Mode::Duck {
+        return Err(Error::new(
+            format!(
+                "Invalid #[{}] attribute, `from_io` cannot be combined with `duck`",
+                derive.attr
+            ),
+            attr.span,
+        ));
+    }
+    if pinned && matches!(mode, Mode::AsRef | Mode::Deref) {
+        return Err(Error::new(
+            "#[pin] fields cannot be delegated with `as_ref` or `deref`",
+            attr.span,
+        ));
+    }
+    let pinned_trait = derive
+        .impls
+        .iter()
+        .flat_map(|imp| imp.methods)
+        .any(|method| method.receiver == Receiver::Pin);
+    if pinned && flush_on_drop.is_some() && !pinned_trait {
+        return Err(Error::new(
+            format!(
+                "#[pin] fields cannot be flushed on drop with `{}`",
+                derive.impls[0].path.trim_start_matches(':')
+            ),
+            attr.span,
+        ));
+    }
+    Ok(Options {
+        mode,
+        overrides,
+        buffered,
+        flush_on_drop,
+        inspect,
+        map_err,
+        from_io,
+        from,
+        raw,
+    })
+}
+
+/// Returns true if `name` is a method of any trait derived using `attr`.
+fn is_method(attr: &str, name: &str) -> bool {
+    DERIVES
+        .iter()
+        .filter(|derive| derive.attr == attr)
+        .flat_map(|derive| derive.impls)
+        .flat_map(|imp| {
+            imp.methods
+                .iter()
+                .chain(imp.raw.iter().map(|raw| &raw.method))
+        })
+        .any(|method| method.name == name || method.duck_via == Some(name))
+}
+
+/// Returns true if the delegate of the derive is optional.
+fn has_absent(derive: &Derive) -> bool {
+    derive
+        .impls
+        .iter()
+        .flat_map(|imp| imp.methods)
+        .any(|method| method.absent.is_some())
+}
+
+/// The roles of a field's attribute, ie: `read` for `#[descriptor(read)]`.
+fn roles(attr: &Attr) -> Vec<String> {
+    let meta = attr.meta_with_unsafe().unwrap_or_default();
+    meta.iter()
+        .map(|meta| meta.key.to_string())
+        .filter(|key| ROLES.contains(&key.as_str()))
+        .collect()
+}
+
+/// Returns true if a variant's `#[descriptor(...)]` attribute only has the
+/// `kind` of `FromDescriptor`, which other derives ignore.
+fn is_kind_only(attr: &Attr) -> bool {
+    attr.meta().is_ok_and(|meta| {
+        !meta.is_empty() && meta.iter().all(|meta| meta.key.to_string() == "kind")
+    })
+}
+
+/// Returns true if any method of a trait derived using `attr` matches.
+fn any_method(attr: &str, f: impl Fn(&Method) -> bool) -> bool {
+    DERIVES
+        .iter()
+        .filter(|derive| derive.attr == attr)
+        .flat_map(|derive| derive.impls)
+        .flat_map(|imp| imp.methods)
+        .any(f)
+}
+
+/// Finds the delegate for each case of the type: the first field marked with
+/// the derive's attribute (with the given role, if any) or, if no field is
+/// marked, the type itself.
+fn find_delegates<'a>(
+    derive: &Derive,
+    input: &'a Input,
+    role: Option<&str>,
+) -> Result<Vec<Delegate<'a>>, Error> {
+    let marked = |attrs: &'a [Attr]| attrs.iter().find(|attr| attr.name == derive.attr);
+    // Fields with the role take precedence over fields without a role, which
+    // delegate every role
+    let marked_role = |field: &'a Field, explicit: bool| {
+        (field.attrs.iter()).find(|attr| {
+            let roles = roles(attr);
+            attr.name == derive.attr
+                && match role {
+                    Some(role) => {
+                        roles.iter().any(|key| key == role) || (!explicit && roles.is_empty())
+                    }
+                    None => !explicit,
+                }
+        })
+    };
+    let marked_field = |field: &'a Field| marked_role(field, false);
+    let missing = match role {
+        Some(role) => format!("No #[{0}] or #[{0}({role})] field found", derive.attr),
+        None => format!("No #[{}] field found", derive.attr),
+    };
+
+    if !input.cases.iter().any(|case| {
+        (has_absent(derive) && marked(&case.attrs).is_some())
+            || case
+                .fields
+                .iter()
+                .any(|field| marked_field(field).is_some())
+    }) {
+        let Some(attr) = marked(&input.attrs) else {
+            return Err(Error::new(missing, input.name.span()));
+        };
+        let options = parse_options(derive, attr, false)?;
+        if options.buffered {
+            return Err(Error::new(
+                format!("#[{}(buffered)] requires a field", derive.attr),
+                attr.span,
+            ));
+        }
+        if matches!(options.raw, Some(None)) {
+            return Err(Error::new(
+                format!("#[{}(unsafe(raw))] requires a field", derive.attr),
+                attr.span,
+            ));
+        }
+        if derive.impls.iter().any(|imp| !imp.assoc.is_empty()) {
+            return Err(Error::new(
+                format!("#[{}] requires a field", derive.attr),
+                attr.span,
+            ));
+        }
+        return Ok(vec![Delegate {
+            cfgs: TokenStream::new(),
+            field: None,
+            variant: TokenStream::new(),
+            variant_name: None,
+            mode: options.mode,
+            overrides: options.overrides,
+            buffered: false,
+            flush_on_drop: options.flush_on_drop,
+            inspect: options.inspect,
+            map_err: options.map_err,
+            from_io: options.from_io,
+            from: options.from,
+            raw: options.raw,
+            rebuild: None,
+        }]);
+    }
+
+    let mut delegates = vec![];
+    for case in &input.cases {
+        let cfgs: TokenStream = case
+            .attrs
+            .iter()
+            .filter(|attr| attr.name == "cfg")
+            .flat_map(|attr| attr.tokens.clone())
+            .collect();
+        // Cases without a delegate are allowed if it is optional, and may be
+        // marked explicitly with `#[descriptor(none)]` on the variant (which
+        // may also have the `kind` of `FromDescriptor`)
+        let absent = Delegate {
+            cfgs: cfgs.clone(),
+            field: None,
+            variant: template("#path { .. }", &[("path", case.path.clone())]),
+            variant_name: case.name.clone(),
+            mode: Mode::Absent,
+            overrides: vec![],
+            buffered: false,
+            flush_on_drop: None,
+            inspect: None,
+            map_err: None,
+            from_io: None,
+            from: false,
+            raw: None,
+            rebuild: None,
+        };
+        let variant_attr = marked(&case.attrs).filter(|_| has_absent(derive));
+        if let Some(attr) = variant_attr.filter(|attr| !is_kind_only(attr)) {
+            if parse_options(derive, attr, false)?.mode != Mode::Absent {
+                return Err(Error::new(
+                    format!(
+                        "#[{}] on a variant only supports `none`, or `kind` for `FromDescriptor`",
+                        derive.attr
+                    ),
+                    attr.span,
+                ));
+            }
+            delegates.push(absent);
+            continue;
+        }
+        let Some((field, attr)) = (case.fields.iter())
+            .find_map(|field| Some((field, marked_role(field, true)?)))
+            .or_else(|| (case.fields.iter()).find_map(|field| Some((field, marked_field(field)?))))
+        else {
+            if has_absent(derive) {
+                delegates.push(absent);
+                continue;
+            }
+            return Err(Error::new(missing, case.span));
+        };
+        let pinned = field.attrs.iter().any(|attr| attr.name == "pin");
+        let options = parse_options(derive, attr, pinned)?;
+        let buffer = find_buffer(derive, case)?;
+        match (derive.buffer, &buffer) {
+            (Some(Buffer::Write), None) if options.buffered => {
+                return Err(Error::new(
+                    "#[write(buffered)] requires a #[buffer(write)] field",
+                    attr.span,
+                ));
+            }
+            (Some(Buffer::Write), Some(_)) if !options.buffered => {
+                return Err(Error::new(
+                    "#[buffer(write)] requires #[write(buffered)]",
+                    attr.span,
+                ));
+            }
+            _ => {}
+        }
+        let mut bindings = template(
+            "#member: __derive_io_field,",
+            &[("member", field.member.to_tokens())],
+        );
+        if let Some(buffer) = &buffer {
+            bindings.extend(template(
+                "#member: __derive_io_buffer,",
+                &[("member", buffer.member.to_tokens())],
+            ));
+        }
+        if let Some(Some(member)) = &options.from_io {
+            bindings.extend(template(
+                "#member: __derive_io_error,",
+                &[("member", member.clone())],
+            ));
+        }
+        let pattern = template(
+            "#path { #bindings .. }",
+            &[("path", case.path.clone()), ("bindings", bindings)],
+        );
+        delegates.push(Delegate {
+            cfgs,
+            field: Some((pattern, field)),
+            variant: template("#path { .. }", &[("path", case.path.clone())]),
+            variant_name: case.name.clone(),
+            mode: options.mode,
+            overrides: options.overrides,
+            buffered: buffer.is_some(),
+            flush_on_drop: options.flush_on_drop,
+            inspect: options.inspect,
+            map_err: options.map_err,
+            from_io: options.from_io,
+            from: options.from,
+            raw: options.raw,
+            rebuild: (case.fields.len() == 1).then(|| {
+                template(
+                    "#path { #member: __derive_io_value }",
+                    &[
+                        ("path", case.path.clone()),
+                        ("member", field.member.to_tokens()),
+                    ],
+                )
+            }),
+        });
+    }
+
+    // There is a single `Drop` impl, so every case must agree
+    let key = |delegate: &Delegate| {
+        (delegate.flush_on_drop.as_ref()).map(|sink| sink.as_ref().map(ToString::to_string))
+    };
+    if let Some((delegate, case)) = delegates
+        .iter()
+        .zip(&input.cases)
+        .find(|(delegate, _)| key(delegate) != key(&delegates[0]))
+    {
+        let span = (delegate.field.as_ref())
+            .and_then(|(_, field)| marked_field(field))
+            .map_or(case.span, |attr| attr.span);
+        return Err(Error::new(
+            format!(
+                "#[{}(flush_on_drop)] must be the same for every #[{}] field",
+                derive.attr, derive.attr
+            ),
+            span,
+        ));
+    }
+
+    Ok(delegates)
+}
+
+/// Finds the buffer field of the given kind for a case, if any.
+fn find_buffer<'a>(derive: &Derive, case: &'a Case) -> Result<Option<&'a Field>, Error> {
+    let mut found = None;
+    for field in &case.fields {
+        for attr in field.attrs.iter().filter(|attr| attr.name == "buffer") {
+            let kind = match attr.args.as_ref().map(|args| args.stream().to_string()) {
+                None => Buffer::Read,
+                Some(args) if args == "read" => Buffer::Read,
+                Some(args) if args == "write" => Buffer::Write,
+                _ => {
+                    return Err(Error::new(
+                        "Invalid #[buffer] attribute, expected #[buffer], #[buffer(read)] or #[buffer(write)]",
+                        attr.span,
+                    ))
+                }
+            };
+            if Some(kind) != derive.buffer {
+                continue;
+            }
+            if found.is_some() {
+                return Err(Error::new(
+                    "Only one buffer of each kind may be specified",
+                    attr.span,
+                ));
+            }
+            found = Some(field);
+        }
+    }
+    Ok(found)
+}
+
+/// Generates the body of a buffered method for a single case.
+fn buffered_call(
+    krate: &TokenStream,
+    imp: &Impl,
+    template_: &str,
+    delegate: &Delegate,
+) -> TokenStream {
+    let mut vars = vec![
+        ("krate", krate.clone()),
+        ("buffer", "__derive_io_buffer".parse().unwrap()),
+    ];
+    for raw in imp.raw {
+        // Reborrow the field, as the raw call may be made more than once
+        let call = template(
+            "{ let __derive_io_field = &mut *__derive_io_field; #call }",
+            &[("call", call(krate, raw.path, &raw.method, delegate, false))],
+        );
+        vars.push((raw.method.name, call));
+    }
+    template(template_, &vars)
+}
+
+/// Generates the delegated call for a method of a single case. If `reborrow`
+/// is true, `self` remains available after the call.
+fn call(
+    krate: &TokenStream,
+    path: &str,
+    method: &Method,
+    delegate: &Delegate,
+    reborrow: bool,
+) -> TokenStream {
+    if delegate.mode == Mode::Absent {
+        return method.absent.unwrap().parse().unwrap();
+    }
+    let this = match method.receiver {
+        _ if delegate.field.is_some() => "__derive_io_field",
+        // `from_io` needs `self` after the call to store the error
+        Receiver::Mut if reborrow || delegate.from_io.is_some() => "&mut *self",
+        Receiver::Pin if reborrow => "self.as_mut()",
+        _ => "self",
+    };
+    let (as_ref, deref) = if method.receiver == Receiver::Ref {
+        (
+            "::core::convert::AsRef::as_ref",
+            "::core::ops::Deref::deref",
+        )
+    } else {
+        (
+            "::core::convert::AsMut::as_mut",
+            "::core::ops::DerefMut::deref_mut",
+        )
+    };
+    let unwrap = match delegate.mode {
+        Mode::AsRef => as_ref,
+        Mode::Deref => deref,
+        Mode::Plain | Mode::Duck | Mode::SourceFd | Mode::Raw | Mode::Absent => "",
+    };
+
+    let raw_fd = match &delegate.raw {
+        None => format!("::std::os::fd::AsRawFd::as_raw_fd(&*{this})")
+            .parse()
+            .unwrap(),
+        Some(None) => format!("*{this}").parse().unwrap(),
+        Some(Some(via)) => template(&format!("{this}.#via()"), &[("via", via.clone())]),
+    };
+    let overridden = delegate.overrides.iter().any(|(key, _)| key == method.name);
+    if delegate.mode == Mode::Raw && !overridden {
+        let Some(from_raw) = method.from_raw else {
+            return "::core::compile_error!(\"#[descriptor(unsafe(raw))] is only supported on unix\")"
+                .parse()
+                .unwrap();
+        };
+        let field = delegate
+            .field
+            .as_ref()
+            .map(|(_, field)| field.member.to_tokens());
+        let field = match (&delegate.variant_name, field) {
+            (Some(variant), Some(field)) => format!("{variant}.{field}"),
+            (None, Some(field)) => field.to_string(),
+            (_, None) => String::new(),
+        };
+        let message = format!(
+            "derive-io: the raw descriptor of field `{field}` is -1, which cannot be borrowed"
+        );
+        return template(
+            from_raw,
+            &[
+                ("fd", raw_fd),
+                ("message", format!("{message:?}").parse().unwrap()),
+            ],
+        );
+    }
+
+    let target = match (method.receiver, &delegate.field) {
+        _ if delegate.mode == Mode::SourceFd => {
+            "&mut #krate::__support::mio::unix::SourceFd(&#raw_fd)".to_string()
+        }
+        _ if unwrap.is_empty()
+            && (method.receiver != Receiver::Pin || delegate.field.is_none()) =>
+        {
+            this.to_string()
+        }
+        (Receiver::Ref | Receiver::Mut | Receiver::Owned, _) => format!("{unwrap}({this})"),
+        // NOTE: as_ref and deref require Unpin for safety
+        (Receiver::Pin, None) => {
+            format!("::core::pin::Pin::new({unwrap}(::core::pin::Pin::get_mut({this})))")
+        }
+        // SAFETY: `#[derive(PinProject)]` upholds the structural pinning
+        // guarantees for `#[pin]` fields.
+        (Receiver::Pin, Some(_)) if delegate.is_pinned() => format!(
+            "{{
+                #krate::__support::assert_pin_projected::<Self>();
+                unsafe {{ ::core::pin::Pin::new_unchecked({this}) }}
+            }}"
+        ),
+        (Receiver::Pin, Some(_)) if unwrap.is_empty() => format!("::core::pin::Pin::new({this})"),
+        (Receiver::Pin, Some(_)) => format!(
+            "::core::pin::Pin::new({unwrap}(::core::pin::Pin::get_mut(::core::pin::Pin::new({this}))))"
+        ),
+    };
+    let mut target = template(&target, &[("krate", krate.clone()), ("raw_fd", raw_fd)]);
+
+    let callable = |name: &str| -> TokenStream {
+        if let Some((_, value)) = delegate.overrides.iter().find(|(key, _)| key == name) {
+            return value.clone();
+        }
+        let (path, name) = match method.delegate {
+            Some(via) if name == method.name => via,
+            _ => (path, name),
+        };
+        let name = TokenStream::from(TokenTree::Ident(Ident::new(name, Span::call_site())));
+        match (delegate.mode, &delegate.field) {
+            (Mode::Duck, None) => template("Self::#name", &[("name", name)]),
+            (Mode::Duck, Some((_, field))) => {
+                template("<#ty>::#name", &[("ty", field.ty.clone()), ("name", name)])
+            }
+            _ => template(
+                &format!("{path}::#name"),
+                &[("krate", krate.clone()), ("name", name)],
+            ),
+        }
+    };
+
+    if let (Some(from_io), Some(error), false) = (method.from_io, &delegate.from_io, overridden) {
+        let store = match (error, &delegate.field) {
+            (None, _) => TokenStream::new(),
+            (Some(member), None) => template(
+                "self.#member = ::core::option::Option::Some(__derive_io_err);",
+                &[("member", member.clone())],
+            ),
+            (Some(_), Some(_)) => {
+                "*__derive_io_error = ::core::option::Option::Some(__derive_io_err);"
+                    .parse()
+                    .unwrap()
+            }
+        };
+        return template(
+            "match #call {
+                ::core::result::Result::Ok(()) => ::core::result::Result::Ok(()),
+                ::core::result::Result::Err(__derive_io_err) => {
+                    #store
+                    ::core::result::Result::Err(::core::fmt::Error)
+                }
+            }",
+            &[
+                ("call", template(from_io, &[("target", target)])),
+                ("store", store),
+            ],
+        );
+    }
+
+    if delegate.mode == Mode::Duck {
+        // Choose the correct pointer for the receiver via trait.
+        target = template(
+            "#krate::__support::DuckCoerce::duck_coerce(#target)",
+            &[("krate", krate.clone()), ("target", target)],
+        );
+        // Raw descriptors are derived from the borrowed descriptor.
+        if let Some(via) = method.duck_via {
+            return template(
+                &format!("{path}::{}(&#callable(#target))", method.name),
+                &[
+                    ("krate", krate.clone()),
+                    ("callable", callable(via)),
+                    ("target", target),
+                ],
+            );
+        }
+    }
+
+    let mut call = template(
+        "#callable(#target, #args)",
+        &[
+            ("callable", callable(method.name)),
+            ("target", target),
+            ("args", method.args.parse().unwrap()),
+        ],
+    );
+    if method.awaited {
+        call = template("#call.await", &[("call", call)]);
+    }
+    // A delegate of another type (or of another case) cannot be rebuilt
+    if method.rebuilds && (delegate.field.is_some() || delegate.mode != Mode::Duck) {
+        call = match (&delegate.rebuild, delegate.mode) {
+            (Some(rebuild), Mode::Plain | Mode::Duck) => template(
+                "#call.map(|__derive_io_value| #rebuild)",
+                &[("call", call), ("rebuild", rebuild.clone())],
+            ),
+            _ => template(
+                &format!(
+                    "{{
+                        {}
+                        ::core::result::Result::Err(#krate::__support::unsupported(#name))
+                    }}",
+                    if delegate.field.is_some() {
+                        "let _ = __derive_io_field;"
+                    } else {
+                        ""
+                    }
+                ),
+                &[
+                    ("krate", krate.clone()),
+                    (
+                        "name",
+                        TokenTree::Literal(Literal::string(method.name)).into(),
+                    ),
+                ],
+            ),
+        };
+    }
+    if let (Mode::Duck, Some(into)) = (delegate.mode, method.duck_into) {
+        call = template(
+            "#call.map(#into)",
+            &[("call", call), ("into", into.parse().unwrap())],
+        );
+    }
+    match &delegate.map_err {
+        Some(map_err) if method.output == Output::Rent => template(
+            "{ let (__derive_io_result, __derive_io_buf) = #call;
+            (__derive_io_result.map_err(#map_err), __derive_io_buf) }",
+            &[("call", call), ("map_err", map_err.clone())],
+        ),
+        Some(map_err) if method.output != Output::None => template(
+            "#call.map_err(#map_err)",
+            &[("call", call), ("map_err", map_err.clone())],
+        ),
+        _ => call,
+    }
+}
+
+/// The trait of the impl with its parameter and the given associated type
+/// bindings, ie: `Sink<__DeriveIoItem, Error = ...>`.
+fn trait_ref(imp: &Impl, bindings: &[String]) -> String {
+    let args = imp
+        .param
+        .iter()
+        .map(ToString::to_string)
+        .chain(bindings.iter().cloned())
+        .collect::<Vec<_>>();
+    if args.is_empty() {
+        imp.path.to_string()
+    } else {
+        format!("{}<{}>", imp.path, args.join(", "))
+    }
+}
+
+/// The type that a delegate's trait impl belongs to, if known.
+fn target_ty(delegate: &Delegate) -> Option<TokenStream> {
+    let (_, field) = delegate.field.as_ref()?;
+    match delegate.mode {
+        Mode::Plain => Some(field.ty.clone()),
+        Mode::Deref => Some(template(
+            "<#ty as ::core::ops::Deref>::Target",
+            &[("ty", field.ty.clone())],
+        )),
+        Mode::AsRef | Mode::Duck | Mode::SourceFd | Mode::Raw | Mode::Absent => None,
+    }
+}
+
+/// The delegate type whose trait impl provides the associated types of the
+/// impl: that of the first case that does not depend on a `#[cfg]`.
+fn assoc_source(delegates: &[Delegate]) -> Option<TokenStream> {
+    let delegate = delegates
+        .iter()
+        .find(|delegate| delegate.cfgs.is_empty())
+        .unwrap_or(&delegates[0]);
+    target_ty(delegate)
+}
+
+/// The where clause of the impl for the given delegates.
+fn bounds(
+    krate: &TokenStream,
+    input: &Input,
+    delegates: &[Delegate],
+    imp: &Impl,
+) -> Vec<TokenStream> {
+    let pinned_trait = imp
+        .methods
+        .iter()
+        .any(|method| method.receiver == Receiver::Pin);
+    // Traits with a parameter or associated types constrain every delegate,
+    // and the associated types of every delegate must match.
+    let generic_trait = imp.param.is_some() || !imp.assoc.is_empty();
+    let source = assoc_source(delegates).map(|ty| ty.to_string());
+
+    // Constrain each generic delegate type, requiring unpinned fields of
+    // pinned traits to be `Unpin`.
+    let mut bounds = vec![];
+    let mut seen = vec![];
+    for delegate in delegates {
+        let Some((_, field)) = delegate.field else {
+            continue;
+        };
+        if generic_trait {
+            let Some(ty) = target_ty(delegate) else {
+                continue;
+            };
+            let mut bindings = vec![];
+            if let Some(source) = source.as_ref().filter(|source| **source != ty.to_string()) {
+                let source_trait = trait_ref(imp, &[]);
+                for assoc in imp.assoc {
+                    bindings.push(format!("{assoc} = <{source} as {source_trait}>::{assoc}"));
+                }
+            }
+            bounds.push(template(
+                &format!("#ty: {}", trait_ref(imp, &bindings)),
+                &[("krate", krate.clone()), ("ty", ty.clone())],
+            ));
+            // `deref` re-pins the target, which must be `Unpin`
+            if pinned_trait && delegate.mode == Mode::Deref {
+                bounds.push(template("#ty: ::core::marker::Unpin", &[("ty", ty)]));
+            }
+        }
+        if !input.has_generic(&field.ty) {
+            continue;
+        }
+        if delegate.mode == Mode::Plain && !generic_trait {
+            // Buffered delegates only need the raw traits
+            let mut paths = vec![imp.path];
+            if delegate.buffered {
+                paths = imp.raw.iter().map(|raw| raw.path).collect();
+            }
+            // Delegates may implement the methods with another trait
+            if let Some((path, _)) = imp.methods.iter().find_map(|method| method.delegate) {
+                paths = vec![path];
+            }
+            for path in paths {
+                bounds.push(template(
+                    &format!("#ty: {path}"),
+                    &[("krate", krate.clone()), ("ty", field.ty.clone())],
+                ));
+            }
+        }
+        if delegate.mode == Mode::SourceFd && delegate.raw.is_none() {
+            bounds.push(template(
+                "#ty: ::std::os::fd::AsRawFd",
+                &[("ty", field.ty.clone())],
+            ));
+        }
+        if pinned_trait && !delegate.is_pinned() {
+            bounds.push(template(
+                "#ty: ::core::marker::Unpin",
+                &[("ty", field.ty.clone())],
+            ));
+        }
+    }
+    bounds.retain(|bound| {
+        let key = bound.to_string();
+        let new = !seen.contains(&key);
+        seen.push(key);
+        new
+    });
+    bounds.extend(input.where_clause.iter().cloned());
+    bounds
+}
+
+/// The type-level `#[io(...)]` hooks.
+#[derive(Default)]
+struct Hooks {
+    /// `#[io(on_pending = path)]`
+    on_pending: Option<TokenStream>,
+    /// `#[io(on_error = path)]`
+    on_error: Option<TokenStream>,
+    /// `#[io(trace)]`
+    trace: bool,
+    /// `#[io(context)]`
+    context: bool,
+    /// `#[io(transcript = path)]`
+    transcript: Option<TokenStream>,
+    /// The `#[io(transcript)]` field of the active case, as a `&TranscriptStream`
+    /// expression
+    transcript_stream: TokenStream,
+    /// `#[io(error = Type)]`
+    error: Option<TokenStream>,
+}
+
+/// Parses the `#[io(...)]` attributes of the type.
+fn parse_hooks(input: &Input) -> Result<Hooks, Error> {
+    let mut hooks = Hooks::default();
+    for attr in input.attrs.iter().filter(|attr| attr.name == "io") {
+        for meta in attr.meta()? {
+            let key = meta.key.to_string();
+            match (key.as_str(), meta.value) {
+                ("on_pending", Some(value)) => hooks.on_pending = Some(value),
+                ("on_error", Some(value)) => hooks.on_error = Some(value),
+                ("trace", None) => hooks.trace = true,
+                ("context", None) => hooks.context = true,
+                ("transcript", Some(value)) => hooks.transcript = Some(value),
+                ("error", Some(value)) => hooks.error = Some(value),
+                _ => {
+                    return Err(Error::new(
+                        format!("Invalid #[io] attribute, unknown option `{key}`"),
+                        meta.key.span(),
+                    ))
+                }
+            }
+        }
+    }
+
+    // Each case records its bytes through its own `#[io(transcript)]` field
+    let mut arms = TokenStream::new();
+    for case in &input.cases {
+        let mut found = None;
+        for field in &case.fields {
+            for attr in field.attrs.iter().filter(|attr| attr.name == "io") {
+                match &attr.meta()?[..] {
+                    [meta] if meta.key.to_string() == "transcript" && meta.value.is_none() => {}
+                    _ => {
+                        return Err(Error::new(
+                            "Invalid #[io] attribute on a field, expected #[io(transcript)]",
+                            attr.span,
+                        ))
+                    }
+                }
+                if hooks.transcript.is_none() {
+                    return Err(Error::new(
+                        "#[io(transcript)] requires #[io(transcript = <path>)] on the type",
+                        attr.span,
+                    ));
+                }
+                if found.is_some() {
+                    return Err(Error::new(
+                        "Only one #[io(transcript)] field may be specified",
+                        attr.span,
+                    ));
+                }
+                found = Some(field);
+            }
+        }
+        if hooks.transcript.is_none() {
+            continue;
+        }
+        let Some(field) = found else {
+            return Err(Error::new(
+                "#[io(transcript = <path>)] requires an #[io(transcript)] field of type `derive_io::TranscriptStream`",
+                case.span,
+            ));
+        };
+        let cfgs: TokenStream = case
+            .attrs
+            .iter()
+            .filter(|attr| attr.name == "cfg")
+            .flat_map(|attr| attr.tokens.clone())
+            .collect();
+        arms.extend(template(
+            "#cfgs #path { #member: __derive_io_stream, .. } => __derive_io_stream,",
+            &[
+                ("cfgs", cfgs),
+                ("path", case.path.clone()),
+                ("member", field.member.to_tokens()),
+            ],
+        ));
+    }
+    hooks.transcript_stream = template("match __derive_io_this { #arms }", &[("arms", arms)]);
+    Ok(hooks)
+}
+
+/// Returns true if any hook applies to the method.
+fn is_hooked(method: &Method, delegates: &[Delegate], hooks: &Hooks) -> bool {
+    match method.output {
+        Output::None | Output::Embedded => false,
+        _ if method.borrowed => hooks.context,
+        output => {
+            hooks.context
+                || hooks.trace
+                || hooks.on_error.is_some()
+                || (output == Output::Poll && hooks.on_pending.is_some())
+                || (method.inspect.is_some()
+                    && (hooks.transcript.is_some()
+                        || delegates.iter().any(|delegate| delegate.inspect.is_some())))
+        }
+    }
+}
+
+/// Wraps the body of a method to attach the error context and pass its result
+/// to the hooks, which are called with `&Self` once the delegated call has
+/// returned.
+fn hook(
+    krate: &TokenStream,
+    input: &Input,
+    method: &Method,
+    delegates: &[Delegate],
+    hooks: &Hooks,
+    body: TokenStream,
+) -> TokenStream {
+    // Calls `call` with the bytes read or written as `__derive_io_bytes`
+    let with_bytes = |call: TokenStream| {
+        match method.inspect {
+        Some(Inspect::IoSlices) => template(
+            "#krate::__support::inspect_io_slices(bufs, *__derive_io_n, |__derive_io_bytes| {
+                #call
+            })",
+            &[("krate", krate.clone()), ("call", call)],
+        ),
+        Some(Inspect::ReadBuf) => template(
+            "let __derive_io_bytes = &buf.filled()[__derive_io_filled..]; #call",
+            &[("call", call)],
+        ),
+        Some(Inspect::Rent) => template(
+            "let __derive_io_bytes = #krate::__support::rent_bytes(&__derive_io_result.1, *__derive_io_n);
+            #call",
+            &[("krate", krate.clone()), ("call", call)],
+        ),
+        _ => template(
+            "let __derive_io_bytes = &buf[..*__derive_io_n]; #call",
+            &[("call", call)],
+        ),
+    }
+    };
+    let inspect_call = |path: &TokenStream| {
+        with_bytes(template(
+            "(#path)(__derive_io_this, __derive_io_bytes)",
+            &[("path", path.clone())],
+        ))
+    };
+
+    let on_error = |err: &str| {
+        hooks.on_error.as_ref().map(|path| {
+            template(
+                &format!("(#path)(__derive_io_this, {err})"),
+                &[("path", path.clone())],
+            )
+        })
+    };
+
+    let mut inspect = TokenStream::new();
+    if let (Some(inspected), Some(path)) = (method.inspect, &hooks.transcript) {
+        let direction = match inspected.direction() {
+            Direction::Read => "Read",
+            Direction::Write => "Write",
+        };
+        // Transcript errors are reported to the `on_error` hook, if any
+        let report = on_error("&__derive_io_err").unwrap_or_else(|| {
+            template(
+                "#krate::__support::transcript_error(&__derive_io_err)",
+                &[("krate", krate.clone())],
+            )
+        });
+        let call = with_bytes(template(
+            &format!(
+                "if let ::core::result::Result::Err(__derive_io_err) =
+                    #krate::__support::record_transcript(
+                        #stream,
+                        || #path,
+                        #krate::Direction::{direction},
+                        __derive_io_bytes,
+                    )
+                {{
+                    #report;
+                }}"
+            ),
+            &[
+                ("krate", krate.clone()),
+                ("stream", hooks.transcript_stream.clone()),
+                ("path", path.clone()),
+                ("report", report),
+            ],
+        ));
+        inspect.extend(template("{ #call }", &[("call", call)]));
+    }
+    if method.inspect.is_some() && delegates.iter().any(|delegate| delegate.inspect.is_some()) {
+        let call = if let [Delegate {
+            field: None,
+            inspect: Some(path),
+            ..
+        }] = delegates
+        {
+            inspect_call(path)
+        } else {
+            // Each case may have its own inspect hook
+            let mut arms = TokenStream::new();
+            for delegate in delegates {
+                let call = delegate.inspect.as_ref().map(inspect_call);
+                arms.extend(template(
+                    "#cfgs #variant => { #call }",
+                    &[
+                        ("cfgs", delegate.cfgs.clone()),
+                        ("variant", delegate.variant.clone()),
+                        ("call", call.unwrap_or_default()),
+                    ],
+                ));
+            }
+            template("match __derive_io_this { #arms }", &[("arms", arms)])
+        };
+        inspect.extend(template("{ #call }", &[("call", call)]));
+    }
+    let filled = if method.inspect == Some(Inspect::ReadBuf) && (!inspect.is_empty() || hooks.trace)
+    {
+        "let __derive_io_filled = buf.filled().len();"
+    } else {
+        ""
+    };
+    let on_error = on_error("__derive_io_err");
+    let on_pending = hooks
+        .on_pending
+        .as_ref()
+        .map(|path| template("(#path)(__derive_io_this)", &[("path", path.clone())]));
+
+    let names = if hooks.trace || hooks.context {
+        names(delegates)
+    } else {
+        TokenStream::new()
+    };
+    let context = if hooks.context {
+        template(
+            &format!(
+                "let __derive_io_result = #krate::__support::AddContext::add_context(
+                    __derive_io_result,
+                    {:?},
+                    __derive_io_variant,
+                    __derive_io_field,
+                );",
+                input.name.to_string()
+            ),
+            &[("krate", krate.clone())],
+        )
+    } else {
+        TokenStream::new()
+    };
+    if method.borrowed {
+        return template(
+            "#names let __derive_io_result = { #body }; #context __derive_io_result",
+            &[("names", names), ("body", body), ("context", context)],
+        );
+    }
+    let trace = if hooks.trace {
+        trace(krate, input, method)
+    } else {
+        Default::default()
+    };
+
+    let arms = if method.output == Output::Poll {
+        "::core::task::Poll::Ready(::core::result::Result::Ok(__derive_io_n)) => { #trace_ok #inspect }
+        ::core::task::Poll::Ready(::core::result::Result::Err(__derive_io_err)) => { #trace_err #on_error }
+        ::core::task::Poll::Pending => { #trace_pending #on_pending }"
+    } else {
+        "::core::result::Result::Ok(__derive_io_n) => { #trace_ok #inspect }
+        ::core::result::Result::Err(__derive_io_err) => { #trace_err #on_error }"
+    };
+    // The result of a completion-based method is paired with the buffer
+    let result = if method.output == Output::Rent {
+        "__derive_io_result.0"
+    } else {
+        "__derive_io_result"
+    };
+    let [trace_ok, trace_err, trace_pending] = trace;
+    template(
+        &format!(
+            "{filled}
+            #names
+            let __derive_io_result = {{ #body }};
+            #context
+            let __derive_io_this: &Self = &*self;
+            match &{result} {{ {arms} }}
+            __derive_io_result"
+        ),
+        &[
+            ("body", body),
+            ("names", names),
+            ("context", context),
+            ("inspect", inspect),
+            ("on_error", on_error.unwrap_or_default()),
+            ("on_pending", on_pending.unwrap_or_default()),
+            ("trace_ok", trace_ok),
+            ("trace_err", trace_err),
+            ("trace_pending", trace_pending),
+        ],
+    )
+}
+
+/// Generates the statement that finds the variant and field names of the
+/// active case, for `#[io(trace)]` and `#[io(context)]`.
+fn names(delegates: &[Delegate]) -> TokenStream {
+    let name = |delegate: &Delegate| {
+        let field = delegate
+            .field
+            .as_ref()
+            .map(|(_, field)| field.member.to_tokens().to_string());
+        format!("({:?}, {:?})", delegate.variant_name, field)
+    };
+    let names = if let [Delegate { field: None, .. }] = delegates {
+        name(&delegates[0])
+    } else {
+        let mut arms = TokenStream::new();
+        for delegate in delegates {
+            arms.extend(template(
+                &format!("#cfgs #variant => {},", name(delegate)),
+                &[
+                    ("cfgs", delegate.cfgs.clone()),
+                    ("variant", delegate.variant.clone()),
+                ],
+            ));
+        }
+        template("match &*self { #arms }", &[("arms", arms)]).to_string()
+    };
+    format!(
+        "let (__derive_io_variant, __derive_io_field): (
+            ::core::option::Option<&'static str>,
+            ::core::option::Option<&'static str>,
+        ) = {names};"
+    )
+    .parse()
+    .unwrap()
+}
+
+/// Generates the `#[io(trace)]` events for the `Ok`, `Err` and `Pending`
+/// outcomes of a method.
+fn trace(krate: &TokenStream, input: &Input, method: &Method) -> [TokenStream; 3] {
+    let bytes = if method.inspect == Some(Inspect::ReadBuf) {
+        "::core::option::Option::Some(buf.filled().len() - __derive_io_filled)"
+    } else {
+        "{
+            use #krate::__support::{ByteCount as _, NoByteCount as _};
+            (&#krate::__support::Counted(__derive_io_n)).byte_count()
+        }"
+    };
+    let poll = |outcome: &str| {
+        if method.output == Output::Poll {
+            format!("poll = {outcome:?},")
+        } else {
+            String::new()
+        }
+    };
+    let event = |fields: String| {
+        template(
+            &format!(
+                "#krate::__support::trace!(
+                    ty = {:?},
+                    variant = __derive_io_variant,
+                    field = __derive_io_field,
+                    method = {:?},
+                    {fields}
+                );",
+                input.name.to_string(),
+                method.name,
+            ),
+            &[("krate", krate.clone())],
+        )
+    };
+    [
+        event(format!("{} bytes = {bytes}", poll("ready"))),
+        event(format!(
+            "{} error = %__derive_io_err, error_kind = ?__derive_io_err.kind()",
+            poll("ready")
+        )),
+        event(poll("pending")),
+    ]
+}
+
+/// Generates the impl blocks for the given derive.
+pub(crate) fn derive(macro_crate: &str, derive: &Derive, input: TokenStream) -> TokenStream {
+    let input = match Input::parse(input) {
+        Ok(input) => input,
+        Err(err) => return err.to_compile_error(),
+    };
+    let delegates = match find_delegates(derive, &input, None) {
+        Ok(delegates) => delegates,
+        Err(err) => return err.to_compile_error(),
+    };
+    let hooks = match parse_hooks(&input) {
+        Ok(hooks) => hooks,
+        Err(err) => return err.to_compile_error(),
+    };
+
+    // Errors of the `embedded-io` traits are converted into `#[io(error)]`,
+    // unless the delegate maps them itself
+    let mut delegates = delegates;
+    let embedded = derive
+        .impls
+        .iter()
+        .flat_map(|imp| imp.methods)
+        .any(|method| method.output == Output::Embedded);
+    let krate: TokenStream = format!("::{macro_crate}").parse().unwrap();
+    // Generic delegates must have an error type that converts into
+    // `#[io(error)]`, or else the same error type as the first delegate
+    let mut error_bounds = vec![];
+    let source = assoc_source(&delegates);
+    if embedded {
+        for delegate in &delegates {
+            let Some(ty) = target_ty(delegate).filter(|ty| input.has_generic(ty)) else {
+                continue;
+            };
+            let bound = match &hooks.error {
+                Some(_) if delegate.map_err.is_some() => continue,
+                Some(_) => "<#ty as #krate::__support::embedded_io::ErrorType>::Error: ::core::convert::Into<#error>",
+                None if source.as_ref().map(ToString::to_string) == Some(ty.to_string()) => continue,
+                None => "#ty: #krate::__support::embedded_io::ErrorType<Error = <#source as #krate::__support::embedded_io::ErrorType>::Error>",
+            };
+            error_bounds.push(template(
+                bound,
+                &[
+                    ("krate", krate.clone()),
+                    ("ty", ty),
+                    ("error", hooks.error.clone().unwrap_or_default()),
+                    ("source", source.clone().unwrap_or_default()),
+                ],
+            ));
+        }
+    }
+    if embedded && hooks.error.is_some() {
+        for delegate in &mut delegates {
+            delegate
+                .map_err
+                .get_or_insert_with(|| "::core::convert::Into::into".parse().unwrap());
+        }
+    }
+
+    // Methods with a role have their own delegates
+    let mut role_delegates = vec![];
+    for role in derive
+        .impls
+        .iter()
+        .flat_map(|imp| imp.methods)
+        .filter_map(|m| m.role)
+    {
+        match find_delegates(derive, &input, Some(role)) {
+            Ok(delegates) => role_delegates.push((role, delegates)),
+            Err(err) => return err.to_compile_error(),
+        }
+    }
+    let delegates_for = |method: &Method| {
+        (role_delegates.iter())
+            .find(|(role, _)| Some(*role) == method.role)
+            .map_or(&delegates, |(_, delegates)| delegates)
+    };
+
+    let mut output = TokenStream::new();
+    for imp in derive.impls {
+        let trait_path = template(&trait_ref(imp, &[]), &[("krate", krate.clone())]);
+        let mut bounds = bounds(&krate, &input, &delegates, imp);
+        if !role_delegates.is_empty() {
+            let mut seen = vec![];
+            bounds = (role_delegates.iter())
+                .flat_map(|(_, delegates)| self::bounds(&krate, &input, delegates, imp))
+                .filter(|bound| {
+                    let key = bound.to_string();
+                    let new = !seen.contains(&key);
+                    seen.push(key);
+                    new
+                })
+                .collect();
+        }
+        bounds.extend(error_bounds.iter().cloned());
+        let path = match imp.param {
+            Some(param) => format!("{}::<{param}>", imp.path),
+            None => imp.path.to_string(),
+        };
+        let mut impl_generics = input.impl_generics();
+        if let Some(param) = imp.param {
+            impl_generics = join([impl_generics, param.parse().unwrap()]);
+        }
+
+        let buffered = delegates.iter().any(|delegate| delegate.buffered);
+        let mut methods = TokenStream::new();
+        if let Some(source) = assoc_source(&delegates) {
+            for assoc in imp.assoc {
+                methods.extend(template(
+                    &format!("type {assoc} = <#source as #trait_path>::{assoc};"),
+                    &[
+                        ("source", source.clone()),
+                        ("trait_path", trait_path.clone()),
+                    ],
+                ));
+            }
+        }
+        for method in imp.methods {
+            // Methods without a buffered implementation use the trait default
+            if buffered && method.buffered.is_none() {
+                continue;
+            }
+            let delegates = delegates_for(method);
+            let hooked = is_hooked(method, delegates, &hooks);
+            // The hooks need `self` after the call, unless the result borrows it
+            let reborrow = hooked && !method.borrowed;
+            let body = if let [Delegate { field: None, .. }] = &delegates[..] {
+                call(&krate, &path, method, &delegates[0], reborrow)
+            } else {
+                let mut arms = TokenStream::new();
+                for delegate in delegates {
+                    let pattern = match &delegate.field {
+                        Some((pattern, _)) => pattern,
+                        None => &delegate.variant,
+                    };
+                    let call = match method.buffered {
+                        Some(buffered) if delegate.buffered => {
+                            buffered_call(&krate, imp, buffered, delegate)
+                        }
+                        _ => call(&krate, &path, method, delegate, false),
+                    };
+                    arms.extend(template(
+                        "#cfgs #pattern => { #call }",
+                        &[
+                            ("cfgs", delegate.cfgs.clone()),
+                            ("pattern", pattern.clone()),
+                            ("call", call),
+                        ],
+                    ));
+                }
+                let scrutinee = match method.receiver {
+                    // SAFETY: we never move out of the reference, and only
+                    // re-pin the delegate field if it is `Unpin` or `#[pin]`.
+                    Receiver::Pin if reborrow => {
+                        "unsafe { ::core::pin::Pin::get_unchecked_mut(self.as_mut()) }"
+                    }
+                    Receiver::Pin => "unsafe { ::core::pin::Pin::get_unchecked_mut(self) }",
+                    _ => "self",
+                };
+                template(&format!("match {scrutinee} {{ #arms }}"), &[("arms", arms)])
+            };
+            let mut signature = method.signature.to_string();
+            let body = if hooked {
+                if reborrow && method.receiver == Receiver::Pin {
+                    signature = signature.replacen("self:", "mut self:", 1);
+                }
+                hook(&krate, &input, method, delegates, &hooks, body)
+            } else {
+                body
+            };
+            methods.extend(template(
+                &format!("{signature} {{ #body }}"),
+                &[("krate", krate.clone()), ("body", body)],
+            ));
+        }
+
+        let cfg = match imp.cfg {
+            Some(cfg) => format!("#[cfg({cfg})]"),
+            None => String::new(),
+        };
+        output.extend(template(
+            &format!(
+                "{cfg} impl<#impl_generics> #trait_path for #name<#ty_generics> where #bounds {{ #methods }}"
+            ),
+            &[
+                ("impl_generics", impl_generics),
+                ("trait_path", trait_path),
+                ("name", TokenTree::Ident(input.name.clone()).into()),
+                ("ty_generics", input.ty_generics()),
+                ("bounds", join(bounds)),
+                ("methods", methods),
+            ],
+        ));
+    }
+
+    if let (Some(flush), Some(sink)) = (derive.flush_on_drop, &delegates[0].flush_on_drop) {
+        output.extend(flush_on_drop(
+            &krate, derive, &input, &delegates, flush, sink,
+        ));
+    }
+    output
+}
+
+/// Generates the `FlushOnDrop` impl for `#[write(flush_on_drop)]` and, unless
+/// `#[derive(PinProject)]` owns the `Drop` impl, a `Drop` impl that calls it.
+fn flush_on_drop(
+    krate: &TokenStream,
+    derive: &Derive,
+    input: &Input,
+    delegates: &[Delegate],
+    flush: &str,
+    sink: &Option<TokenStream>,
+) -> TokenStream {
+    let pin_projected = pin_project_owns_drop(input);
+    let handle = match sink {
+        Some(sink) => template(
+            "if let ::core::result::Result::Err(err) = result { (#sink)(err) }",
+            &[("sink", sink.clone())],
+        ),
+        None => template(
+            "if let ::core::result::Result::Err(err) = result { #krate::__support::flush_on_drop_error(err) }",
+            &[("krate", krate.clone())],
+        ),
+    };
+    let assert = if pin_projected {
+        template(
+            "#krate::__support::assert_pin_projected::<Self>();",
+            &[("krate", krate.clone())],
+        )
+    } else {
+        TokenStream::new()
+    };
+    let drop = if pin_projected {
+        ""
+    } else {
+        "impl<#impl_generics> ::core::ops::Drop for #name<#ty_generics> where #where_clause {
+            fn drop(&mut self) {
+                // SAFETY: `self` is being dropped and will never be moved
+                // again, so it may be considered pinned from here on.
+                let this = unsafe { ::core::pin::Pin::new_unchecked(self) };
+                #krate::__support::FlushOnDrop::flush_on_drop(this);
+            }
+        }"
+    };
+    template(
+        &format!(
+            "impl<#impl_generics> #krate::__support::FlushOnDrop for #name<#ty_generics> where #bounds {{
+                fn flush_on_drop(self: ::core::pin::Pin<&mut Self>) {{
+                    #assert
+                    let result: ::std::io::Result<()> = {{ {flush} }};
+                    #handle
+                }}
+            }}
+
+            // Deriving both `Write` and `AsyncWrite` with `flush_on_drop`
+            // reports this marker as a duplicate definition, which names the
+            // problem at both derives. The derives cannot see each other.
+            impl<#impl_generics> #name<#ty_generics> where #where_clause {{
+                #[doc(hidden)]
+                #[allow(dead_code)]
+                const __DERIVE_IO_ONLY_ONE_OF_WRITE_OR_ASYNC_WRITE_MAY_FLUSH_ON_DROP: () = ();
+            }}
+
+            {drop}"
+        ),
+        &[
+            ("krate", krate.clone()),
+            ("impl_generics", input.impl_generics()),
+            ("name", TokenTree::Ident(input.name.clone()).into()),
+            ("ty_generics", input.ty_generics()),
+            ("bounds", join(bounds(krate, input, delegates, &derive.impls[0]))),
+            ("where_clause", join(input.where_clause.iter().cloned())),
+            ("assert", assert),
+            ("handle", handle),
+        ],
+    )
+}
+
+/// Returns true if any field of the type is marked `#[pin]`, in which case
+/// `#[derive(PinProject)]` is required.
+fn has_pinned_fields(input: &Input) -> bool {
+    input
+        .cases
+        .iter()
+        .flat_map(|case| &case.fields)
+        .any(|field| field.attrs.iter().any(|attr| attr.name == "pin"))
+}
+
+/// Returns true if `#[derive(PinProject)]` generates the `Drop` impl, which
+/// is required for `#[pin]` fields or `#[pin(PinnedDrop)]`. Otherwise, the
+/// `Write` derives generate it for `#[write(flush_on_drop)]`.
+fn pin_project_owns_drop(input: &Input) -> bool {
+    has_pinned_fields(input) || input.attrs.iter().any(|attr| attr.name == "pin")
+}
+
+/// Returns true if any `#[write]` attribute of the type requests
+/// `flush_on_drop`. Invalid attributes are reported by the `Write` derives.
+fn has_flush_on_drop(input: &Input) -> bool {
+    input
+        .attrs
+        .iter()
+        .chain(
+            input
+                .cases
+                .iter()
+                .flat_map(|case| &case.fields)
+                .flat_map(|field| &field.attrs),
+        )
+        .filter(|attr| attr.name == "write")
+        .filter_map(|attr| attr.meta().ok())
+        .flatten()
+        .any(|meta| meta.key.to_string() == "flush_on_drop")
+}
+
+/// Generates the `IntoRawFd` impl for `#[derive(IntoFileDescriptor)]`, along
+/// with `From<Self> for OwnedFd` and, for a `#[descriptor(from)]` field, the
+/// `FromRawFd` and `From<OwnedFd>` impls.
+pub(crate) fn into_file_descriptor(macro_crate: &str, input: TokenStream) -> TokenStream {
+    let mut output = derive(macro_crate, &INTO_FILE_DESCRIPTOR, input.clone());
+    // Any errors have already been reported by `derive`
+    let Ok(input) = Input::parse(input) else {
+        return output;
+    };
+    let Ok(delegates) = find_delegates(&INTO_FILE_DESCRIPTOR, &input, None) else {
+        return output;
+    };
+    let name: TokenStream = TokenTree::Ident(input.name.clone()).into();
+    let mut bounds = vec![template(
+        "#name<#ty_generics>: ::std::os::fd::IntoRawFd",
+        &[("name", name.clone()), ("ty_generics", input.ty_generics())],
+    )];
+    bounds.extend(input.where_clause.iter().cloned());
+    // SAFETY: `into_raw_fd` transfers ownership of the descriptor
+    output.extend(template(
+        "#[cfg(unix)]
+        impl<#impl_generics> ::core::convert::From<#name<#ty_generics>> for ::std::os::fd::OwnedFd
+        where #bounds {
+            fn from(value: #name<#ty_generics>) -> Self {
+                unsafe {
+                    ::std::os::fd::FromRawFd::from_raw_fd(::std::os::fd::IntoRawFd::into_raw_fd(value))
+                }
+            }
+        }",
+        &[
+            ("impl_generics", input.impl_generics()),
+            ("name", name.clone()),
+            ("ty_generics", input.ty_generics()),
+            ("bounds", join(bounds)),
+        ],
+    ));
+
+    let Some(delegate) = delegates.iter().find(|delegate| delegate.from) else {
+        return output;
+    };
+    let Some((_, field)) = delegate.field else {
+        return Error::new("#[descriptor(from)] requires a field", input.name.span())
+            .to_compile_error();
+    };
+    if let [case] = &input.cases[..] {
+        if case.name.is_none() && case.fields.len() == 1 {
+            let mut bounds = vec![];
+            if input.has_generic(&field.ty) {
+                bounds.push(template(
+                    "#ty: ::std::os::fd::FromRawFd + ::core::convert::From<::std::os::fd::OwnedFd>",
+                    &[("ty", field.ty.clone())],
+                ));
+            }
+            bounds.extend(input.where_clause.iter().cloned());
+            output.extend(template(
+                "#[cfg(unix)]
+                impl<#impl_generics> ::std::os::fd::FromRawFd for #name<#ty_generics> where #bounds {
+                    unsafe fn from_raw_fd(fd: ::std::os::fd::RawFd) -> Self {
+                        Self { #member: unsafe { ::std::os::fd::FromRawFd::from_raw_fd(fd) } }
+                    }
+                }
+
+                #[cfg(unix)]
+                impl<#impl_generics> ::core::convert::From<::std::os::fd::OwnedFd> for #name<#ty_generics>
+                where #bounds {
+                    fn from(fd: ::std::os::fd::OwnedFd) -> Self {
+                        Self { #member: ::core::convert::From::from(fd) }
+                    }
+                }",
+                &[
+                    ("impl_generics", input.impl_generics()),
+                    ("name", name),
+                    ("ty_generics", input.ty_generics()),
+                    ("bounds", join(bounds)),
+                    ("member", field.member.to_tokens()),
+                ],
+            ));
+            return output;
+        }
+    }
+    let span = field
+        .attrs
+        .iter()
+        .find(|attr| attr.name == "descriptor")
+        .map_or(input.name.span(), |attr| attr.span);
+    Error::new(
+        "#[descriptor(from)] requires a struct with a single field",
+        span,
+    )
+    .to_compile_error()
+}
+
+/// The `kind` values of `#[descriptor(kind = ...)]`, and the matching
+/// `DescriptorKind` variants.
+const DESCRIPTOR_KINDS: &[(&str, &str)] = &[
+    ("tcp", "Tcp"),
+    ("tcp_listener", "TcpListener"),
+    ("udp", "Udp"),
+    ("unix_stream", "UnixStream"),
+    ("unix_listener", "UnixListener"),
+    ("pipe", "Pipe"),
+    ("file", "File"),
+    ("tty", "Tty"),
+];
+
+/// Generates the `TryFrom<OwnedFd>` impl for `#[derive(FromDescriptor)]`,
+/// which builds the first variant whose `#[descriptor(kind = ...)]` matches
+/// the probed kind of the descriptor.
+pub(crate) fn from_descriptor(macro_crate: &str, input: TokenStream) -> TokenStream {
+    let input = match Input::parse(input) {
+        Ok(input) => input,
+        Err(err) => return err.to_compile_error(),
+    };
+    let krate: TokenStream = format!("::{macro_crate}").parse().unwrap();
+
+    let mut arms = TokenStream::new();
+    let mut bounds = vec![];
+    // The kinds handled by variants that do not depend on a `#[cfg]`
+    let mut handled = vec![];
+    for case in &input.cases {
+        if case.name.is_none() {
+            return Error::new(
+                "#[derive(FromDescriptor)] requires an enum",
+                input.name.span(),
+            )
+            .to_compile_error();
+        }
+        for field in &case.fields {
+            let attrs = field.attrs.iter().filter(|attr| attr.name == "descriptor");
+            for attr in attrs {
+                if !is_kind_only(attr) {
+                    continue;
+                }
+                return Error::new(
+                    "#[descriptor(kind = ...)] must be placed on the variant",
+                    attr.span,
+                )
+                .to_compile_error();
+            }
+        }
+        let Some(attr) = case.attrs.iter().find(|attr| attr.name == "descriptor") else {
+            continue;
+        };
+        let meta = match attr.meta() {
+            Ok(meta) => meta,
+            Err(err) => return err.to_compile_error(),
+        };
+        let mut kinds = vec![];
+        for meta in meta {
+            match (meta.key.to_string().as_str(), meta.value) {
+                ("kind", Some(value)) => {
+                    for token in value {
+                        let (name, span) = match &token {
+                            TokenTree::Punct(punct) if punct.as_char() == '|' => continue,
+                            TokenTree::Ident(ident) => (ident.to_string(), ident.span()),
+                            token => (token.to_string(), token.span()),
+                        };
+                        let Some((_, kind)) = DESCRIPTOR_KINDS.iter().find(|(key, _)| *key == name)
+                        else {
+                            let expected = DESCRIPTOR_KINDS
+                                .iter()
+                                .map(|(key, _)| format!("`{key}`"))
+                                .collect::<Vec<_>>()
+                                .join(", ");
+                            return Error::new(
+                                format!(
+                                    "Unknown descriptor kind `{name}`, expected one of {expected}"
+                                ),
+                                span,
+                            )
+                            .to_compile_error();
+                        };
+                        if handled.contains(kind) {
+                            return Error::new(
+                                format!("Descriptor kind `{name}` is already handled by another variant"),
+                                span,
+                            )
+                            .to_compile_error();
+                        }
+                        kinds.push(*kind);
+                    }
+                }
+                // The options of `MaybeAsFd`
+                ("none", None) => {}
+                (key, _) => {
+                    return Error::new(
+                        format!("Invalid #[descriptor] attribute, unknown option `{key}`"),
+                        meta.key.span(),
+                    )
+                    .to_compile_error()
+                }
+            }
+        }
+        if kinds.is_empty() {
+            continue;
+        }
+        let [field] = &case.fields[..] else {
+            return Error::new(
+                "#[descriptor(kind = ...)] requires a variant with a single field",
+                attr.span,
+            )
+            .to_compile_error();
+        };
+        let cfgs: TokenStream = case
+            .attrs
+            .iter()
+            .filter(|attr| attr.name == "cfg")
+            .flat_map(|attr| attr.tokens.clone())
+            .collect();
+        if cfgs.is_empty() {
+            handled.extend(kinds.iter().copied());
+        }
+        if input.has_generic(&field.ty) {
+            bounds.push(template(
+                "#ty: ::core::convert::From<::std::os::fd::OwnedFd>",
+                &[("ty", field.ty.clone())],
+            ));
+        }
+        let pattern = kinds
+            .iter()
+            .map(|kind| format!("#krate::DescriptorKind::{kind}"))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        arms.extend(template(
+            &format!(
+                "#cfgs {pattern} => ::core::result::Result::Ok(
+                    #path {{ #member: ::core::convert::From::from(fd) }}
+                ),"
+            ),
+            &[
+                ("krate", krate.clone()),
+                ("cfgs", cfgs),
+                ("path", case.path.clone()),
+                ("member", field.member.to_tokens()),
+            ],
+        ));
+    }
+    if arms.is_empty() {
+        return Error::new(
+            "No #[descriptor(kind = ...)] variant found",
+            input.name.span(),
+        )
+        .to_compile_error();
+    }
+    bounds.extend(input.where_clause.iter().cloned());
+
+    template(
+        "#[cfg(unix)]
+        impl<#impl_generics> ::core::convert::TryFrom<::std::os::fd::OwnedFd> for #name<#ty_generics>
+        where #bounds {
+            type Error = #krate::FromDescriptorError;
+
+            fn try_from(
+                fd: ::std::os::fd::OwnedFd,
+            ) -> ::core::result::Result<Self, #krate::FromDescriptorError> {
+                let (fd, kind) = #krate::__support::probe_descriptor(fd)?;
+                #[allow(unreachable_patterns)]
+                match kind {
+                    #arms
+                    _ => ::core::result::Result::Err(
+                        #krate::FromDescriptorError::unsupported(fd, kind),
+                    ),
+                }
+            }
+        }",
+        &[
+            ("krate", krate),
+            ("impl_generics", input.impl_generics()),
+            ("name", TokenTree::Ident(input.name.clone()).into()),
+            ("ty_generics", input.ty_generics()),
+            ("bounds", join(bounds)),
+            ("arms", arms),
+        ],
+    )
+}
+
+/// Splits the path of an enum variant into the enum and the variant, ie:
+/// `Streams` and `Tcp` for `Streams::Tcp`.
+fn split_variant(path: &TokenStream) -> Option<TokenStream> {
+    let tokens: Vec<TokenTree> = path.clone().into_iter().collect();
+    match &tokens[..] {
+        [ty @ .., TokenTree::Punct(a), TokenTree::Punct(b), TokenTree::Ident(_)]
+            if !ty.is_empty() && a.as_char() == ':' && b.as_char() == ':' =>
+        {
+            Some(ty.iter().cloned().collect())
+        }
+        _ => None,
+    }
+}
+
+/// Generates the `Listener` impl for `#[derive(Listener)]`, which wraps each
+/// accepted stream in the `#[listener(stream = ...)]` variant of the field,
+/// along with the descriptor impls and, with the `axum` feature,
+/// `axum::serve::Listener`.
+pub(crate) fn listener(macro_crate: &str, input: TokenStream) -> TokenStream {
+    let descriptors = Derive {
+        impls: &LISTENER.impls[1..],
+        ..LISTENER
+    };
+    let mut output = derive(macro_crate, &descriptors, input.clone());
+    // Any errors have already been reported by `derive`
+    let Ok(input) = Input::parse(input) else {
+        return output;
+    };
+    let Ok(delegates) = find_delegates(&LISTENER, &input, None) else {
+        return output;
+    };
+    let krate: TokenStream = format!("::{macro_crate}").parse().unwrap();
+
+    let mut stream_ty: Option<TokenStream> = None;
+    let mut poll_accept = TokenStream::new();
+    let mut local_addr = TokenStream::new();
+    for delegate in &delegates {
+        let Some((pattern, field)) = &delegate.field else {
+            return Error::new("#[listener] requires a field", input.name.span())
+                .to_compile_error();
+        };
+        let attr = (field.attrs.iter())
+            .find(|attr| attr.name == "listener")
+            .unwrap();
+        let meta = attr.meta().unwrap_or_default();
+        let Some(stream) = (meta.into_iter())
+            .find(|meta| meta.key.to_string() == "stream")
+            .and_then(|meta| meta.value)
+        else {
+            return Error::new(
+                "#[listener] requires the variant of the accepted stream, ie: `#[listener(stream = Streams::Tcp)]`",
+                attr.span,
+            )
+            .to_compile_error();
+        };
+        let Some(ty) = split_variant(&stream) else {
+            return Error::new(
+                "#[listener(stream = ...)] must be the path of an enum variant, ie: `Streams::Tcp`",
+                attr.span,
+            )
+            .to_compile_error();
+        };
+        match &stream_ty {
+            Some(first) if first.to_string() != ty.to_string() => {
+                return Error::new(
+                    "Every #[listener(stream = ...)] must be a variant of the same enum",
+                    attr.span,
+                )
+                .to_compile_error();
+            }
+            Some(_) => {}
+            None => stream_ty = Some(ty),
+        }
+
+        let [accept, addr] = &LISTENER_IMPL.methods else {
+            unreachable!()
+        };
+        poll_accept.extend(template(
+            "#cfgs #pattern => #call.map(|__derive_io_result| {
+                __derive_io_result.map(|(__derive_io_stream, __derive_io_addr)| {
+                    (#stream(__derive_io_stream), ::core::convert::Into::into(__derive_io_addr))
+                })
+            }),",
+            &[
+                ("cfgs", delegate.cfgs.clone()),
+                ("pattern", pattern.clone()),
+                (
+                    "call",
+                    call(&krate, LISTENER_IMPL.path, accept, delegate, false),
+                ),
+                ("stream", stream),
+            ],
+        ));
+        local_addr.extend(template(
+            "#cfgs #pattern => #call.map(::core::convert::Into::into),",
+            &[
+                ("cfgs", delegate.cfgs.clone()),
+                ("pattern", pattern.clone()),
+                (
+                    "call",
+                    call(&krate, LISTENER_IMPL.path, addr, delegate, false),
+                ),
+            ],
+        ));
+    }
+
+    let bounds = bounds(&krate, &input, &delegates, &LISTENER_IMPL);
+    let name: TokenStream = TokenTree::Ident(input.name.clone()).into();
+    let [accept, addr] = &LISTENER_IMPL.methods else {
+        unreachable!()
+    };
+    output.extend(template(
+        &format!(
+            "impl<#impl_generics> #krate::Listener for #name<#ty_generics> where #bounds {{
+                type Stream = #stream_ty;
+
+                {} {{ match self {{ #poll_accept }} }}
+
+                {} {{ match self {{ #local_addr }} }}
+            }}
+
+            #krate::__support::axum_listener! {{
+                [#impl_generics] [#name<#ty_generics>] [#bounds]
+            }}",
+            accept.signature, addr.signature,
+        ),
+        &[
+            ("krate", krate.clone()),
+            ("impl_generics", input.impl_generics()),
+            ("name", name),
+            ("ty_generics", input.ty_generics()),
+            ("bounds", join(bounds)),
+            ("stream_ty", stream_ty.unwrap_or_default()),
+            ("poll_accept", poll_accept),
+            ("local_addr", local_addr),
+        ],
+    ));
+    output
+}
+
+/// Generates the companion half enums and the `NativeSplit` impl for
+/// `#[derive(Split)]`. Each variant is split with the `NativeSplit` impl of
+/// its field or, for `#[split(generic)]` fields, with `tokio::io::split`.
+pub(crate) fn split(macro_crate: &str, input: TokenStream) -> TokenStream {
+    let input = match Input::parse(input) {
+        Ok(input) => input,
+        Err(err) => return err.to_compile_error(),
+    };
+    let krate: TokenStream = format!("::{macro_crate}").parse().unwrap();
+    let name = &input.name;
+    let half = |suffix: &str| -> TokenStream {
+        TokenTree::Ident(Ident::new(&format!("{name}{suffix}"), name.span())).into()
+    };
+    let [read_half, write_half, owned_read_half, owned_write_half] =
+        ["ReadHalf", "WriteHalf", "OwnedReadHalf", "OwnedWriteHalf"].map(half);
+
+    let mut bounds = vec![];
+    let mut halves = [(); 4].map(|_| TokenStream::new());
+    let mut split = TokenStream::new();
+    let mut into_split = TokenStream::new();
+    let mut reunite = TokenStream::new();
+    for case in &input.cases {
+        let Some(variant) = &case.name else {
+            return Error::new("#[derive(Split)] requires an enum", input.name.span())
+                .to_compile_error();
+        };
+        let [field] = &case.fields[..] else {
+            return Error::new(
+                "#[derive(Split)] requires each variant to have a single field",
+                case.span,
+            )
+            .to_compile_error();
+        };
+        let mut generic = false;
+        for attr in field.attrs.iter().filter(|attr| attr.name == "split") {
+            let meta = match attr.meta() {
+                Ok(meta) => meta,
+                Err(err) => return err.to_compile_error(),
+            };
+            for meta in meta {
+                match (meta.key.to_string().as_str(), meta.value) {
+                    ("generic", None) => generic = true,
+                    (key, _) => {
+                        return Error::new(
+                            format!("Invalid #[split] attribute, unknown option `{key}`"),
+                            meta.key.span(),
+                        )
+                        .to_compile_error()
+                    }
+                }
+            }
+        }
+
+        let cfgs: TokenStream = (case.attrs.iter())
+            .filter(|attr| attr.name == "cfg")
+            .flat_map(|attr| attr.tokens.clone())
+            .collect();
+        let vars = [
+            ("krate", krate.clone()),
+            ("cfgs", cfgs),
+            (
+                "variant",
+                TokenTree::Ident(Ident::new(variant, case.span)).into(),
+            ),
+            ("member", field.member.to_tokens()),
+            ("ty", field.ty.clone()),
+            ("read_half", read_half.clone()),
+            ("write_half", write_half.clone()),
+            ("owned_read_half", owned_read_half.clone()),
+            ("owned_write_half", owned_write_half.clone()),
+        ];
+        let types = if generic {
+            if input.has_generic(&field.ty) {
+                bounds.push(template(
+                    "#ty: #krate::__support::tokio::io::AsyncRead
+                        + #krate::__support::tokio::io::AsyncWrite
+                        + ::core::marker::Unpin",
+                    &vars,
+                ));
+            }
+            [
+                "#krate::__support::tokio::io::ReadHalf<&'__split mut #ty>",
+                "#krate::__support::tokio::io::WriteHalf<&'__split mut #ty>",
+                "#krate::__support::tokio::io::ReadHalf<#ty>",
+                "#krate::__support::tokio::io::WriteHalf<#ty>",
+            ]
+        } else {
+            if input.has_generic(&field.ty) {
+                bounds.push(template("#ty: #krate::NativeSplit", &vars));
+            }
+            [
+                "<#ty as #krate::NativeSplit>::ReadHalf<'__split>",
+                "<#ty as #krate::NativeSplit>::WriteHalf<'__split>",
+                "<#ty as #krate::NativeSplit>::OwnedReadHalf",
+                "<#ty as #krate::NativeSplit>::OwnedWriteHalf",
+            ]
+        };
+        for ((half, ty), attr) in halves
+            .iter_mut()
+            .zip(types)
+            .zip(["read", "write"].repeat(2))
+        {
+            half.extend(template(&format!("#cfgs #variant(#[{attr}] {ty}),"), &vars));
+        }
+        let (split_fn, into_split_fn, reunite_fn) = if generic {
+            (
+                "#krate::__support::tokio::io::split",
+                "#krate::__support::tokio::io::split",
+                "#krate::__support::reunite_generic",
+            )
+        } else {
+            (
+                "#krate::NativeSplit::split",
+                "#krate::NativeSplit::into_split",
+                "#krate::NativeSplit::reunite",
+            )
+        };
+        split.extend(template(
+            &format!(
+                "#cfgs Self::#variant {{ #member: __derive_io_field }} => {{
+                    let (__derive_io_read, __derive_io_write) = {split_fn}(__derive_io_field);
+                    (#read_half::#variant(__derive_io_read), #write_half::#variant(__derive_io_write))
+                }}"
+            ),
+            &vars,
+        ));
+        into_split.extend(template(
+            &format!(
+                "#cfgs Self::#variant {{ #member: __derive_io_field }} => {{
+                    let (__derive_io_read, __derive_io_write) = {into_split_fn}(__derive_io_field);
+                    (
+                        #owned_read_half::#variant(__derive_io_read),
+                        #owned_write_half::#variant(__derive_io_write),
+                    )
+                }}"
+            ),
+            &vars,
+        ));
+        reunite.extend(template(
+            &format!(
+                "#cfgs (#owned_read_half::#variant(__derive_io_read), #owned_write_half::#variant(__derive_io_write)) => {{
+                    match {reunite_fn}(__derive_io_read, __derive_io_write) {{
+                        ::core::result::Result::Ok(__derive_io_field) => {{
+                            ::core::result::Result::Ok(Self::#variant {{ #member: __derive_io_field }})
+                        }}
+                        ::core::result::Result::Err(#krate::ReuniteError(__derive_io_read, __derive_io_write)) => {{
+                            ::core::result::Result::Err(#krate::ReuniteError(
+                                #owned_read_half::#variant(__derive_io_read),
+                                #owned_write_half::#variant(__derive_io_write),
+                            ))
+                        }}
+                    }}
+                }}"
+            ),
+            &vars,
+        ));
+    }
+    bounds.extend(input.where_clause.iter().cloned());
+
+    let ty_generics = input.ty_generics();
+    let [read_variants, write_variants, owned_read_variants, owned_write_variants] = halves;
+    let vars = [
+        ("krate", krate.clone()),
+        ("vis", input.vis.clone()),
+        ("name", TokenTree::Ident(name.clone()).into()),
+        ("impl_generics", input.impl_generics()),
+        ("ty_generics", ty_generics.clone()),
+        (
+            "split_generics",
+            join(["'__split".parse().unwrap(), ty_generics]),
+        ),
+        ("bounds", join(bounds)),
+        ("read_half", read_half),
+        ("write_half", write_half),
+        ("owned_read_half", owned_read_half),
+        ("owned_write_half", owned_write_half),
+        ("read_variants", read_variants),
+        ("write_variants", write_variants),
+        ("owned_read_variants", owned_read_variants),
+        ("owned_write_variants", owned_write_variants),
+        ("split", split),
+        ("into_split", into_split),
+        ("reunite", reunite),
+    ];
+    template(
+        "#[doc = ::core::concat!(\"The read half of [`\", ::core::stringify!(#name), \"`], borrowed by `split`.\")]
+        #[derive(#krate::AsyncRead)]
+        #vis enum #read_half<'__split, #impl_generics> where #name<#ty_generics>: '__split, #bounds {
+            #read_variants
+        }
+
+        #[doc = ::core::concat!(\"The write half of [`\", ::core::stringify!(#name), \"`], borrowed by `split`.\")]
+        #[derive(#krate::AsyncWrite)]
+        #vis enum #write_half<'__split, #impl_generics> where #name<#ty_generics>: '__split, #bounds {
+            #write_variants
+        }
+
+        #[doc = ::core::concat!(\"The read half of [`\", ::core::stringify!(#name), \"`], returned by `into_split`.\")]
+        #[derive(#krate::AsyncRead)]
+        #vis enum #owned_read_half<#impl_generics> where #bounds {
+            #owned_read_variants
+        }
+
+        #[doc = ::core::concat!(\"The write half of [`\", ::core::stringify!(#name), \"`], returned by `into_split`.\")]
+        #[derive(#krate::AsyncWrite)]
+        #vis enum #owned_write_half<#impl_generics> where #bounds {
+            #owned_write_variants
+        }
+
+        impl<#impl_generics> #krate::NativeSplit for #name<#ty_generics> where #bounds {
+            type ReadHalf<'__split> = #read_half<#split_generics> where Self: '__split;
+            type WriteHalf<'__split> = #write_half<#split_generics> where Self: '__split;
+            type OwnedReadHalf = #owned_read_half<#ty_generics>;
+            type OwnedWriteHalf = #owned_write_half<#ty_generics>;
+
+            fn split(&mut self) -> (Self::ReadHalf<'_>, Self::WriteHalf<'_>) {
+                match self { #split }
+            }
+
+            fn into_split(self) -> (Self::OwnedReadHalf, Self::OwnedWriteHalf) {
+                match self { #into_split }
+            }
+
+            fn reunite(
+                read: Self::OwnedReadHalf,
+                write: Self::OwnedWriteHalf,
+            ) -> ::core::result::Result<Self, #krate::ReuniteError<Self::OwnedReadHalf, Self::OwnedWriteHalf>> {
+                #[allow(unreachable_patterns)]
+                match (read, write) {
+                    #reunite
+                    (__derive_io_read, __derive_io_write) => {
+                        ::core::result::Result::Err(#krate::ReuniteError(__derive_io_read, __derive_io_write))
+                    }
+                }
+            }
+        }",
+        &vars,
+    )
+}
+
+/// Generates the `embedded_io::ErrorType` impl: the `#[io(error = ...)]` type
+/// if specified, or the error type of the first `#[read]`, `#[write]` or
+/// `#[seek]` field.
+pub(crate) fn error_type(macro_crate: &str, input: TokenStream) -> TokenStream {
+    let input = match Input::parse(input) {
+        Ok(input) => input,
+        Err(err) => return err.to_compile_error(),
+    };
+    let hooks = match parse_hooks(&input) {
+        Ok(hooks) => hooks,
+        Err(err) => return err.to_compile_error(),
+    };
+    let krate: TokenStream = format!("::{macro_crate}").parse().unwrap();
+
+    let mut bounds = vec![];
+    let error = if let Some(error) = hooks.error {
+        error
+    } else {
+        // Prefer a case that does not depend on a `#[cfg]`
+        let case = input
+            .cases
+            .iter()
+            .find(|case| !case.attrs.iter().any(|attr| attr.name == "cfg"))
+            .or(input.cases.first());
+        let mut found = None;
+        for field in case.iter().flat_map(|case| &case.fields) {
+            for attr in &field.attrs {
+                let derive = match attr.name.as_str() {
+                    "read" => &EMBEDDED_READ,
+                    "write" => &EMBEDDED_WRITE,
+                    "seek" => &EMBEDDED_SEEK,
+                    _ => continue,
+                };
+                let options = match parse_options(derive, attr, false) {
+                    Ok(options) => options,
+                    Err(err) => return err.to_compile_error(),
+                };
+                found = Some((field, options.mode));
+                break;
+            }
+            if found.is_some() {
+                break;
+            }
+        }
+        let ty = match found {
+            Some((field, Mode::Plain)) => field.ty.clone(),
+            Some((field, Mode::Deref)) => template(
+                "<#ty as ::core::ops::Deref>::Target",
+                &[("ty", field.ty.clone())],
+            ),
+            _ => {
+                return Error::new(
+                    "No #[read], #[write] or #[seek] field found to take the error type from, specify it with #[io(error = <type>)]",
+                    input.name.span(),
+                )
+                .to_compile_error()
+            }
+        };
+        if input.has_generic(&ty) {
+            bounds.push(template(
+                "#ty: #krate::__support::embedded_io::ErrorType",
+                &[("krate", krate.clone()), ("ty", ty.clone())],
+            ));
+        }
+        template(
+            "<#ty as #krate::__support::embedded_io::ErrorType>::Error",
+            &[("krate", krate.clone()), ("ty", ty)],
+        )
+    };
+    bounds.extend(input.where_clause.iter().cloned());
+
+    template(
+        "impl<#impl_generics> #krate::__support::embedded_io::ErrorType for #name<#ty_generics>
+        where #bounds {
+            type Error = #error;
+        }",
+        &[
+            ("krate", krate),
+            ("impl_generics", input.impl_generics()),
+            ("name", TokenTree::Ident(input.name.clone()).into()),
+            ("ty_generics", input.ty_generics()),
+            ("bounds", join(bounds)),
+            ("error", error),
+        ],
+    )
+}
+
+/// Generates the `Unpin`, `PinProjected` and `Drop` (or `Drop` guard) impls
+/// for `#[derive(PinProject)]`.
+pub(crate) fn pin_project(macro_crate: &str, input: TokenStream) -> TokenStream {
+    let input = match Input::parse(input) {
+        Ok(input) => input,
+        Err(err) => return err.to_compile_error(),
+    };
+
+    let mut pinned_drop = false;
+    for attr in input.attrs.iter().filter(|attr| attr.name == "pin") {
+        match &attr.args {
+            Some(args) if args.stream().to_string() == "PinnedDrop" => pinned_drop = true,
+            _ => {
+                return Error::new(
+                    "Invalid #[pin] attribute on type, expected #[pin(PinnedDrop)]",
+                    attr.span,
+                )
+                .to_compile_error()
+            }
+        }
+    }
+
+    // Collect the types of all fields marked #[pin]
+    let mut pinned = vec![];
+    for field in input.cases.iter().flat_map(|case| &case.fields) {
+        for attr in field.attrs.iter().filter(|attr| attr.name == "pin") {
+            if attr.args.is_some() {
+                return Error::new(
+                    "Invalid #[pin] attribute on field, expected #[pin]",
+                    attr.span,
+                )
+                .to_compile_error();
+            }
+        }
+        if field.attrs.iter().any(|attr| attr.name == "pin") {
+            pinned.push(field.ty.clone());
+        }
+    }
+
+    let flush_on_drop = has_flush_on_drop(&input) && pin_project_owns_drop(&input);
+    let drop = if pinned_drop || flush_on_drop {
+        let flush = if flush_on_drop {
+            "#krate::__support::FlushOnDrop::flush_on_drop(this.as_mut());"
+        } else {
+            ""
+        };
+        let drop = if pinned_drop {
+            "<Self as #krate::PinnedDrop>::drop(this);"
+        } else {
+            ""
+        };
+        &format!(
+            "impl<#impl_generics> ::core::ops::Drop for #name<#ty_generics> where #where_clause {{
+                fn drop(&mut self) {{
+                    // SAFETY: `self` is being dropped and will never be moved
+                    // again, so it may be considered pinned from here on.
+                    #[allow(unused_mut)]
+                    let mut this = unsafe {{ ::core::pin::Pin::new_unchecked(self) }};
+                    {flush}
+                    {drop}
+                }}
+            }}"
+        )
+    } else if has_flush_on_drop(&input) {
+        // Without pinned fields, the `Write` derives own the `Drop` impl
+        ""
+    } else {
+        // A manual `Drop` impl could move out of a pinned field, so we make one
+        // conflict with this blanket impl.
+        "const _: () = {
+            #[allow(dead_code)]
+            trait __DeriveIoMustNotImplDrop {}
+            #[allow(drop_bounds)]
+            impl<T: ::core::ops::Drop> __DeriveIoMustNotImplDrop for T {}
+            impl<#impl_generics> __DeriveIoMustNotImplDrop for #name<#ty_generics> where #where_clause {}
+        };"
+    };
+
+    // SAFETY: the impls below uphold the structural pinning guarantees.
+    template(
+        &format!(
+            "impl<'__derive_io_pin, #impl_generics> ::core::marker::Unpin for #name<#ty_generics>
+            where
+                #krate::__support::PinnedFields<'__derive_io_pin, (#pinned)>: ::core::marker::Unpin,
+                #where_clause
+            {{
+            }}
+
+            unsafe impl<#impl_generics> #krate::__support::PinProjected for #name<#ty_generics> where #where_clause {{}}
+
+            {drop}"
+        ),
+        &[
+            ("krate", format!("::{macro_crate}").parse().unwrap()),
+            ("impl_generics", input.impl_generics()),
+            ("name", TokenTree::Ident(input.name.clone()).into()),
+            ("ty_generics", input.ty_generics()),
+            ("where_clause", join(input.where_clause.iter().cloned())),
+            // Each type is followed by a comma, so that no pinned fields is `()`
+            (
+                "pinned",
+                pinned
+                    .into_iter()
+                    .flat_map(|ty| template("#ty,", &[("ty", ty)]))
+                    .collect(),
+            ),
+        ],
+    )
+}
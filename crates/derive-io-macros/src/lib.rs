@@ -1,598 +1,901 @@
 //! Support macros for `derive-io`. This is not intended to be used directly and
 //! has no stable API.
 
-use std::collections::HashSet;
-
 use proc_macro::*;
 
-/// `#[derive(Read)]`
-///
-/// Derives `std::io::Read` for the given struct.
-///
-/// Supported attributes:
-///
-/// - `#[read]`: Marks the field as a read stream.
-/// - `#[read(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
-/// - `#[read(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
-/// - `#[read(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
-#[proc_macro_derive(Read, attributes(read, write, descriptor))]
-pub fn derive_io_read(input: TokenStream) -> TokenStream {
-    generate("derive_io", "derive_io_read", input)
-}
-
-/// `#[derive(BufRead)]`
-///
-/// Derives `std::io::BufRead` for the given struct. `std::io::Read` must also
-/// be implemented.
-///
-/// Unsupported methods:
-///
-/// - `split` (std-internal implementation)
-/// - `lines` (std-internal implementation)
-/// - `has_data_left` (unstable feature)
-///
-/// Supported attributes:
-///
-/// - `#[read]`: Marks the field as a read stream.
-/// - `#[read(as_ref)]`: Delegates the field to the inner type using
-///   `AsRef`/`AsMut`.
-/// - `#[read(deref)]`: Delegates the field to the inner type using
-///   `Deref`/`DerefMut`.
-/// - `#[read(<function>=<override>)]`: Overrides the default `<function>`
-///   method with the given override function.
-#[proc_macro_derive(BufRead, attributes(read, write, descriptor))]
-pub fn derive_io_bufread(input: TokenStream) -> TokenStream {
-    generate("derive_io", "derive_io_bufread", input)
-}
-
-/// `#[derive(Write)]`
-///
-/// Derives `std::io::Write` for the given struct.
-///
-/// Supported attributes:
-///
-/// - `#[write]`: Marks the field as a write stream.
-/// - `#[write(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
-/// - `#[write(deref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
-/// - `#[write(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
-#[proc_macro_derive(Write, attributes(read, write, descriptor))]
-pub fn derive_io_write(input: TokenStream) -> TokenStream {
-    generate("derive_io", "derive_io_write", input)
-}
-
-/// `#[derive(AsyncRead)]`:
-///
-/// Derives `tokio::io::AsyncRead` for the given struct.
-///
-/// Supported attributes:
-///
-/// - `#[read]`: Marks the field as a read stream.
-/// - `#[read(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
-/// - `#[read(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
-/// - `#[read(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
-#[proc_macro_derive(AsyncRead, attributes(read, write, descriptor, duck))]
-pub fn derive_io_async_read(input: TokenStream) -> TokenStream {
-    generate("derive_io", "derive_io_async_read", input)
-}
-
-/// `#[derive(AsyncWrite)]`:
-///
-/// Derives `tokio::io::AsyncWrite` for the given struct.
-///
-/// Supported attributes:
-///
-/// - `#[write]`: Marks the field as a write stream.
-/// - `#[write(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
-/// - `#[write(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
-/// - `#[write(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
-#[proc_macro_derive(AsyncWrite, attributes(read, write, descriptor, duck))]
-pub fn derive_io_async_write(input: TokenStream) -> TokenStream {
-    generate("derive_io", "derive_io_async_write", input)
-}
-
-/// `#[derive(AsFileDescriptor)]`
-///
-/// Derives `std::os::fd::{AsFd, AsRawFd}` and `std::os::windows::io::{AsHandle, AsRawHandle}` for the given struct.
-///
-/// Supported attributes:
-///
-/// - `#[descriptor]`: Marks the field as a file descriptor.
-/// - `#[descriptor(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
-/// - `#[descriptor(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
-/// - `#[descriptor(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
-#[proc_macro_derive(AsFileDescriptor, attributes(read, write, descriptor, duck))]
-pub fn derive_io_as_file_descriptor(input: TokenStream) -> TokenStream {
-    generate("derive_io", "derive_io_as_file_descriptor", input)
-}
-
-/// `#[derive(AsSocketDescriptor)]`
-///
-/// Derives `std::os::fd::{AsFd, AsRawFd}` and `std::os::windows::io::{AsSocket, AsRawSocket}` for the given struct.
-///
-/// Supported attributes:
-///
-/// - `#[descriptor]`: Marks the field as a socket descriptor.
-/// - `#[descriptor(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
-/// - `#[descriptor(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
-/// - `#[descriptor(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
-#[proc_macro_derive(AsSocketDescriptor, attributes(read, write, descriptor, duck))]
-pub fn derive_io_as_socket_descriptor(input: TokenStream) -> TokenStream {
-    generate("derive_io", "derive_io_as_socket_descriptor", input)
-}
-
-/// Generates the equivalent of this Rust code as a TokenStream:
-///
-/// ```nocompile
-/// ::ctor::__support::ctor_parse!(#[ctor] fn foo() { ... });
-/// ::dtor::__support::dtor_parse!(#[dtor] fn foo() { ... });
-/// ```
-#[allow(unknown_lints, tail_expr_drop_order)]
-fn generate(macro_crate: &str, macro_type: &str, item: TokenStream) -> TokenStream {
-    let mut generics = TokenStream::new();
-    let mut where_clause = TokenStream::new();
-    let mut new_item = TokenStream::new();
-    let mut iterator = item.into_iter();
-
-    // Parse out generics and where clause into something easier for macros to digest:
-    //  - Generic bounds are moved to where clause, leaving just types/lifetimes
-    //  - If a generic has no bounds, we don't add it to the where clause
-    let mut in_generics = false;
-    let mut generics_ident = false;
-    let mut generics_accum = TokenStream::new();
-    let mut in_where_clause = false;
-    let mut in_generic_default = false;
-    let mut in_generic_const = false;
-    for token in iterator.by_ref() {
-        match token {
-            TokenTree::Punct(p) if !in_where_clause && p.as_char() == '<' => {
-                in_generics = true;
-                generics_ident = true;
-            }
-            TokenTree::Punct(ref p) if !in_where_clause && p.as_char() == '>' => {
-                if in_generics {
-                    in_generics = false;
-                    if generics_ident {
-                        generics.extend([TokenTree::Group(Group::new(
-                            Delimiter::Parenthesis,
-                            std::mem::take(&mut generics_accum),
-                        ))]);
-                    }
-                    if !generics_accum.is_empty() {
-                        panic!();
-                    }
-                }
-            }
-            TokenTree::Punct(ref p) if p.as_char() == ':' => {
-                if in_generics {
-                    if in_generic_const {
-                        generics_accum.extend([token]);
-                    } else {
-                        if generics_ident {
-                            generics.extend([TokenTree::Group(Group::new(
-                                Delimiter::Parenthesis,
-                                generics_accum.clone(),
-                            ))]);
-                        }
-                        generics_ident = false;
-                        where_clause.extend(std::mem::take(&mut generics_accum));
-                        where_clause.extend([token]);
-                    }
-                } else if in_where_clause {
-                    where_clause.extend([token]);
-                } else {
-                    new_item.extend([token]);
-                }
-            }
-            TokenTree::Punct(ref p) if p.as_char() == ',' => {
-                if in_generics {
-                    if generics_ident {
-                        generics.extend([TokenTree::Group(Group::new(
-                            Delimiter::Parenthesis,
-                            std::mem::take(&mut generics_accum),
-                        ))]);
-                    } else if !in_generic_const {
-                        where_clause.extend([token.clone()]);
-                    }
-                    generics.extend([token]);
-                    generics_ident = true;
-                    in_generic_default = false;
-                    in_generic_const = false;
-                } else if in_where_clause {
-                    where_clause.extend([token]);
-                } else {
-                    new_item.extend([token]);
-                }
-            }
-            TokenTree::Punct(ref p) if p.as_char() == '=' => {
-                if in_generics {
-                    generics_ident = false;
-                    in_generic_default = true;
-                    if in_generic_const {
-                        generics.extend([TokenTree::Group(Group::new(
-                            Delimiter::Parenthesis,
-                            std::mem::take(&mut generics_accum),
-                        ))]);
-                    }
-                }
-            }
-            TokenTree::Ident(ref l) if l.to_string() == "const" => {
-                panic!("const not yet supported");
-                // if in_generics {
-                //     generics_ident = true;
-                //     in_generic_const = true;
-                //     generics_accum.extend([token.clone()]);
-                // }
-            }
-            TokenTree::Ident(l) if l.to_string() == "where" => {
-                in_where_clause = true;
-            }
-            TokenTree::Group(ref p) if p.delimiter() == Delimiter::Brace => {
-                new_item.extend([token]);
-                break;
-            }
-            _ => {
-                if in_generics {
-                    if generics_ident {
-                        generics_accum.extend([token]);
-                    } else if !in_generic_default {
-                        where_clause.extend([token]);
-                    }
-                } else if in_where_clause {
-                    where_clause.extend([token]);
-                } else {
-                    new_item.extend([token]);
-                }
-            }
+mod generate;
+mod parse;
+
+/// Declares a derive with the helper attributes it consumes. A derive that
+/// delegates through `generate::derive` (`delegate <attr>`) also consumes the
+/// `#[duck]`, `#[pin]` and `#[io]` attributes.
+macro_rules! derive {
+    (
+        $(#[$doc:meta])*
+        $name:ident(delegate $($helper:ident),*) => $fn:ident($input:ident) $body:expr
+    ) => {
+        derive! {
+            $(#[$doc])*
+            $name($($helper,)* duck, pin, io) => $fn($input) $body
+        }
+    };
+    (
+        $(#[$doc:meta])*
+        $name:ident($($helper:ident),*) => $fn:ident($input:ident) $body:expr
+    ) => {
+        $(#[$doc])*
+        #[proc_macro_derive($name, attributes($($helper),*))]
+        pub fn $fn($input: TokenStream) -> TokenStream {
+            $body
         }
-    }
-    new_item.extend(iterator);
-
-    let mut inner = TokenStream::new();
-    inner.extend([
-        TokenTree::Group(Group::new(Delimiter::Parenthesis, new_item)),
-        TokenTree::Group(Group::new(Delimiter::Parenthesis, generics)),
-        TokenTree::Group(Group::new(Delimiter::Parenthesis, where_clause)),
-    ]);
-
-    let mut invoke = TokenStream::from_iter([
-        TokenTree::Punct(Punct::new(':', Spacing::Joint)),
-        TokenTree::Punct(Punct::new(':', Spacing::Alone)),
-        TokenTree::Ident(Ident::new(macro_crate, Span::call_site())),
-    ]);
-    invoke.extend([
-        TokenTree::Punct(Punct::new(':', Spacing::Joint)),
-        TokenTree::Punct(Punct::new(':', Spacing::Alone)),
-        TokenTree::Ident(Ident::new("__support", Span::call_site())),
-        TokenTree::Punct(Punct::new(':', Spacing::Joint)),
-        TokenTree::Punct(Punct::new(':', Spacing::Alone)),
-        TokenTree::Ident(Ident::new(
-            &format!("{macro_type}_parse"),
-            Span::call_site(),
-        )),
-        TokenTree::Punct(Punct::new('!', Spacing::Alone)),
-        TokenTree::Group(Group::new(Delimiter::Parenthesis, inner)),
-        TokenTree::Punct(Punct::new(';', Spacing::Alone)),
-    ]);
-
-    invoke
-}
-
-fn expect_any(named: &str, iterator: &mut impl Iterator<Item = TokenTree>) -> TokenTree {
-    let next = iterator.next();
-    let Some(token) = next else {
-        panic!("Expected {named} token, got end of stream");
     };
-    token
 }
 
-fn expect_group(named: &str, iterator: &mut impl Iterator<Item = TokenTree>) -> Group {
-    let next = iterator.next();
-    let Some(TokenTree::Group(group)) = next else {
-        panic!("Expected {named} group, got {next:?}");
-    };
-    group
+derive! {
+    /// `#[derive(Read)]`
+    ///
+    /// Derives `std::io::Read` for the given struct.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[read]`: Marks the field as a read stream.
+    /// - `#[read(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[read(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[read(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[read(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[buffer]`: Marks a `derive_io::ReadBuffer` field. Reads are served from the buffer first.
+    /// - `#[read(inspect = <function>)]`: Calls `<function>(&self, &[u8])` with the bytes read.
+    /// - `#[read(map_err = <function>)]`: Maps errors from the field with `<function>(std::io::Error) -> std::io::Error`.
+    /// - `#[io(on_error = <function>)]` (on the type): Calls `<function>(&self, &std::io::Error)` when a method fails.
+    /// - `#[io(trace)]` (on the type): Emits a `tracing` event for each call. Requires the `tracing` feature.
+    /// - `#[io(context)]` (on the type): Wraps errors in a `derive_io::ErrorContext` naming the type, variant and field.
+    /// - `#[io(transcript = <path>)]` (on the type): Records the bytes read and written to a `derive_io::Transcript` file.
+    /// - `#[io(transcript)]`: Marks the `derive_io::TranscriptStream` field of each case, required by `#[io(transcript = <path>)]`.
+    Read(delegate read, buffer) => derive_io_read(input)
+        generate::derive("derive_io", &generate::READ, input)
 }
 
-fn expect_ident(named: &str, iterator: &mut impl Iterator<Item = TokenTree>) -> Ident {
-    let next = iterator.next();
-    let Some(TokenTree::Ident(ident)) = next else {
-        panic!("Expected {named} ident, got {next:?}");
-    };
-    ident
+derive! {
+    /// `#[derive(BufRead)]`
+    ///
+    /// Derives `std::io::BufRead` for the given struct. `std::io::Read` must also
+    /// be implemented.
+    ///
+    /// Unsupported methods:
+    ///
+    /// - `split` (std-internal implementation)
+    /// - `lines` (std-internal implementation)
+    /// - `has_data_left` (unstable feature)
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[read]`: Marks the field as a read stream.
+    /// - `#[read(as_ref)]`: Delegates the field to the inner type using
+    ///   `AsRef`/`AsMut`.
+    /// - `#[read(deref)]`: Delegates the field to the inner type using
+    ///   `Deref`/`DerefMut`.
+    /// - `#[read(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[read(<function>=<override>)]`: Overrides the default `<function>`
+    ///   method with the given override function.
+    /// - `#[buffer]`: Marks a `derive_io::ReadBuffer` field, which buffers the
+    ///   `std::io::Read` implementation of the `#[read]` field.
+    /// - `#[read(map_err = <function>)]`: Maps errors from the field with `<function>(std::io::Error) -> std::io::Error`.
+    /// - `#[io(on_error = <function>)]` (on the type): Calls `<function>(&self, &std::io::Error)` when a method fails.
+    /// - `#[io(trace)]` (on the type): Emits a `tracing` event for each call. Requires the `tracing` feature.
+    /// - `#[io(context)]` (on the type): Wraps errors in a `derive_io::ErrorContext` naming the type, variant and field.
+    BufRead(delegate read, buffer) => derive_io_bufread(input)
+        generate::derive("derive_io", &generate::BUF_READ, input)
 }
 
-fn expect_literal(named: &str, iterator: &mut impl Iterator<Item = TokenTree>) -> Literal {
-    let next = iterator.next();
-    if let Some(TokenTree::Group(ref group)) = next {
-        if group.delimiter() == Delimiter::None {
-            let mut iter = group.stream().into_iter();
-            return expect_literal(named, &mut iter);
-        }
-    }
-    let Some(TokenTree::Literal(literal)) = next else {
-        panic!("Expected {named} literal, got {next:?}");
-    };
-    literal
-}
-
-/// Unwrap a grouped meta element to its final group.
-fn expect_is_meta(named: &str, mut attr: TokenTree) -> Group {
-    let outer = attr.clone();
-    while let TokenTree::Group(group) = attr {
-        let mut iter = group.clone().stream().into_iter();
-        let first = iter
-            .next()
-            .expect("Expected attr group to have one element");
-        if let TokenTree::Ident(_) = first {
-            return Group::new(Delimiter::Bracket, group.stream());
-        }
-        attr = first;
-    }
-    panic!("Expected meta group {named}, got {outer}");
-}
-
-/// [
-///   (__next__) (args) expected_attr {on_error}
-///   ((attr attr) (item)) ((attr attr) (item))
-/// ] -> __next__!((args) (item))
-#[proc_macro]
-pub fn find_annotated(input: TokenStream) -> TokenStream {
-    let mut iterator = input.into_iter();
-
-    let next_macro = expect_group("__next__ macro", &mut iterator);
-    let args = expect_group("__next__ arguments", &mut iterator);
-    let expected_attr = expect_ident("expected_attr", &mut iterator);
-    let on_error = expect_group("on_error", &mut iterator);
-
-    for token in iterator {
-        let TokenTree::Group(check) = token else {
-            panic!("Expected check group");
-        };
-        let mut iter = check.stream().into_iter();
-        let attrs = expect_group("attrs", &mut iter);
-        let item = expect_any("item", &mut iter);
-        for (index, attr) in attrs.stream().into_iter().enumerate() {
-            let attr = expect_is_meta("attr", attr);
-            let first = expect_ident("first attr", &mut attr.clone().stream().into_iter());
-            if first.to_string() == expected_attr.to_string() {
-                let mut next = next_macro.stream();
-                next.extend([
-                    TokenTree::Punct(Punct::new('!', Spacing::Alone)),
-                    TokenTree::Group(Group::new(
-                        Delimiter::Parenthesis,
-                        TokenStream::from_iter([
-                            TokenTree::Group(args),
-                            TokenTree::Literal(Literal::usize_unsuffixed(index)),
-                            TokenTree::Group(attr),
-                            item,
-                        ]),
-                    )),
-                    TokenTree::Punct(Punct::new(';', Spacing::Alone)),
-                ]);
-                return next;
-            }
-        }
-    }
+derive! {
+    /// `#[derive(Write)]`
+    ///
+    /// Derives `std::io::Write` for the given struct.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[write]`: Marks the field as a write stream.
+    /// - `#[write(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[write(deref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[write(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[write(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[write(buffered)]`: Coalesces writes in the `derive_io::WriteBuffer` field marked `#[buffer(write)]`.
+    /// - `#[write(flush_on_drop)]`: Generates a `Drop` impl that flushes the type. Errors are passed to the given
+    ///   function with `#[write(flush_on_drop = <function>)]`, or else emitted as a `tracing` event with the `tracing`
+    ///   feature.
+    /// - `#[write(inspect = <function>)]`: Calls `<function>(&self, &[u8])` with the bytes accepted by each write.
+    /// - `#[write(map_err = <function>)]`: Maps errors from the field with `<function>(std::io::Error) -> std::io::Error`.
+    /// - `#[io(on_error = <function>)]` (on the type): Calls `<function>(&self, &std::io::Error)` when a method fails.
+    /// - `#[io(trace)]` (on the type): Emits a `tracing` event for each call. Requires the `tracing` feature.
+    /// - `#[io(context)]` (on the type): Wraps errors in a `derive_io::ErrorContext` naming the type, variant and field.
+    /// - `#[io(transcript = <path>)]` (on the type): Records the bytes read and written to a `derive_io::Transcript` file.
+    /// - `#[io(transcript)]`: Marks the `derive_io::TranscriptStream` field of each case, required by `#[io(transcript = <path>)]`.
+    Write(delegate write, buffer) => derive_io_write(input)
+        generate::derive("derive_io", &generate::WRITE, input)
+}
 
-    on_error.stream()
+derive! {
+    /// `#[derive(FmtWrite)]`
+    ///
+    /// Derives `core::fmt::Write` for the given struct, so that it can be used with
+    /// `write!`.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[fmt_write]`: Marks the field as a `fmt::Write` writer.
+    /// - `#[fmt_write(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[fmt_write(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[fmt_write(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[fmt_write(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[fmt_write(from_io)]`: Adapts a `std::io::Write` field, mapping `io::Error` to `fmt::Error`.
+    /// - `#[fmt_write(from_io = <member>)]`: As above, and stores the last `io::Error` in the `Option<std::io::Error>`
+    ///   field `<member>` of the same struct or variant.
+    FmtWrite(delegate fmt_write) => derive_io_fmt_write(input)
+        generate::derive("derive_io", &generate::FMT_WRITE, input)
 }
 
-/// [(__next__) (args) expected_attr {on_error}
-///  ( (id) (([attr] [attr]) (item)) (([attr] [attr]) (item)) )
-///  ( (id) (([attr] [attr]) (item)) (([attr] [attr]) (item)) )
-/// ] -> __next__!((args) ((id) (item)))
-#[proc_macro]
-pub fn find_annotated_multi(input: TokenStream) -> TokenStream {
-    let mut iterator = input.into_iter();
+derive! {
+    /// `#[derive(PositionalIo)]`
+    ///
+    /// Derives `derive_io::PositionalIo` for the given struct.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[positional]`: Marks the field as a positional store.
+    /// - `#[positional(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[positional(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[positional(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[positional(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[positional(inspect = <function>)]`: Calls `<function>(&self, &[u8])` with the bytes read or written by `read_at`/`write_at`.
+    /// - `#[positional(map_err = <function>)]`: Maps errors from the field with `<function>(std::io::Error) -> std::io::Error`.
+    /// - `#[io(on_error = <function>)]` (on the type): Calls `<function>(&self, &std::io::Error)` when a method fails.
+    /// - `#[io(trace)]` (on the type): Emits a `tracing` event for each call. Requires the `tracing` feature.
+    /// - `#[io(context)]` (on the type): Wraps errors in a `derive_io::ErrorContext` naming the type, variant and field.
+    PositionalIo(delegate positional) => derive_io_positional_io(input)
+        generate::derive("derive_io", &generate::POSITIONAL_IO, input)
+}
 
-    let next_macro = expect_group("__next__ macro", &mut iterator);
-    let args = expect_group("__next__ arguments", &mut iterator);
-    let expected_attr = expect_ident("expected_attr", &mut iterator);
-    let on_error = expect_group("on_error", &mut iterator);
-    let mut output = TokenStream::new();
+derive! {
+    /// `#[derive(FileExt)]`
+    ///
+    /// Derives `std::os::unix::fs::FileExt` for the given struct (unix only).
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[positional]`: Marks the field as a positional store.
+    /// - `#[positional(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[positional(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[positional(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[positional(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[positional(inspect = <function>)]`: Calls `<function>(&self, &[u8])` with the bytes read or written by `read_at`/`write_at`.
+    /// - `#[positional(map_err = <function>)]`: Maps errors from the field with `<function>(std::io::Error) -> std::io::Error`.
+    /// - `#[io(on_error = <function>)]` (on the type): Calls `<function>(&self, &std::io::Error)` when a method fails.
+    /// - `#[io(trace)]` (on the type): Emits a `tracing` event for each call. Requires the `tracing` feature.
+    /// - `#[io(context)]` (on the type): Wraps errors in a `derive_io::ErrorContext` naming the type, variant and field.
+    FileExt(delegate positional) => derive_io_file_ext(input)
+        generate::derive("derive_io", &generate::FILE_EXT, input)
+}
 
-    'outer: for token in iterator {
-        let TokenTree::Group(id) = token else {
-            panic!("Expected id group");
-        };
-        let mut iter = id.stream().into_iter();
-        let id = expect_group("id", &mut iter);
-        let mut index = 0;
-        for token in iter {
-            let TokenTree::Group(check) = token else {
-                panic!("Expected check group");
-            };
-            let mut iter = check.stream().into_iter();
-            let attrs = expect_group("attrs", &mut iter);
-            let item = expect_any("item", &mut iter);
-            for attr in attrs.stream().into_iter() {
-                let attr = expect_is_meta("attr", attr);
-                let first = expect_ident("first attr", &mut attr.clone().stream().into_iter());
-                if first.to_string() == expected_attr.to_string() {
-                    output.extend([TokenTree::Group(Group::new(
-                        Delimiter::Parenthesis,
-                        TokenStream::from_iter([
-                            TokenTree::Group(id.clone()),
-                            TokenTree::Literal(Literal::usize_unsuffixed(index)),
-                            TokenTree::Group(attr),
-                            item.clone(),
-                        ]),
-                    ))]);
-                    continue 'outer;
-                }
-            }
-            index += 1;
-        }
-        return on_error.stream();
-    }
+derive! {
+    /// `#[derive(AsyncRead)]`:
+    ///
+    /// Derives `tokio::io::AsyncRead` for the given struct.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[read]`: Marks the field as a read stream.
+    /// - `#[read(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[read(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[read(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[read(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[pin]`: Delegates to a structurally pinned field, which need not be `Unpin`. Requires `#[derive(PinProject)]`.
+    /// - `#[buffer]`: Marks a `derive_io::ReadBuffer` field. Reads are served from the buffer first.
+    /// - `#[read(inspect = <function>)]`: Calls `<function>(&self, &[u8])` with the bytes read.
+    /// - `#[read(map_err = <function>)]`: Maps errors from the field with `<function>(std::io::Error) -> std::io::Error`.
+    /// - `#[io(on_error = <function>)]` (on the type): Calls `<function>(&self, &std::io::Error)` when a method fails.
+    /// - `#[io(trace)]` (on the type): Emits a `tracing` event for each call. Requires the `tracing` feature.
+    /// - `#[io(context)]` (on the type): Wraps errors in a `derive_io::ErrorContext` naming the type, variant and field.
+    /// - `#[io(transcript = <path>)]` (on the type): Records the bytes read and written to a `derive_io::Transcript` file.
+    /// - `#[io(transcript)]`: Marks the `derive_io::TranscriptStream` field of each case, required by `#[io(transcript = <path>)]`.
+    /// - `#[io(on_pending = <function>)]` (on the type): Calls `<function>(&self)` when a method returns `Poll::Pending`.
+    AsyncRead(delegate read, buffer) => derive_io_async_read(input)
+        generate::derive("derive_io", &generate::ASYNC_READ, input)
+}
 
-    let mut next = next_macro.stream();
-    next.extend([
-        TokenTree::Punct(Punct::new('!', Spacing::Alone)),
-        TokenTree::Group(Group::new(
-            Delimiter::Parenthesis,
-            TokenStream::from_iter([
-                TokenTree::Group(args),
-                TokenTree::Group(Group::new(Delimiter::Parenthesis, output)),
-            ]),
-        )),
-        TokenTree::Punct(Punct::new(';', Spacing::Alone)),
-    ]);
-    next
-}
-
-/// [prefix count repeated suffix] -> prefix (repeated*count suffix)
-#[proc_macro]
-pub fn repeat_in_parenthesis(input: TokenStream) -> TokenStream {
-    let mut iterator = input.into_iter();
-    let prefix = expect_group("prefix", &mut iterator);
-    let count = expect_literal("count", &mut iterator);
-    let count: usize = str::parse(&count.to_string()).expect("Expected count to be a number");
-    let repeated = expect_group("repeated", &mut iterator);
-    let suffix = expect_group("suffix", &mut iterator);
-    let mut repeat = TokenStream::new();
-    for _ in 0..count {
-        repeat.extend(repeated.clone().stream());
-    }
-    repeat.extend(suffix.stream());
-    let mut output = TokenStream::new();
-    output.extend(prefix.stream());
-    output.extend([TokenTree::Group(Group::new(Delimiter::Parenthesis, repeat))]);
-    output
+derive! {
+    /// `#[derive(AsyncBufRead)]`:
+    ///
+    /// Derives `tokio::io::AsyncBufRead` for the given struct. `tokio::io::AsyncRead`
+    /// must also be implemented.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[read]`: Marks the field as a read stream.
+    /// - `#[read(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[read(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[read(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[read(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[pin]`: Delegates to a structurally pinned field, which need not be `Unpin`. Requires `#[derive(PinProject)]`.
+    /// - `#[buffer]`: Marks a `derive_io::ReadBuffer` field, which buffers the
+    ///   `tokio::io::AsyncRead` implementation of the `#[read]` field.
+    /// - `#[read(map_err = <function>)]`: Maps errors from the field with `<function>(std::io::Error) -> std::io::Error`.
+    /// - `#[io(on_error = <function>)]` (on the type): Calls `<function>(&self, &std::io::Error)` when a method fails.
+    /// - `#[io(trace)]` (on the type): Emits a `tracing` event for each call. Requires the `tracing` feature.
+    /// - `#[io(context)]` (on the type): Wraps errors in a `derive_io::ErrorContext` naming the type, variant and field.
+    /// - `#[io(on_pending = <function>)]` (on the type): Calls `<function>(&self)` when a method returns `Poll::Pending`.
+    AsyncBufRead(delegate read, buffer) => derive_io_async_bufread(input)
+        generate::derive("derive_io", &generate::ASYNC_BUF_READ, input)
 }
 
-// needle haystack(key=value,key=value) default -> extracted OR default
-#[proc_macro]
-pub fn extract_meta(input: TokenStream) -> TokenStream {
-    let mut iterator = input.into_iter();
-    let needle = expect_ident("needle", &mut iterator);
-    let haystack = expect_group("haystack", &mut iterator);
-    let default = expect_group("default", &mut iterator);
+derive! {
+    /// `#[derive(AsyncWrite)]`:
+    ///
+    /// Derives `tokio::io::AsyncWrite` for the given struct.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[write]`: Marks the field as a write stream.
+    /// - `#[write(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[write(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[write(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[write(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[pin]`: Delegates to a structurally pinned field, which need not be `Unpin`. Requires `#[derive(PinProject)]`.
+    /// - `#[write(buffered)]`: Coalesces writes in the `derive_io::WriteBuffer` field marked `#[buffer(write)]`.
+    /// - `#[write(flush_on_drop)]`: Generates a `Drop` impl that polls `poll_flush` once. Errors (including an
+    ///   incomplete flush, as `WouldBlock`) are passed to the given function with
+    ///   `#[write(flush_on_drop = <function>)]`, or else emitted as a `tracing` event with the `tracing` feature.
+    /// - `#[write(inspect = <function>)]`: Calls `<function>(&self, &[u8])` with the bytes accepted by each write.
+    /// - `#[write(map_err = <function>)]`: Maps errors from the field with `<function>(std::io::Error) -> std::io::Error`.
+    /// - `#[io(on_error = <function>)]` (on the type): Calls `<function>(&self, &std::io::Error)` when a method fails.
+    /// - `#[io(trace)]` (on the type): Emits a `tracing` event for each call. Requires the `tracing` feature.
+    /// - `#[io(context)]` (on the type): Wraps errors in a `derive_io::ErrorContext` naming the type, variant and field.
+    /// - `#[io(transcript = <path>)]` (on the type): Records the bytes read and written to a `derive_io::Transcript` file.
+    /// - `#[io(transcript)]`: Marks the `derive_io::TranscriptStream` field of each case, required by `#[io(transcript = <path>)]`.
+    /// - `#[io(on_pending = <function>)]` (on the type): Calls `<function>(&self)` when a method returns `Poll::Pending`.
+    AsyncWrite(delegate write, buffer) => derive_io_async_write(input)
+        generate::derive("derive_io", &generate::ASYNC_WRITE, input)
+}
 
-    let mut haystack = haystack.stream().into_iter();
+derive! {
+    /// `#[derive(AsFileDescriptor)]`
+    ///
+    /// Derives `std::os::fd::{AsFd, AsRawFd}` and `std::os::windows::io::{AsHandle, AsRawHandle}` for the given struct.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[descriptor]`: Marks the field as a file descriptor.
+    /// - `#[descriptor(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[descriptor(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[descriptor(duck)]`: Delegates to an inherent `as_fd`/`as_handle`/`as_socket` method on the type (duck typing).
+    /// - `#[descriptor(unsafe(raw))]`: Uses the value of a `RawFd` field as the descriptor (unix only). The
+    ///   descriptor must stay open while the type is borrowed, as it is borrowed with `BorrowedFd::borrow_raw`.
+    /// - `#[descriptor(unsafe(raw_via = <method>))]`: Uses the `RawFd` returned by `<method>` of the field (unix
+    ///   only), which must stay open in the same way.
+    ///
+    ///   A raw descriptor must not be `-1`, which panics when it is borrowed.
+    /// - `#[descriptor(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    AsFileDescriptor(delegate descriptor) => derive_io_as_file_descriptor(input)
+        generate::derive("derive_io", &generate::AS_FILE_DESCRIPTOR, input)
+}
 
-    loop {
-        let attr = haystack.next();
-        if let Some(TokenTree::Group(ref group)) = attr {
-            haystack = group.stream().into_iter();
-            continue;
-        }
-        break;
-    }
+derive! {
+    /// `#[derive(AsSocketDescriptor)]`
+    ///
+    /// Derives `std::os::fd::{AsFd, AsRawFd}` and `std::os::windows::io::{AsSocket, AsRawSocket}` for the given struct.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[descriptor]`: Marks the field as a socket descriptor.
+    /// - `#[descriptor(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[descriptor(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[descriptor(duck)]`: Delegates to an inherent `as_fd`/`as_handle`/`as_socket` method on the type (duck typing).
+    /// - `#[descriptor(unsafe(raw))]`: Uses the value of a `RawFd` field as the descriptor (unix only). The
+    ///   descriptor must stay open while the type is borrowed, as it is borrowed with `BorrowedFd::borrow_raw`.
+    /// - `#[descriptor(unsafe(raw_via = <method>))]`: Uses the `RawFd` returned by `<method>` of the field (unix
+    ///   only), which must stay open in the same way.
+    ///
+    ///   A raw descriptor must not be `-1`, which panics when it is borrowed.
+    /// - `#[descriptor(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    AsSocketDescriptor(delegate descriptor) => derive_io_as_socket_descriptor(input)
+        generate::derive("derive_io", &generate::AS_SOCKET_DESCRIPTOR, input)
+}
 
-    loop {
-        let key = haystack.next();
-        if let Some(TokenTree::Group(ref group)) = key {
-            haystack = group.stream().into_iter();
-            continue;
-        }
-        let Some(key) = key else {
-            break;
-        };
-        let Some(next) = haystack.next() else {
-            break;
-        };
-        // Ignore simple attributes
-        let TokenTree::Punct(punct) = next else {
-            panic!("Expected = after key, got {next:?}");
-        };
-
-        if punct.as_char() == ',' {
-            continue;
-        }
+derive! {
+    /// `#[derive(AsReadWriteFds)]`
+    ///
+    /// Derives `derive_io::AsReadWriteFds` for the given struct (unix only), which borrows separate descriptors for
+    /// reading and writing.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[descriptor(read)]`: Marks the field as the descriptor for reading.
+    /// - `#[descriptor(write)]`: Marks the field as the descriptor for writing.
+    /// - `#[descriptor]`: Marks the field as the descriptor for both, unless another field has the role.
+    /// - `#[descriptor(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[descriptor(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[descriptor(duck)]`: Delegates to an inherent `as_fd` method on the type (duck typing).
+    /// - `#[descriptor(unsafe(raw))]`: Uses the value of a `RawFd` field as the descriptor (unix only). The
+    ///   descriptor must stay open while the type is borrowed, as it is borrowed with `BorrowedFd::borrow_raw`.
+    /// - `#[descriptor(unsafe(raw_via = <method>))]`: Uses the `RawFd` returned by `<method>` of the field (unix
+    ///   only), which must stay open in the same way.
+    ///
+    ///   A raw descriptor must not be `-1`, which panics when it is borrowed.
+    /// - `#[descriptor(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    AsReadWriteFds(delegate descriptor) => derive_io_as_read_write_fds(input)
+        generate::derive("derive_io", &generate::AS_READ_WRITE_FDS, input)
+}
 
-        let value = expect_ident("value", &mut haystack);
+derive! {
+    /// `#[derive(IntoFileDescriptor)]`
+    ///
+    /// Derives `std::os::fd::IntoRawFd` and `From<Self> for std::os::fd::OwnedFd` for the given struct (unix only),
+    /// by moving the descriptor field out and dropping the other fields.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[descriptor]`: Marks the field as a file descriptor.
+    /// - `#[descriptor(from)]`: Also derives `std::os::fd::FromRawFd` and `From<std::os::fd::OwnedFd>` for a struct
+    ///   with a single field. This is opt-in, as some fields (ie: `std::process::ChildStdin`) cannot be built from
+    ///   a descriptor.
+    /// - `#[descriptor(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    IntoFileDescriptor(delegate descriptor) => derive_io_into_file_descriptor(input)
+        generate::into_file_descriptor("derive_io", input)
+}
 
-        if key.to_string() == needle.to_string() {
-            return TokenStream::from_iter([TokenTree::Ident(value)]);
-        }
+derive! {
+    /// `#[derive(FromDescriptor)]`
+    ///
+    /// Derives `TryFrom<std::os::fd::OwnedFd>` for the given enum (unix only), which probes the kind of the
+    /// descriptor and builds the first variant that accepts it. The error is a `derive_io::FromDescriptorError`,
+    /// which returns the descriptor.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[descriptor(kind = <kind>)]` (on a variant): Builds the variant's single field with `From<OwnedFd>` for
+    ///   descriptors of the given kind: `tcp`, `tcp_listener`, `udp`, `unix_stream`, `unix_listener`, `pipe`, `file`
+    ///   or `tty`. Several kinds may be given, ie: `kind = file | pipe`.
+    FromDescriptor(descriptor) => derive_io_from_descriptor(input)
+        generate::from_descriptor("derive_io", input)
+}
 
-        if haystack.next().is_none() {
-            break;
-        }
-    }
+derive! {
+    /// `#[derive(MaybeAsFd)]`
+    ///
+    /// Derives `derive_io::{MaybeAsFd, MaybeAsRawFd}` for the given struct (unix only), for types where some
+    /// variants have no file descriptor.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[descriptor]`: Marks the field as a file descriptor. The field must implement `MaybeAsFd` and
+    ///   `MaybeAsRawFd`, as every `AsFd` and `AsRawFd` type does.
+    /// - `#[descriptor(none)]` (on a variant): The variant has no file descriptor. Variants without a
+    ///   `#[descriptor]` field have no file descriptor either.
+    /// - `#[descriptor(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[descriptor(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[descriptor(duck)]`: Delegates to inherent `try_as_fd`/`try_as_raw_fd` methods on the type (duck typing).
+    /// - `#[descriptor(unsafe(raw))]`: Uses the value of a `RawFd` field as the descriptor (unix only). The
+    ///   descriptor must stay open while the type is borrowed, as it is borrowed with `BorrowedFd::borrow_raw`.
+    /// - `#[descriptor(unsafe(raw_via = <method>))]`: Uses the `RawFd` returned by `<method>` of the field (unix
+    ///   only), which must stay open in the same way.
+    ///
+    ///   A raw descriptor of `-1` is treated as absent.
+    /// - `#[descriptor(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    MaybeAsFd(delegate descriptor) => derive_io_maybe_as_fd(input)
+        generate::derive("derive_io", &generate::MAYBE_AS_FD, input)
+}
 
-    default.stream()
+derive! {
+    /// `#[derive(MioSource)]`
+    ///
+    /// Derives `mio::event::Source` for the given struct, so that it can be
+    /// registered with a `mio::Registry`.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[descriptor]`: Marks the field as an event source.
+    /// - `#[descriptor(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[descriptor(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[descriptor(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[descriptor(source_fd)]`: Registers the raw descriptor of a field that only implements `AsRawFd`,
+    ///   using `mio::unix::SourceFd` (unix only). Other derives use the field as usual.
+    /// - `#[descriptor(unsafe(raw))]` or `#[descriptor(unsafe(raw_via = <method>))]`: Registers a `RawFd` field,
+    ///   or the `RawFd` returned by `<method>` of the field, using `mio::unix::SourceFd` (unix only). The
+    ///   descriptor must not be `-1`.
+    /// - `#[descriptor(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[descriptor(map_err = <function>)]`: Maps errors from the field with `<function>(std::io::Error) -> std::io::Error`.
+    /// - `#[io(on_error = <function>)]` (on the type): Calls `<function>(&self, &std::io::Error)` when a method fails.
+    /// - `#[io(trace)]` (on the type): Emits a `tracing` event for each call. Requires the `tracing` feature.
+    /// - `#[io(context)]` (on the type): Wraps errors in a `derive_io::ErrorContext` naming the type, variant and field.
+    MioSource(delegate descriptor) => derive_io_mio_source(input)
+        generate::derive("derive_io", &generate::MIO_SOURCE, input)
 }
 
-#[proc_macro]
-pub fn if_meta(input: TokenStream) -> TokenStream {
-    let mut iterator = input.into_iter();
-    let needle = expect_ident("needle", &mut iterator);
-    let haystack = expect_group("haystack", &mut iterator);
-    let if_true = expect_group("if_true", &mut iterator);
-    let if_false = expect_group("if_false", &mut iterator);
+derive! {
+    /// `#[derive(SocketAddrs)]`
+    ///
+    /// Derives `derive_io::SocketAddrs` for the given struct or enum, which returns the local and peer addresses of the
+    /// socket as a `derive_io::SocketAddrAny`.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[descriptor]`: Marks the field as the socket.
+    /// - `#[descriptor(as_ref)]`: Delegates the field to the inner type using `AsRef`.
+    /// - `#[descriptor(deref)]`: Delegates the field to the inner type using `Deref`.
+    /// - `#[descriptor(duck)]`: Delegates to the inherent `local_addr` and `peer_addr` methods of the type, converting
+    ///   the address with `Into<SocketAddrAny>`.
+    /// - `#[descriptor(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[descriptor(map_err = <function>)]`: Maps errors from the field with `<function>(std::io::Error) -> std::io::Error`.
+    /// - `#[io(on_error = <function>)]` (on the type): Calls `<function>(&self, &std::io::Error)` when a method fails.
+    /// - `#[io(trace)]` (on the type): Emits a `tracing` event for each call. Requires the `tracing` feature.
+    /// - `#[io(context)]` (on the type): Wraps errors in a `derive_io::ErrorContext` naming the type, variant and field.
+    SocketAddrs(delegate descriptor) => derive_io_socket_addrs(input)
+        generate::derive("derive_io", &generate::SOCKET_ADDRS, input)
+}
 
-    let mut haystack = haystack.stream().into_iter();
+derive! {
+    /// `#[derive(SocketControl)]`
+    ///
+    /// Derives `derive_io::SocketControl` for the given struct or enum, which forwards socket options (`set_nodelay`,
+    /// `set_ttl`, timeouts and so on), `shutdown`, `try_clone` and `take_error` to the socket. Operations that the
+    /// socket does not support return an error of kind `Unsupported`.
+    ///
+    /// `try_clone` rebuilds the case from the cloned field, so it is only supported for cases with a single field that
+    /// is delegated directly or with `duck`.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[descriptor]`: Marks the field as the socket.
+    /// - `#[descriptor(as_ref)]`: Delegates the field to the inner type using `AsRef`.
+    /// - `#[descriptor(deref)]`: Delegates the field to the inner type using `Deref`.
+    /// - `#[descriptor(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[descriptor(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[descriptor(map_err = <function>)]`: Maps errors from the field with `<function>(std::io::Error) -> std::io::Error`.
+    /// - `#[io(on_error = <function>)]` (on the type): Calls `<function>(&self, &std::io::Error)` when a method fails.
+    /// - `#[io(trace)]` (on the type): Emits a `tracing` event for each call. Requires the `tracing` feature.
+    /// - `#[io(context)]` (on the type): Wraps errors in a `derive_io::ErrorContext` naming the type, variant and field.
+    SocketControl(delegate descriptor) => derive_io_socket_control(input)
+        generate::derive("derive_io", &generate::SOCKET_CONTROL, input)
+}
 
-    loop {
-        let attr = haystack.next();
-        if let Some(TokenTree::Group(ref group)) = attr {
-            haystack = group.stream().into_iter();
-            continue;
-        }
-        break;
-    }
+derive! {
+    /// `#[derive(Listener)]`
+    ///
+    /// Derives `derive_io::Listener` for the given struct or enum, which accepts connections and wraps each stream in
+    /// the matching variant of a stream enum, along with `std::os::fd::{AsFd, AsRawFd}` and
+    /// `std::os::windows::io::{AsSocket, AsRawSocket}`. With the `axum` feature, also derives
+    /// `axum::serve::Listener`.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[listener(stream = <Enum>::<Variant>)]`: Marks the field as a listener, whose accepted streams are wrapped
+    ///   in `<Enum>::<Variant>`. Every field must use the same enum.
+    /// - `#[listener(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[listener(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[listener(duck)]`: Delegates to inherent `poll_accept`/`local_addr`/`as_fd`/`as_socket` methods on the
+    ///   type (duck typing), converting addresses with `Into<derive_io::SocketAddrAny>`.
+    /// - `#[listener(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    Listener(delegate listener) => derive_io_listener(input)
+        generate::listener("derive_io", input)
+}
 
-    loop {
-        let key = haystack.next();
-        if let Some(TokenTree::Group(ref group)) = key {
-            haystack = group.stream().into_iter();
-            continue;
-        }
-        let Some(key) = key else {
-            break;
-        };
-        if key.to_string() == needle.to_string() {
-            return if_true.stream();
-        }
-    }
+derive! {
+    /// `#[derive(Split)]`
+    ///
+    /// Derives `derive_io::NativeSplit` for the given enum, which splits each variant's stream into read and write
+    /// halves without locking. The halves are wrapped in the generated `<Name>ReadHalf<'_>`, `<Name>WriteHalf<'_>`,
+    /// `<Name>OwnedReadHalf` and `<Name>OwnedWriteHalf` enums, which derive `AsyncRead` or `AsyncWrite`. Each variant
+    /// must have a single field.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[split(generic)]`: Splits a field without a `NativeSplit` impl with `tokio::io::split`, which locks the
+    ///   stream for each call.
+    Split(split) => derive_io_split(input)
+        generate::split("derive_io", input)
+}
 
-    if_false.stream()
+derive! {
+    /// `#[derive(Stream)]`
+    ///
+    /// Derives `futures::Stream` for the given struct. The `Item` type is that of
+    /// the first `#[stream]` field, and every other `#[stream]` field must have
+    /// the same `Item` type.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[stream]`: Marks the field as a stream.
+    /// - `#[stream(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[stream(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[pin]`: Delegates to a structurally pinned field, which need not be `Unpin`. Requires `#[derive(PinProject)]`.
+    Stream(delegate stream) => derive_io_stream(input)
+        generate::derive("derive_io", &generate::STREAM, input)
 }
 
-#[proc_macro]
-pub fn type_has_generic(input: TokenStream) -> TokenStream {
-    let mut iterator = input.into_iter();
+derive! {
+    /// `#[derive(Sink)]`
+    ///
+    /// Derives `futures::Sink<Item>` for the given struct, for every `Item` that
+    /// the `#[sink]` fields accept. The `Error` type is that of the first `#[sink]`
+    /// field, and every other `#[sink]` field must have the same `Error` type.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[sink]`: Marks the field as a sink.
+    /// - `#[sink(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[sink(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[pin]`: Delegates to a structurally pinned field, which need not be `Unpin`. Requires `#[derive(PinProject)]`.
+    Sink(delegate sink) => derive_io_sink(input)
+        generate::derive("derive_io", &generate::SINK, input)
+}
 
-    let type_ = expect_group("type", &mut iterator);
-    let generic = expect_group("generics", &mut iterator);
-    let if_true = expect_group("if_true", &mut iterator);
-    let if_false = expect_group("if_false", &mut iterator);
+derive! {
+    /// `#[derive(HyperRead)]`
+    ///
+    /// Derives `hyper::rt::Read` for the given struct, so that it can be served
+    /// by hyper 1.x without a `TokioIo` wrapper.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[read]`: Marks the field as a read stream.
+    /// - `#[read(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[read(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[read(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[read(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[pin]`: Delegates to a structurally pinned field, which need not be `Unpin`. Requires `#[derive(PinProject)]`.
+    /// - `#[read(map_err = <function>)]`: Maps errors from the field with `<function>(std::io::Error) -> std::io::Error`.
+    /// - `#[io(on_error = <function>)]` (on the type): Calls `<function>(&self, &std::io::Error)` when a method fails.
+    /// - `#[io(trace)]` (on the type): Emits a `tracing` event for each call. Requires the `tracing` feature.
+    /// - `#[io(context)]` (on the type): Wraps errors in a `derive_io::ErrorContext` naming the type, variant and field.
+    /// - `#[io(on_pending = <function>)]` (on the type): Calls `<function>(&self)` when a method returns `Poll::Pending`.
+    ///
+    /// As the `ReadBufCursor` is passed to the field, the bytes read are not passed
+    /// to `inspect` functions or recorded in transcripts, and `#[buffer]` fields are
+    /// not used.
+    HyperRead(delegate read) => derive_io_hyper_read(input)
+        generate::derive("derive_io", &generate::HYPER_READ, input)
+}
 
-    fn recursive_collect_generics(generics: &mut HashSet<String>, type_tokens: TokenStream) {
-        let iterator = type_tokens.into_iter();
-        for token in iterator {
-            if let TokenTree::Ident(ident) = &token {
-                generics.insert(ident.to_string());
-            } else if let TokenTree::Group(group) = token {
-                recursive_collect_generics(generics, group.stream());
-            }
+derive! {
+    /// `#[derive(HyperWrite)]`
+    ///
+    /// Derives `hyper::rt::Write` for the given struct.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[write]`: Marks the field as a write stream.
+    /// - `#[write(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[write(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[write(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[write(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[pin]`: Delegates to a structurally pinned field, which need not be `Unpin`. Requires `#[derive(PinProject)]`.
+    /// - `#[write(inspect = <function>)]`: Calls `<function>(&self, &[u8])` with the bytes accepted by each write.
+    /// - `#[write(map_err = <function>)]`: Maps errors from the field with `<function>(std::io::Error) -> std::io::Error`.
+    /// - `#[io(on_error = <function>)]` (on the type): Calls `<function>(&self, &std::io::Error)` when a method fails.
+    /// - `#[io(trace)]` (on the type): Emits a `tracing` event for each call. Requires the `tracing` feature.
+    /// - `#[io(context)]` (on the type): Wraps errors in a `derive_io::ErrorContext` naming the type, variant and field.
+    /// - `#[io(transcript = <path>)]` (on the type): Records the bytes written to a `derive_io::Transcript` file.
+    /// - `#[io(transcript)]`: Marks the `derive_io::TranscriptStream` field of each case, required by `#[io(transcript = <path>)]`.
+    /// - `#[io(on_pending = <function>)]` (on the type): Calls `<function>(&self)` when a method returns `Poll::Pending`.
+    HyperWrite(delegate write) => derive_io_hyper_write(input)
+        generate::derive("derive_io", &generate::HYPER_WRITE, input)
+}
+
+derive! {
+    /// `#[derive(AsyncReadRent)]`
+    ///
+    /// Derives `monoio::io::AsyncReadRent` for the given struct, forwarding the
+    /// owned buffer to the field and returning it with the result.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[read]`: Marks the field as a read stream.
+    /// - `#[read(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[read(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[read(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[read(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[read(map_err = <function>)]`: Maps errors from the field with `<function>(std::io::Error) -> std::io::Error`.
+    /// - `#[io(on_error = <function>)]` (on the type): Calls `<function>(&self, &std::io::Error)` when a method fails.
+    /// - `#[io(trace)]` (on the type): Emits a `tracing` event for each call. Requires the `tracing` feature.
+    /// - `#[io(context)]` (on the type): Wraps errors in a `derive_io::ErrorContext` naming the type, variant and field.
+    ///
+    /// The bytes read are not passed to `inspect` functions or recorded in
+    /// transcripts.
+    AsyncReadRent(delegate read) => derive_io_async_read_rent(input)
+        generate::derive("derive_io", &generate::ASYNC_READ_RENT, input)
+}
+
+derive! {
+    /// `#[derive(AsyncWriteRent)]`
+    ///
+    /// Derives `monoio::io::AsyncWriteRent` for the given struct, forwarding the
+    /// owned buffer to the field and returning it with the result.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[write]`: Marks the field as a write stream.
+    /// - `#[write(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[write(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[write(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[write(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[write(inspect = <function>)]`: Calls `<function>(&self, &[u8])` with the bytes accepted by each `write`.
+    /// - `#[write(map_err = <function>)]`: Maps errors from the field with `<function>(std::io::Error) -> std::io::Error`.
+    /// - `#[io(on_error = <function>)]` (on the type): Calls `<function>(&self, &std::io::Error)` when a method fails.
+    /// - `#[io(trace)]` (on the type): Emits a `tracing` event for each call. Requires the `tracing` feature.
+    /// - `#[io(context)]` (on the type): Wraps errors in a `derive_io::ErrorContext` naming the type, variant and field.
+    /// - `#[io(transcript = <path>)]` (on the type): Records the bytes written by `write` to a `derive_io::Transcript` file.
+    /// - `#[io(transcript)]`: Marks the `derive_io::TranscriptStream` field of each case, required by `#[io(transcript = <path>)]`.
+    AsyncWriteRent(delegate write) => derive_io_async_write_rent(input)
+        generate::derive("derive_io", &generate::ASYNC_WRITE_RENT, input)
+}
+
+derive! {
+    /// `#[derive(EmbeddedRead)]`
+    ///
+    /// Derives `embedded_io::Read` for the given struct. `embedded_io::ErrorType` must also
+    /// be implemented (ie: with `#[derive(EmbeddedErrorType)]`).
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[read]`: Marks the field as a read stream.
+    /// - `#[read(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[read(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[read(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[read(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[read(map_err = <function>)]`: Maps errors from the field with `<function>`, instead of converting them with `Into`.
+    /// - `#[io(error = <type>)]` (on the type): Converts errors from each field into the `#[derive(EmbeddedErrorType)]`
+    ///   error `<type>` with `Into`.
+    EmbeddedRead(delegate read) => derive_io_embedded_read(input)
+        generate::derive("derive_io", &generate::EMBEDDED_READ, input)
+}
+
+derive! {
+    /// `#[derive(EmbeddedBufRead)]`
+    ///
+    /// Derives `embedded_io::BufRead` for the given struct. `embedded_io::Read` and
+    /// `embedded_io::ErrorType` must also be implemented.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[read]`: Marks the field as a buffered read stream.
+    /// - `#[read(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[read(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[read(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[read(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[read(map_err = <function>)]`: Maps errors from the field with `<function>`, instead of converting them with `Into`.
+    /// - `#[io(error = <type>)]` (on the type): Converts errors from each field into the `#[derive(EmbeddedErrorType)]`
+    ///   error `<type>` with `Into`.
+    EmbeddedBufRead(delegate read) => derive_io_embedded_bufread(input)
+        generate::derive("derive_io", &generate::EMBEDDED_BUF_READ, input)
+}
+
+derive! {
+    /// `#[derive(EmbeddedWrite)]`
+    ///
+    /// Derives `embedded_io::Write` for the given struct. `embedded_io::ErrorType` must also
+    /// be implemented (ie: with `#[derive(EmbeddedErrorType)]`).
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[write]`: Marks the field as a write stream.
+    /// - `#[write(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[write(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[write(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[write(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[write(map_err = <function>)]`: Maps errors from the field with `<function>`, instead of converting them with `Into`.
+    /// - `#[io(error = <type>)]` (on the type): Converts errors from each field into the `#[derive(EmbeddedErrorType)]`
+    ///   error `<type>` with `Into`.
+    EmbeddedWrite(delegate write) => derive_io_embedded_write(input)
+        generate::derive("derive_io", &generate::EMBEDDED_WRITE, input)
+}
+
+derive! {
+    /// `#[derive(EmbeddedSeek)]`
+    ///
+    /// Derives `embedded_io::Seek` for the given struct. `embedded_io::ErrorType` must also
+    /// be implemented (ie: with `#[derive(EmbeddedErrorType)]`).
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[seek]`: Marks the field as a seekable stream.
+    /// - `#[seek(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[seek(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[seek(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[seek(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[seek(map_err = <function>)]`: Maps errors from the field with `<function>`, instead of converting them with `Into`.
+    /// - `#[io(error = <type>)]` (on the type): Converts errors from each field into the `#[derive(EmbeddedErrorType)]`
+    ///   error `<type>` with `Into`.
+    EmbeddedSeek(delegate seek) => derive_io_embedded_seek(input)
+        generate::derive("derive_io", &generate::EMBEDDED_SEEK, input)
+}
+
+derive! {
+    /// `#[derive(EmbeddedAsyncRead)]`
+    ///
+    /// Derives `embedded_io_async::Read` for the given struct. `embedded_io::ErrorType` must also
+    /// be implemented (ie: with `#[derive(EmbeddedErrorType)]`).
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[read]`: Marks the field as a read stream.
+    /// - `#[read(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[read(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[read(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[read(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[read(map_err = <function>)]`: Maps errors from the field with `<function>`, instead of converting them with `Into`.
+    /// - `#[io(error = <type>)]` (on the type): Converts errors from each field into the `#[derive(EmbeddedErrorType)]`
+    ///   error `<type>` with `Into`.
+    EmbeddedAsyncRead(delegate read) => derive_io_embedded_async_read(input)
+        generate::derive("derive_io", &generate::EMBEDDED_ASYNC_READ, input)
+}
+
+derive! {
+    /// `#[derive(EmbeddedAsyncBufRead)]`
+    ///
+    /// Derives `embedded_io_async::BufRead` for the given struct. `embedded_io_async::Read` and
+    /// `embedded_io::ErrorType` must also be implemented.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[read]`: Marks the field as a buffered read stream.
+    /// - `#[read(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[read(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[read(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[read(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[read(map_err = <function>)]`: Maps errors from the field with `<function>`, instead of converting them with `Into`.
+    /// - `#[io(error = <type>)]` (on the type): Converts errors from each field into the `#[derive(EmbeddedErrorType)]`
+    ///   error `<type>` with `Into`.
+    EmbeddedAsyncBufRead(delegate read) => derive_io_embedded_async_bufread(input)
+        generate::derive("derive_io", &generate::EMBEDDED_ASYNC_BUF_READ, input)
+}
+
+derive! {
+    /// `#[derive(EmbeddedAsyncWrite)]`
+    ///
+    /// Derives `embedded_io_async::Write` for the given struct. `embedded_io::ErrorType` must also
+    /// be implemented (ie: with `#[derive(EmbeddedErrorType)]`).
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[write]`: Marks the field as a write stream.
+    /// - `#[write(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[write(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[write(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[write(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[write(map_err = <function>)]`: Maps errors from the field with `<function>`, instead of converting them with `Into`.
+    /// - `#[io(error = <type>)]` (on the type): Converts errors from each field into the `#[derive(EmbeddedErrorType)]`
+    ///   error `<type>` with `Into`.
+    EmbeddedAsyncWrite(delegate write) => derive_io_embedded_async_write(input)
+        generate::derive("derive_io", &generate::EMBEDDED_ASYNC_WRITE, input)
+}
+
+derive! {
+    /// `#[derive(EmbeddedAsyncSeek)]`
+    ///
+    /// Derives `embedded_io_async::Seek` for the given struct. `embedded_io::ErrorType` must also
+    /// be implemented (ie: with `#[derive(EmbeddedErrorType)]`).
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[seek]`: Marks the field as a seekable stream.
+    /// - `#[seek(as_ref)]`: Delegates the field to the inner type using `AsRef`/`AsMut`.
+    /// - `#[seek(deref)]`: Delegates the field to the inner type using `Deref`/`DerefMut`.
+    /// - `#[seek(duck)]`: Delegates to inherent methods of the same name on the type (duck typing).
+    /// - `#[seek(<function>=<override>)]`: Overrides the default `<function>` method with the given override function.
+    /// - `#[seek(map_err = <function>)]`: Maps errors from the field with `<function>`, instead of converting them with `Into`.
+    /// - `#[io(error = <type>)]` (on the type): Converts errors from each field into the `#[derive(EmbeddedErrorType)]`
+    ///   error `<type>` with `Into`.
+    EmbeddedAsyncSeek(delegate seek) => derive_io_embedded_async_seek(input)
+        generate::derive("derive_io", &generate::EMBEDDED_ASYNC_SEEK, input)
+}
+
+derive! {
+    /// `#[derive(EmbeddedErrorType)]`
+    ///
+    /// Derives `embedded_io::ErrorType` for the given struct, with the error type
+    /// of the first `#[read]`, `#[write]` or `#[seek]` field. The other fields must
+    /// have the same error type, unless `#[io(error = <type>)]` is specified.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[io(error = <type>)]` (on the type): Uses `<type>` as the error type. The
+    ///   `embedded-io` derives convert errors from each field into it with `Into`.
+    EmbeddedErrorType(read, write, seek, io) => derive_io_embedded_error_type(input)
+        generate::error_type("derive_io", input)
+}
+
+derive! {
+    /// `#[derive(PinProject)]`
+    ///
+    /// Generates the structural pinning guarantees required to delegate the async
+    /// traits to fields marked `#[pin]` that may not be `Unpin`:
+    ///
+    /// - A conditional `Unpin` impl: the type is `Unpin` only if every `#[pin]`
+    ///   field is `Unpin`. This also prevents a manual `impl Unpin` for the type.
+    /// - A guard that rejects any manual `Drop` impl for the type. If
+    ///   `#[write(flush_on_drop)]` is specified, the generated `Drop` impl flushes
+    ///   the type instead.
+    ///
+    /// Supported attributes:
+    ///
+    /// - `#[pin]`: Marks the field as structurally pinned.
+    /// - `#[pin(PinnedDrop)]` (on the type): Generates a `Drop` impl that calls
+    ///   `derive_io::PinnedDrop::drop` with a pinned `self`.
+    PinProject(pin) => derive_io_pin_project(input)
+        generate::pin_project("derive_io", input)
+}
+
+/// An error reported as a `compile_error!` at the given span.
+struct Error {
+    message: String,
+    span: Span,
+}
+
+impl Error {
+    fn new(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            message: message.into(),
+            span,
         }
     }
 
-    let mut generics = HashSet::new();
-    recursive_collect_generics(&mut generics, generic.stream());
+    fn to_compile_error(&self) -> TokenStream {
+        compile_error(&self.message, self.span)
+    }
+}
+
+/// Generate a `compile_error!` with the given message at the given span.
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let mut output = TokenStream::new();
+    for mut token in "::core::compile_error!".parse::<TokenStream>().unwrap() {
+        token.set_span(span);
+        output.extend([token]);
+    }
+    let mut message = Literal::string(message);
+    message.set_span(span);
+    let mut group = Group::new(
+        Delimiter::Parenthesis,
+        TokenStream::from_iter([TokenTree::Literal(message)]),
+    );
+    group.set_span(span);
+    let mut semi = Punct::new(';', Spacing::Alone);
+    semi.set_span(span);
+    output.extend([TokenTree::Group(group), TokenTree::Punct(semi)]);
+    output
+}
 
-    fn recursive_check_generics(generics: &HashSet<String>, type_tokens: TokenStream) -> bool {
-        let iterator = type_tokens.into_iter();
-        for token in iterator {
-            if let TokenTree::Ident(ident) = &token {
-                if generics.contains(&ident.to_string()) {
-                    return true;
+/// Parses `template` as Rust code, replacing each `#name` with the matching
+/// token stream. The substituted tokens keep their original spans.
+fn template(template: &str, vars: &[(&str, TokenStream)]) -> TokenStream {
+    fn substitute(tokens: TokenStream, vars: &[(&str, TokenStream)]) -> TokenStream {
+        let mut output = TokenStream::new();
+        let mut iterator = tokens.into_iter().peekable();
+        while let Some(token) = iterator.next() {
+            match token {
+                TokenTree::Punct(p) if p.as_char() == '#' => {
+                    let var = match iterator.peek() {
+                        Some(TokenTree::Ident(ident)) => {
+                            let ident = ident.to_string();
+                            vars.iter().find(|(name, _)| *name == ident)
+                        }
+                        _ => None,
+                    };
+                    if let Some((_, value)) = var {
+                        iterator.next();
+                        output.extend(value.clone());
+                    } else {
+                        output.extend([TokenTree::Punct(p)]);
+                    }
                 }
-            } else if let TokenTree::Group(group) = token {
-                if recursive_check_generics(generics, group.stream()) {
-                    return true;
+                TokenTree::Group(group) => {
+                    let mut new = Group::new(group.delimiter(), substitute(group.stream(), vars));
+                    new.set_span(group.span());
+                    output.extend([TokenTree::Group(new)]);
                 }
+                token => output.extend([token]),
             }
         }
-        false
+        output
     }
 
-    if recursive_check_generics(&generics, type_.stream()) {
-        if_true.stream()
-    } else {
-        if_false.stream()
-    }
+    substitute(template.parse().expect("Failed to parse template"), vars)
 }
@@ -0,0 +1,461 @@
+//! A minimal parser for the derive input: just enough of the item structure
+//! (attributes, generics, where clause and fields) to generate the impls.
+
+use proc_macro::*;
+
+use crate::Error;
+
+/// An attribute, ie: `#[name]`, `#[name(args)]` or `#[name = value]`.
+pub(crate) struct Attr {
+    pub name: String,
+    pub args: Option<Group>,
+    pub tokens: TokenStream,
+    pub span: Span,
+}
+
+impl Attr {
+    /// Parses the attribute arguments as a comma-separated list of `key` or
+    /// `key = value` items.
+    pub fn meta(&self) -> Result<Vec<Meta>, Error> {
+        let meta = self.meta_with_unsafe()?;
+        if let Some(span) = meta.iter().find_map(|meta| meta.unsafety) {
+            return Err(Error::new(
+                format!("Invalid #[{}] attribute, unexpected `unsafe`", self.name),
+                span,
+            ));
+        }
+        Ok(meta)
+    }
+
+    /// Parses the attribute arguments like [`Attr::meta`], also accepting
+    /// items wrapped in `unsafe(...)`, ie: `#[descriptor(unsafe(raw))]`.
+    pub fn meta_with_unsafe(&self) -> Result<Vec<Meta>, Error> {
+        let mut meta = vec![];
+        if let Some(args) = &self.args {
+            self.parse_meta(args.stream(), None, &mut meta)?;
+        }
+        Ok(meta)
+    }
+
+    fn parse_meta(
+        &self,
+        stream: TokenStream,
+        unsafety: Option<Span>,
+        meta: &mut Vec<Meta>,
+    ) -> Result<(), Error> {
+        for item in split_top_level(stream, true) {
+            let mut item = item.into_iter();
+            let key = match item.next() {
+                Some(TokenTree::Ident(key)) => key,
+                Some(token) => {
+                    return Err(Error::new(
+                        format!("Invalid #[{}] attribute, expected identifier", self.name),
+                        token.span(),
+                    ))
+                }
+                None => continue,
+            };
+            let value = match item.next() {
+                None => None,
+                Some(TokenTree::Group(group))
+                    if unsafety.is_none()
+                        && key.to_string() == "unsafe"
+                        && group.delimiter() == Delimiter::Parenthesis =>
+                {
+                    if let Some(token) = item.next() {
+                        return Err(Error::new(
+                            format!("Invalid #[{}] attribute, expected `,`", self.name),
+                            token.span(),
+                        ));
+                    }
+                    self.parse_meta(group.stream(), Some(key.span()), meta)?;
+                    continue;
+                }
+                Some(TokenTree::Punct(p)) if p.as_char() == '=' => {
+                    let value = TokenStream::from_iter(item);
+                    if value.is_empty() {
+                        return Err(Error::new(
+                            format!(
+                                "Invalid #[{}] attribute, expected value for `{key}`",
+                                self.name
+                            ),
+                            p.span(),
+                        ));
+                    }
+                    Some(value)
+                }
+                Some(token) => {
+                    return Err(Error::new(
+                        format!("Invalid #[{}] attribute, expected `,` or `=`", self.name),
+                        token.span(),
+                    ))
+                }
+            };
+            meta.push(Meta {
+                key,
+                value,
+                unsafety,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A single `key` or `key = value` item of an attribute.
+pub(crate) struct Meta {
+    pub key: Ident,
+    pub value: Option<TokenStream>,
+    /// The `unsafe` keyword of an item wrapped in `unsafe(...)`.
+    pub unsafety: Option<Span>,
+}
+
+pub(crate) struct Generic {
+    /// The parameter as declared, minus any default.
+    pub decl: TokenStream,
+    /// The parameter as used in a type, ie: `'a`, `T` or `N`.
+    pub name: TokenStream,
+}
+
+pub(crate) enum Member {
+    Named(Ident),
+    Unnamed(usize),
+}
+
+impl Member {
+    pub fn to_tokens(&self) -> TokenStream {
+        match self {
+            Member::Named(ident) => TokenTree::Ident(ident.clone()).into(),
+            Member::Unnamed(index) => TokenTree::Literal(Literal::usize_unsuffixed(*index)).into(),
+        }
+    }
+}
+
+pub(crate) struct Field {
+    pub attrs: Vec<Attr>,
+    pub member: Member,
+    pub ty: TokenStream,
+}
+
+/// The struct itself, or a single enum variant.
+pub(crate) struct Case {
+    pub attrs: Vec<Attr>,
+    /// `Self` or `Self::Variant`
+    pub path: TokenStream,
+    /// The variant name, or `None` for a struct.
+    pub name: Option<String>,
+    pub span: Span,
+    pub fields: Vec<Field>,
+}
+
+pub(crate) struct Input {
+    pub attrs: Vec<Attr>,
+    /// The visibility of the type, ie: `pub(crate)`.
+    pub vis: TokenStream,
+    pub name: Ident,
+    pub generics: Vec<Generic>,
+    pub where_clause: Vec<TokenStream>,
+    pub cases: Vec<Case>,
+}
+
+impl Input {
+    pub fn parse(input: TokenStream) -> Result<Self, Error> {
+        let mut iterator = input.into_iter().peekable();
+        let attrs = parse_attrs(&mut iterator)?;
+
+        let mut vis = TokenStream::new();
+        let is_enum = loop {
+            match iterator.next() {
+                Some(TokenTree::Ident(i)) if i.to_string() == "struct" => break false,
+                Some(TokenTree::Ident(i)) if i.to_string() == "enum" => break true,
+                Some(TokenTree::Ident(i)) if i.to_string() == "union" => {
+                    return Err(Error::new("Unions are not supported", i.span()))
+                }
+                Some(token) => vis.extend([token]),
+                None => return Err(Error::new("Expected struct or enum", Span::call_site())),
+            }
+        };
+
+        let Some(TokenTree::Ident(name)) = iterator.next() else {
+            return Err(Error::new("Expected type name", Span::call_site()));
+        };
+
+        let mut generics = vec![];
+        if matches!(iterator.peek(), Some(TokenTree::Punct(p)) if p.as_char() == '<') {
+            iterator.next();
+            let mut params = TokenStream::new();
+            let mut depth = 0_usize;
+            let mut last_dash = false;
+            for token in iterator.by_ref() {
+                let mut dash = false;
+                if let TokenTree::Punct(p) = &token {
+                    match p.as_char() {
+                        '<' => depth += 1,
+                        // Don't count the `>` in `->`
+                        '>' if !last_dash => {
+                            if depth == 0 {
+                                break;
+                            }
+                            depth -= 1;
+                        }
+                        '-' => dash = p.spacing() == Spacing::Joint,
+                        _ => {}
+                    }
+                }
+                last_dash = dash;
+                params.extend([token]);
+            }
+            for param in split_top_level(params, true) {
+                generics.push(parse_generic(param));
+            }
+        }
+
+        // The fields of a tuple struct come before its where clause
+        let mut body = None;
+        if let Some(TokenTree::Group(group)) = iterator.peek() {
+            if group.delimiter() == Delimiter::Parenthesis {
+                body = Some(group.clone());
+                iterator.next();
+            }
+        }
+
+        let mut where_clause = TokenStream::new();
+        for token in iterator.by_ref() {
+            match token {
+                TokenTree::Ident(i) if i.to_string() == "where" => {}
+                TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => {
+                    body = Some(group);
+                    break;
+                }
+                TokenTree::Punct(p) if p.as_char() == ';' => break,
+                token => where_clause.extend([token]),
+            }
+        }
+        let where_clause = split_top_level(where_clause, true)
+            .into_iter()
+            .map(TokenStream::from_iter)
+            .collect();
+
+        let self_path: TokenStream = "Self".parse().unwrap();
+        let cases = if is_enum {
+            let Some(body) = body else {
+                return Err(Error::new("Expected enum body", name.span()));
+            };
+            let mut cases = vec![];
+            for variant in split_top_level(body.stream(), false) {
+                let mut variant = variant.into_iter().peekable();
+                let attrs = parse_attrs(&mut variant)?;
+                let Some(TokenTree::Ident(ident)) = variant.next() else {
+                    return Err(Error::new("Expected variant name", name.span()));
+                };
+                let mut path = self_path.clone();
+                path.extend([
+                    TokenTree::Punct(Punct::new(':', Spacing::Joint)),
+                    TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+                    TokenTree::Ident(ident.clone()),
+                ]);
+                let fields = match variant.next() {
+                    Some(TokenTree::Group(group)) => parse_fields(group)?,
+                    _ => vec![],
+                };
+                cases.push(Case {
+                    attrs,
+                    path,
+                    name: Some(ident.to_string()),
+                    span: ident.span(),
+                    fields,
+                });
+            }
+            cases
+        } else {
+            let fields = match body {
+                Some(body) => parse_fields(body)?,
+                None => vec![],
+            };
+            vec![Case {
+                attrs: vec![],
+                path: self_path,
+                name: None,
+                span: name.span(),
+                fields,
+            }]
+        };
+
+        Ok(Input {
+            attrs,
+            vis,
+            name,
+            generics,
+            where_clause,
+            cases,
+        })
+    }
+
+    /// The generics for the `impl<...>` of this type.
+    pub fn impl_generics(&self) -> TokenStream {
+        join(self.generics.iter().map(|generic| generic.decl.clone()))
+    }
+
+    /// The generics for the `for Type<...>` of this type.
+    pub fn ty_generics(&self) -> TokenStream {
+        join(self.generics.iter().map(|generic| generic.name.clone()))
+    }
+
+    /// Returns true if the given type refers to any of the type's generics.
+    pub fn has_generic(&self, ty: &TokenStream) -> bool {
+        fn collect(names: &mut Vec<String>, tokens: TokenStream) {
+            for token in tokens {
+                match token {
+                    TokenTree::Ident(ident) => names.push(ident.to_string()),
+                    TokenTree::Group(group) => collect(names, group.stream()),
+                    _ => {}
+                }
+            }
+        }
+
+        let mut generics = vec![];
+        for generic in &self.generics {
+            collect(&mut generics, generic.name.clone());
+        }
+        let mut idents = vec![];
+        collect(&mut idents, ty.clone());
+        idents.iter().any(|ident| generics.contains(ident))
+    }
+}
+
+/// Parses a generic parameter, stripping any default.
+fn parse_generic(param: Vec<TokenTree>) -> Generic {
+    let name = match &param[..] {
+        [TokenTree::Punct(p), lifetime, ..] if p.as_char() == '\'' => {
+            TokenStream::from_iter([param[0].clone(), lifetime.clone()])
+        }
+        [TokenTree::Ident(i), name, ..] if i.to_string() == "const" => name.clone().into(),
+        [name, ..] => name.clone().into(),
+        [] => TokenStream::new(),
+    };
+    let mut decl = TokenStream::new();
+    let mut depth = 0_usize;
+    for token in param {
+        if let TokenTree::Punct(p) = &token {
+            match p.as_char() {
+                '<' => depth += 1,
+                '>' => depth = depth.saturating_sub(1),
+                '=' if depth == 0 => break,
+                _ => {}
+            }
+        }
+        decl.extend([token]);
+    }
+    Generic { decl, name }
+}
+
+/// Parses the fields of a braced or parenthesized field list.
+fn parse_fields(group: Group) -> Result<Vec<Field>, Error> {
+    let named = group.delimiter() == Delimiter::Brace;
+    let mut fields = vec![];
+    for (index, field) in split_top_level(group.stream(), true)
+        .into_iter()
+        .enumerate()
+    {
+        let mut field = field.into_iter().peekable();
+        let attrs = parse_attrs(&mut field)?;
+        if matches!(field.peek(), Some(TokenTree::Ident(i)) if i.to_string() == "pub") {
+            field.next();
+            if matches!(field.peek(), Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Parenthesis)
+            {
+                field.next();
+            }
+        }
+        let member = if named {
+            let Some(TokenTree::Ident(name)) = field.next() else {
+                return Err(Error::new("Expected field name", group.span()));
+            };
+            field.next();
+            Member::Named(name)
+        } else {
+            Member::Unnamed(index)
+        };
+        fields.push(Field {
+            attrs,
+            member,
+            ty: field.collect(),
+        });
+    }
+    Ok(fields)
+}
+
+/// Parses any leading outer attributes.
+fn parse_attrs(
+    iterator: &mut std::iter::Peekable<impl Iterator<Item = TokenTree>>,
+) -> Result<Vec<Attr>, Error> {
+    let mut attrs = vec![];
+    while let Some(TokenTree::Punct(p)) = iterator.peek() {
+        if p.as_char() != '#' {
+            break;
+        }
+        let hash = iterator.next().unwrap();
+        let Some(TokenTree::Group(group)) = iterator.next() else {
+            return Err(Error::new("Expected attribute", hash.span()));
+        };
+
+        let mut name = String::new();
+        let mut args = None;
+        for token in group.stream() {
+            match token {
+                TokenTree::Group(group) if args.is_none() => args = Some(group),
+                TokenTree::Ident(ident) if args.is_none() => name.push_str(&ident.to_string()),
+                TokenTree::Punct(p) if args.is_none() && p.as_char() == ':' => name.push(':'),
+                _ => break,
+            }
+        }
+        attrs.push(Attr {
+            name,
+            args,
+            tokens: TokenStream::from_iter([hash, TokenTree::Group(group.clone())]),
+            span: group.span(),
+        });
+    }
+    Ok(attrs)
+}
+
+/// Joins token streams with commas.
+pub(crate) fn join(items: impl IntoIterator<Item = TokenStream>) -> TokenStream {
+    let mut output = TokenStream::new();
+    for item in items {
+        if !output.is_empty() {
+            output.extend([TokenTree::Punct(Punct::new(',', Spacing::Alone))]);
+        }
+        output.extend(item);
+    }
+    output
+}
+
+/// Split a token stream at top-level commas, optionally treating `<...>` as
+/// nested (for type positions, where angle brackets are not token groups).
+pub(crate) fn split_top_level(stream: TokenStream, angle_brackets: bool) -> Vec<Vec<TokenTree>> {
+    let mut output = vec![];
+    let mut current = vec![];
+    let mut depth = 0_usize;
+    let mut last_dash = false;
+    for token in stream {
+        let mut dash = false;
+        if let TokenTree::Punct(p) = &token {
+            match p.as_char() {
+                ',' if depth == 0 => {
+                    output.push(std::mem::take(&mut current));
+                    continue;
+                }
+                '<' if angle_brackets => depth += 1,
+                // Don't count the `>` in `->`
+                '>' if angle_brackets && !last_dash => depth = depth.saturating_sub(1),
+                '-' => dash = p.spacing() == Spacing::Joint,
+                _ => {}
+            }
+        }
+        last_dash = dash;
+        current.push(token);
+    }
+    if !current.is_empty() {
+        output.push(current);
+    }
+    output
+}
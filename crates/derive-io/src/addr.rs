@@ -0,0 +1,208 @@
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The address of a socket of any transport: an IPv4 or IPv6 address, or a
+/// Unix domain socket address.
+///
+/// Unix addresses are stored by value, so this type is available on every
+/// platform.
+///
+/// Addresses are displayed (and parsed) as inet addresses (ie:
+/// `127.0.0.1:80` or `[::1]:80`), or with a `unix:` prefix: `unix:/path` for
+/// a path, `unix:@name` for an abstract name (with non-printable bytes
+/// escaped as in [`u8::escape_ascii`]), and `unix:` for an unnamed socket.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SocketAddrAny {
+    /// An IPv4 or IPv6 address.
+    Inet(SocketAddr),
+    /// A Unix domain socket bound to a path.
+    Path(PathBuf),
+    /// A Unix domain socket bound to a name in the abstract namespace (Linux
+    /// and Android only).
+    Abstract(Vec<u8>),
+    /// An unbound Unix domain socket.
+    Unnamed,
+}
+
+impl fmt::Display for SocketAddrAny {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Inet(addr) => addr.fmt(f),
+            // A relative path starting with `@` would parse as an abstract name
+            Self::Path(path) if path.as_os_str().as_encoded_bytes().starts_with(b"@") => {
+                write!(f, "unix:{}", Path::new(".").join(path).display())
+            }
+            Self::Path(path) => write!(f, "unix:{}", path.display()),
+            Self::Abstract(name) => write!(f, "unix:@{}", name.escape_ascii()),
+            Self::Unnamed => f.write_str("unix:"),
+        }
+    }
+}
+
+/// The error returned when parsing a [`SocketAddrAny`] fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SocketAddrParseError(());
+
+impl fmt::Display for SocketAddrParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid socket address syntax")
+    }
+}
+
+impl std::error::Error for SocketAddrParseError {}
+
+impl FromStr for SocketAddrAny {
+    type Err = SocketAddrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(unix) = s.strip_prefix("unix:") else {
+            return s
+                .parse()
+                .map(Self::Inet)
+                .map_err(|_| SocketAddrParseError(()));
+        };
+        if unix.is_empty() {
+            Ok(Self::Unnamed)
+        } else if let Some(name) = unix.strip_prefix('@') {
+            unescape_ascii(name).map(Self::Abstract)
+        } else {
+            Ok(Self::Path(PathBuf::from(unix)))
+        }
+    }
+}
+
+/// Reverses [`u8::escape_ascii`].
+fn unescape_ascii(s: &str) -> Result<Vec<u8>, SocketAddrParseError> {
+    let mut bytes = s.bytes();
+    let mut output = Vec::with_capacity(s.len());
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            output.push(byte);
+            continue;
+        }
+        let byte = match bytes.next() {
+            Some(b't') => b'\t',
+            Some(b'r') => b'\r',
+            Some(b'n') => b'\n',
+            Some(byte @ (b'\\' | b'\'' | b'"')) => byte,
+            Some(b'x') => {
+                let hex = [bytes.next(), bytes.next()];
+                let hex = hex.map(|digit| digit.and_then(|digit| (digit as char).to_digit(16)));
+                match hex {
+                    [Some(high), Some(low)] => (high * 16 + low) as u8,
+                    _ => return Err(SocketAddrParseError(())),
+                }
+            }
+            _ => return Err(SocketAddrParseError(())),
+        };
+        output.push(byte);
+    }
+    Ok(output)
+}
+
+impl From<SocketAddr> for SocketAddrAny {
+    fn from(addr: SocketAddr) -> Self {
+        Self::Inet(addr)
+    }
+}
+
+#[cfg(unix)]
+impl From<std::os::unix::net::SocketAddr> for SocketAddrAny {
+    fn from(addr: std::os::unix::net::SocketAddr) -> Self {
+        if let Some(path) = addr.as_pathname() {
+            return Self::Path(path.to_path_buf());
+        }
+        #[cfg(target_os = "android")]
+        use std::os::android::net::SocketAddrExt;
+        #[cfg(target_os = "linux")]
+        use std::os::linux::net::SocketAddrExt;
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if let Some(name) = addr.as_abstract_name() {
+            return Self::Abstract(name.to_vec());
+        }
+        Self::Unnamed
+    }
+}
+
+#[cfg(all(unix, feature = "tokio"))]
+impl From<tokio::net::unix::SocketAddr> for SocketAddrAny {
+    fn from(addr: tokio::net::unix::SocketAddr) -> Self {
+        std::os::unix::net::SocketAddr::from(addr).into()
+    }
+}
+
+/// The local and peer addresses of a socket, as a [`SocketAddrAny`].
+///
+/// This is implemented for the connected sockets of std and tokio, and can be
+/// derived for enums of them with `#[derive(SocketAddrs)]`.
+pub trait SocketAddrs {
+    /// Returns the address that this socket is bound to.
+    fn local_addr(&self) -> io::Result<SocketAddrAny>;
+
+    /// Returns the address of the peer that this socket is connected to.
+    fn peer_addr(&self) -> io::Result<SocketAddrAny>;
+}
+
+macro_rules! socket_addrs {
+    ($($(#[$cfg:meta])* $ty:ty),* $(,)?) => {$(
+        $(#[$cfg])*
+        impl SocketAddrs for $ty {
+            fn local_addr(&self) -> io::Result<SocketAddrAny> {
+                <$ty>::local_addr(self).map(Into::into)
+            }
+
+            fn peer_addr(&self) -> io::Result<SocketAddrAny> {
+                <$ty>::peer_addr(self).map(Into::into)
+            }
+        }
+    )*};
+}
+
+socket_addrs!(
+    std::net::TcpStream,
+    std::net::UdpSocket,
+    #[cfg(unix)]
+    std::os::unix::net::UnixStream,
+    #[cfg(unix)]
+    std::os::unix::net::UnixDatagram,
+    #[cfg(feature = "tokio")]
+    tokio::net::TcpStream,
+    #[cfg(feature = "tokio")]
+    tokio::net::UdpSocket,
+    #[cfg(all(unix, feature = "tokio"))]
+    tokio::net::UnixStream,
+    #[cfg(all(unix, feature = "tokio"))]
+    tokio::net::UnixDatagram,
+    #[cfg(all(unix, feature = "tokio"))]
+    tokio::net::unix::OwnedReadHalf,
+    #[cfg(all(unix, feature = "tokio"))]
+    tokio::net::unix::OwnedWriteHalf,
+    #[cfg(feature = "tokio")]
+    tokio::net::tcp::OwnedReadHalf,
+    #[cfg(feature = "tokio")]
+    tokio::net::tcp::OwnedWriteHalf,
+);
+
+impl<T: SocketAddrs + ?Sized> SocketAddrs for &T {
+    fn local_addr(&self) -> io::Result<SocketAddrAny> {
+        (**self).local_addr()
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddrAny> {
+        (**self).peer_addr()
+    }
+}
+
+impl<T: SocketAddrs + ?Sized> SocketAddrs for Box<T> {
+    fn local_addr(&self) -> io::Result<SocketAddrAny> {
+        (**self).local_addr()
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddrAny> {
+        (**self).peer_addr()
+    }
+}
@@ -0,0 +1,254 @@
+use std::io;
+#[cfg(feature = "tokio")]
+use std::task::{ready, Poll};
+
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/// A read buffer for a `#[buffer]` field, which provides `BufRead` and
+/// `AsyncBufRead` on top of a raw `#[read]` stream.
+///
+/// Reads are served from the buffer first. Reads larger than the buffer's
+/// capacity bypass the buffer once it has been drained.
+pub struct ReadBuffer {
+    buf: Box<[u8]>,
+    pos: usize,
+    filled: usize,
+}
+
+impl ReadBuffer {
+    /// Creates a new read buffer with the default capacity (8 KiB).
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Creates a new read buffer with the given capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buf: vec![0; capacity].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+        }
+    }
+
+    /// The capacity of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// The buffered data that has not yet been read.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..self.filled]
+    }
+
+    /// Discards any buffered data.
+    pub fn discard(&mut self) {
+        self.pos = 0;
+        self.filled = 0;
+    }
+}
+
+impl Default for ReadBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for ReadBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReadBuffer")
+            .field("buffered", &(self.filled - self.pos))
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+/// A write buffer for a `#[buffer(write)]` field, which coalesces small writes
+/// to a `#[write(buffered)]` stream in the same way as `BufWriter`.
+///
+/// Buffered data is written on flush (or shutdown), or when the buffer is
+/// full. Data still buffered when the type is dropped is lost.
+pub struct WriteBuffer {
+    buf: Vec<u8>,
+    capacity: usize,
+}
+
+impl WriteBuffer {
+    /// Creates a new write buffer with the default capacity (8 KiB).
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Creates a new write buffer with the given capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buf: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// The capacity of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The buffered data that has not yet been written.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl Default for WriteBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for WriteBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WriteBuffer")
+            .field("buffered", &self.buf.len())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+pub fn buffered_fill_buf(
+    buffer: &mut ReadBuffer,
+    read: impl FnOnce(&mut [u8]) -> io::Result<usize>,
+) -> io::Result<&[u8]> {
+    if buffer.pos >= buffer.filled {
+        let n = read(&mut buffer.buf)?;
+        buffer.pos = 0;
+        buffer.filled = n;
+    }
+    Ok(buffer.buffer())
+}
+
+pub fn buffered_consume(buffer: &mut ReadBuffer, amt: usize) {
+    buffer.pos = (buffer.pos + amt).min(buffer.filled);
+}
+
+pub fn buffered_read(
+    buffer: &mut ReadBuffer,
+    buf: &mut [u8],
+    read: impl FnOnce(&mut [u8]) -> io::Result<usize>,
+) -> io::Result<usize> {
+    if buffer.pos == buffer.filled && buf.len() >= buffer.capacity() {
+        buffer.discard();
+        return read(buf);
+    }
+    let available = buffered_fill_buf(buffer, read)?;
+    let n = available.len().min(buf.len());
+    buf[..n].copy_from_slice(&available[..n]);
+    buffered_consume(buffer, n);
+    Ok(n)
+}
+
+#[cfg(feature = "tokio")]
+pub fn buffered_poll_fill_buf(
+    buffer: &mut ReadBuffer,
+    poll_read: impl FnOnce(&mut tokio::io::ReadBuf<'_>) -> Poll<io::Result<()>>,
+) -> Poll<io::Result<&[u8]>> {
+    if buffer.pos >= buffer.filled {
+        let mut buf = tokio::io::ReadBuf::new(&mut buffer.buf);
+        ready!(poll_read(&mut buf))?;
+        buffer.filled = buf.filled().len();
+        buffer.pos = 0;
+    }
+    Poll::Ready(Ok(buffer.buffer()))
+}
+
+#[cfg(feature = "tokio")]
+pub fn buffered_poll_read(
+    buffer: &mut ReadBuffer,
+    buf: &mut tokio::io::ReadBuf<'_>,
+    poll_read: impl FnOnce(&mut tokio::io::ReadBuf<'_>) -> Poll<io::Result<()>>,
+) -> Poll<io::Result<()>> {
+    if buffer.pos == buffer.filled && buf.remaining() >= buffer.capacity() {
+        buffer.discard();
+        return poll_read(buf);
+    }
+    let available = ready!(buffered_poll_fill_buf(buffer, poll_read))?;
+    let n = available.len().min(buf.remaining());
+    buf.put_slice(&available[..n]);
+    buffered_consume(buffer, n);
+    Poll::Ready(Ok(()))
+}
+
+pub fn buffered_flush_buf(
+    buffer: &mut WriteBuffer,
+    mut write: impl FnMut(&[u8]) -> io::Result<usize>,
+) -> io::Result<()> {
+    while !buffer.buf.is_empty() {
+        match write(&buffer.buf) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write the buffered data",
+                ))
+            }
+            Ok(n) => {
+                buffer.buf.drain(..n);
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+pub fn buffered_write(
+    buffer: &mut WriteBuffer,
+    buf: &[u8],
+    mut write: impl FnMut(&[u8]) -> io::Result<usize>,
+) -> io::Result<usize> {
+    if buffer.buf.len() + buf.len() > buffer.capacity {
+        buffered_flush_buf(buffer, &mut write)?;
+    }
+    if buf.len() >= buffer.capacity {
+        write(buf)
+    } else {
+        buffer.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub fn buffered_poll_flush_buf(
+    buffer: &mut WriteBuffer,
+    mut poll_write: impl FnMut(&[u8]) -> Poll<io::Result<usize>>,
+) -> Poll<io::Result<()>> {
+    while !buffer.buf.is_empty() {
+        match ready!(poll_write(&buffer.buf)) {
+            Ok(0) => {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to write the buffered data",
+                )))
+            }
+            Ok(n) => {
+                buffer.buf.drain(..n);
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Poll::Ready(Err(e)),
+        }
+    }
+    Poll::Ready(Ok(()))
+}
+
+#[cfg(feature = "tokio")]
+pub fn buffered_poll_write(
+    buffer: &mut WriteBuffer,
+    buf: &[u8],
+    mut poll_write: impl FnMut(&[u8]) -> Poll<io::Result<usize>>,
+) -> Poll<io::Result<usize>> {
+    if buffer.buf.len() + buf.len() > buffer.capacity {
+        ready!(buffered_poll_flush_buf(buffer, &mut poll_write))?;
+    }
+    if buf.len() >= buffer.capacity {
+        poll_write(buf)
+    } else {
+        buffer.buf.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+}
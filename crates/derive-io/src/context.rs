@@ -0,0 +1,124 @@
+use std::fmt;
+use std::io;
+use std::task::Poll;
+
+/// The context attached by `#[io(context)]` to an error from a derived
+/// method: the type, variant and field that the error came from.
+///
+/// The wrapping `io::Error` keeps the original `ErrorKind`, and the context is
+/// available with `io::Error::get_ref` and `downcast_ref::<ErrorContext>()`.
+#[derive(Debug)]
+pub struct ErrorContext {
+    ty: &'static str,
+    variant: Option<&'static str>,
+    field: Option<&'static str>,
+    error: io::Error,
+}
+
+impl ErrorContext {
+    /// The name of the type that the error came from.
+    pub fn ty(&self) -> &'static str {
+        self.ty
+    }
+
+    /// The name of the enum variant that the error came from, if any.
+    pub fn variant(&self) -> Option<&'static str> {
+        self.variant
+    }
+
+    /// The name (or index) of the field that the error came from, or `None`
+    /// if the type delegates to itself.
+    pub fn field(&self) -> Option<&'static str> {
+        self.field
+    }
+
+    /// The original error.
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+
+    /// Consumes the context, returning the original error.
+    pub fn into_error(self) -> io::Error {
+        self.error
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.ty)?;
+        if let Some(variant) = self.variant {
+            write!(f, "::{variant}")?;
+        }
+        if let Some(field) = self.field {
+            write!(f, ".{field}")?;
+        }
+        write!(f, ": {}", self.error)
+    }
+}
+
+impl std::error::Error for ErrorContext {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Attaches an `ErrorContext` to the error of a result.
+pub trait AddContext {
+    fn add_context(
+        self,
+        ty: &'static str,
+        variant: Option<&'static str>,
+        field: Option<&'static str>,
+    ) -> Self;
+}
+
+fn add_context(
+    error: io::Error,
+    ty: &'static str,
+    variant: Option<&'static str>,
+    field: Option<&'static str>,
+) -> io::Error {
+    io::Error::new(
+        error.kind(),
+        ErrorContext {
+            ty,
+            variant,
+            field,
+            error,
+        },
+    )
+}
+
+impl<T> AddContext for io::Result<T> {
+    fn add_context(
+        self,
+        ty: &'static str,
+        variant: Option<&'static str>,
+        field: Option<&'static str>,
+    ) -> Self {
+        self.map_err(|error| add_context(error, ty, variant, field))
+    }
+}
+
+impl<T> AddContext for Poll<io::Result<T>> {
+    fn add_context(
+        self,
+        ty: &'static str,
+        variant: Option<&'static str>,
+        field: Option<&'static str>,
+    ) -> Self {
+        self.map_err(|error| add_context(error, ty, variant, field))
+    }
+}
+
+/// The result of a completion-based method, with its owned buffer.
+impl<T, B> AddContext for (io::Result<T>, B) {
+    fn add_context(
+        self,
+        ty: &'static str,
+        variant: Option<&'static str>,
+        field: Option<&'static str>,
+    ) -> Self {
+        (self.0.add_context(ty, variant, field), self.1)
+    }
+}
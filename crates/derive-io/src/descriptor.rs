@@ -0,0 +1,224 @@
+use std::fmt;
+use std::io;
+use std::mem::{self, MaybeUninit};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+
+/// A type that may have a file descriptor, such as an enum over sockets and
+/// in-memory streams.
+///
+/// Every `AsFd` type implements this trait, and always returns `Some`.
+pub trait MaybeAsFd {
+    /// Borrows the file descriptor, if any.
+    fn try_as_fd(&self) -> Option<BorrowedFd<'_>>;
+}
+
+/// A type that may have a raw file descriptor.
+///
+/// Every `AsRawFd` type implements this trait, and always returns `Some`.
+pub trait MaybeAsRawFd {
+    /// Extracts the raw file descriptor, if any.
+    fn try_as_raw_fd(&self) -> Option<RawFd>;
+}
+
+/// A type with separate descriptors for reading and writing, such as a pair
+/// of pipes, which an event loop can poll for each direction.
+pub trait AsReadWriteFds {
+    /// Borrows the descriptor that is read from.
+    fn read_fd(&self) -> BorrowedFd<'_>;
+
+    /// Borrows the descriptor that is written to.
+    fn write_fd(&self) -> BorrowedFd<'_>;
+}
+
+impl<T: AsFd + ?Sized> MaybeAsFd for T {
+    fn try_as_fd(&self) -> Option<BorrowedFd<'_>> {
+        Some(self.as_fd())
+    }
+}
+
+impl<T: AsRawFd + ?Sized> MaybeAsRawFd for T {
+    fn try_as_raw_fd(&self) -> Option<RawFd> {
+        Some(self.as_raw_fd())
+    }
+}
+
+/// The kind of a file descriptor, as found by `DescriptorKind::probe`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DescriptorKind {
+    /// A TCP socket (`AF_INET` or `AF_INET6`, `SOCK_STREAM`).
+    Tcp,
+    /// A listening TCP socket, ie: from systemd socket activation.
+    TcpListener,
+    /// A UDP socket (`AF_INET` or `AF_INET6`, `SOCK_DGRAM`).
+    Udp,
+    /// A Unix domain stream socket (`AF_UNIX`, `SOCK_STREAM`).
+    UnixStream,
+    /// A listening Unix domain stream socket.
+    UnixListener,
+    /// A pipe or FIFO.
+    Pipe,
+    /// A regular file.
+    File,
+    /// A terminal.
+    Tty,
+    /// Any other descriptor, ie: a directory or another kind of socket.
+    Other,
+}
+
+impl DescriptorKind {
+    /// Finds the kind of a descriptor with `fstat`, and with `getsockopt` and
+    /// `getsockname` for sockets. Stream sockets that are listening
+    /// (`SO_ACCEPTCONN`) are listeners.
+    pub fn probe(fd: BorrowedFd<'_>) -> io::Result<Self> {
+        let fd = fd.as_raw_fd();
+        let mut stat = MaybeUninit::<libc::stat>::uninit();
+        // SAFETY: `fd` is borrowed, and `stat` is initialized on success
+        if unsafe { libc::fstat(fd, stat.as_mut_ptr()) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let stat = unsafe { stat.assume_init() };
+        Ok(match stat.st_mode & libc::S_IFMT {
+            libc::S_IFREG => Self::File,
+            libc::S_IFIFO => Self::Pipe,
+            // SAFETY: `fd` is borrowed
+            libc::S_IFCHR if unsafe { libc::isatty(fd) } == 1 => Self::Tty,
+            libc::S_IFSOCK => Self::probe_socket(fd)?,
+            _ => Self::Other,
+        })
+    }
+
+    fn probe_socket(fd: RawFd) -> io::Result<Self> {
+        let ty = Self::socket_option(fd, libc::SO_TYPE)?;
+        // SAFETY: an all-zero `sockaddr_storage` is valid
+        let mut addr: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let mut len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        // SAFETY: `addr` is a writable `sockaddr_storage` of length `len`
+        let result = unsafe {
+            libc::getsockname(
+                fd,
+                (&mut addr as *mut libc::sockaddr_storage).cast(),
+                &mut len,
+            )
+        };
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let listening = || Ok::<_, io::Error>(Self::socket_option(fd, libc::SO_ACCEPTCONN)? != 0);
+        Ok(match (libc::c_int::from(addr.ss_family), ty) {
+            (libc::AF_INET | libc::AF_INET6, libc::SOCK_STREAM) if listening()? => {
+                Self::TcpListener
+            }
+            (libc::AF_INET | libc::AF_INET6, libc::SOCK_STREAM) => Self::Tcp,
+            (libc::AF_INET | libc::AF_INET6, libc::SOCK_DGRAM) => Self::Udp,
+            (libc::AF_UNIX, libc::SOCK_STREAM) if listening()? => Self::UnixListener,
+            (libc::AF_UNIX, libc::SOCK_STREAM) => Self::UnixStream,
+            _ => Self::Other,
+        })
+    }
+
+    /// Reads an integer `SOL_SOCKET` option with `getsockopt`.
+    fn socket_option(fd: RawFd, option: libc::c_int) -> io::Result<libc::c_int> {
+        let mut value: libc::c_int = 0;
+        let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+        // SAFETY: `value` is a writable `c_int` of length `len`
+        let result = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                option,
+                (&mut value as *mut libc::c_int).cast(),
+                &mut len,
+            )
+        };
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(value)
+    }
+}
+
+impl fmt::Display for DescriptorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Tcp => "TCP socket",
+            Self::TcpListener => "TCP listener",
+            Self::Udp => "UDP socket",
+            Self::UnixStream => "Unix stream socket",
+            Self::UnixListener => "Unix listener",
+            Self::Pipe => "pipe",
+            Self::File => "regular file",
+            Self::Tty => "terminal",
+            Self::Other => "other descriptor",
+        })
+    }
+}
+
+/// The error of a `TryFrom<OwnedFd>` impl derived with `FromDescriptor`: the
+/// descriptor could not be probed, or no variant accepts its kind.
+///
+/// The descriptor is returned with the error, and is closed when the error is
+/// dropped.
+#[derive(Debug)]
+pub struct FromDescriptorError {
+    fd: OwnedFd,
+    kind: io::Result<DescriptorKind>,
+}
+
+impl FromDescriptorError {
+    /// Creates an error for a descriptor of a kind that is not supported.
+    pub fn unsupported(fd: OwnedFd, kind: DescriptorKind) -> Self {
+        Self { fd, kind: Ok(kind) }
+    }
+
+    /// The kind of the descriptor, or `None` if it could not be probed.
+    pub fn kind(&self) -> Option<DescriptorKind> {
+        self.kind.as_ref().ok().copied()
+    }
+
+    /// Borrows the descriptor.
+    pub fn fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+
+    /// Consumes the error, returning the descriptor.
+    pub fn into_fd(self) -> OwnedFd {
+        self.fd
+    }
+}
+
+impl fmt::Display for FromDescriptorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            Ok(kind) => write!(f, "unsupported descriptor: {kind}"),
+            Err(error) => write!(f, "failed to probe descriptor: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for FromDescriptorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.kind.as_ref().err().map(|error| error as _)
+    }
+}
+
+impl From<FromDescriptorError> for io::Error {
+    fn from(error: FromDescriptorError) -> Self {
+        let kind = match &error.kind {
+            Ok(_) => io::ErrorKind::Unsupported,
+            Err(error) => error.kind(),
+        };
+        io::Error::new(kind, error)
+    }
+}
+
+/// Probes the kind of a descriptor for a derived `TryFrom<OwnedFd>` impl.
+pub fn probe_descriptor(fd: OwnedFd) -> Result<(OwnedFd, DescriptorKind), FromDescriptorError> {
+    match DescriptorKind::probe(fd.as_fd()) {
+        Ok(kind) => Ok((fd, kind)),
+        Err(error) => Err(FromDescriptorError {
+            fd,
+            kind: Err(error),
+        }),
+    }
+}
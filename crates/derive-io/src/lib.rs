@@ -1,699 +1,384 @@
 #![doc = include_str!("../README.md")]
+#![cfg_attr(not(feature = "std"), no_std)]
 
 #[cfg(feature = "std")]
-pub use derive_io_macros::{AsFileDescriptor, AsSocketDescriptor, BufRead, Read, Write};
+pub use derive_io_macros::{
+    AsFileDescriptor, AsReadWriteFds, AsSocketDescriptor, BufRead, FileExt, FromDescriptor,
+    IntoFileDescriptor, MaybeAsFd, PositionalIo, Read, SocketAddrs, SocketControl, Write,
+};
 
 #[cfg(feature = "tokio")]
-pub use derive_io_macros::{AsyncRead, AsyncWrite};
+pub use derive_io_macros::{AsyncBufRead, AsyncRead, AsyncWrite, Listener, Split};
 
-#[doc(hidden)]
-pub mod __support {
-    pub use crate::__derive_io_as_file_descriptor_parse as derive_io_as_file_descriptor_parse;
-    pub use crate::__derive_io_as_socket_descriptor_parse as derive_io_as_socket_descriptor_parse;
-    pub use crate::__derive_io_async_read_parse as derive_io_async_read_parse;
-    pub use crate::__derive_io_async_write_parse as derive_io_async_write_parse;
-    pub use crate::__derive_io_bufread_parse as derive_io_bufread_parse;
-    pub use crate::__derive_io_read_parse as derive_io_read_parse;
-    pub use crate::__derive_io_write_parse as derive_io_write_parse;
-    pub use derive_io_macros::{
-        extract_meta, find_annotated, find_annotated_multi, if_meta, repeat_in_parenthesis,
-        type_has_generic,
-    };
+#[cfg(feature = "futures")]
+pub use derive_io_macros::{Sink, Stream};
 
-    // We need a guaranteed valid implementation of this trait for each trait we support.
-    #[doc(hidden)]
-    pub trait IsSupported<T> {
-        type Type;
-    }
-
-    impl IsSupported<&'static dyn std::io::Read> for () {
-        type Type = Box<dyn std::io::Read + Unpin>;
-    }
+#[cfg(feature = "hyper")]
+pub use derive_io_macros::{HyperRead, HyperWrite};
 
-    impl IsSupported<&'static dyn std::io::BufRead> for () {
-        type Type = Box<dyn std::io::BufRead + Unpin>;
-    }
+#[cfg(feature = "monoio")]
+pub use derive_io_macros::{AsyncReadRent, AsyncWriteRent};
 
-    impl IsSupported<&'static dyn std::io::Write> for () {
-        type Type = Box<dyn std::io::Write + Unpin>;
-    }
+#[cfg(feature = "mio")]
+pub use derive_io_macros::MioSource;
 
-    #[cfg(feature = "tokio")]
-    impl IsSupported<&'static dyn tokio::io::AsyncRead> for () {
-        type Type = Box<dyn tokio::io::AsyncRead + Unpin>;
-    }
+#[cfg(feature = "embedded-io")]
+pub use derive_io_macros::{
+    EmbeddedBufRead, EmbeddedErrorType, EmbeddedRead, EmbeddedSeek, EmbeddedWrite,
+};
 
-    #[cfg(feature = "tokio")]
-    impl IsSupported<&'static dyn tokio::io::AsyncWrite> for () {
-        type Type = Box<dyn tokio::io::AsyncWrite + Unpin>;
-    }
+#[cfg(feature = "embedded-io-async")]
+pub use derive_io_macros::{
+    EmbeddedAsyncBufRead, EmbeddedAsyncRead, EmbeddedAsyncSeek, EmbeddedAsyncWrite,
+};
 
-    #[cfg(unix)]
-    impl IsSupported<&'static dyn std::os::fd::AsFd> for () {
-        type Type = Box<dyn std::os::fd::AsFd + Unpin>;
-    }
-
-    #[cfg(unix)]
-    // This one has buggy bounds in the rust stdlib
-    impl IsSupported<&'static dyn std::os::fd::AsRawFd> for () {
-        type Type = std::os::fd::RawFd;
-    }
-
-    #[cfg(windows)]
-    impl IsSupported<&'static dyn std::os::windows::io::AsHandle> for () {
-        type Type = std::os::windows::io::OwnedHandle;
-    }
-
-    #[cfg(windows)]
-    impl IsSupported<&'static dyn std::os::windows::io::AsRawHandle> for () {
-        type Type = std::os::windows::io::OwnedHandle;
-    }
-
-    #[cfg(windows)]
-    impl IsSupported<&'static dyn std::os::windows::io::AsSocket> for () {
-        type Type = std::os::windows::io::OwnedSocket;
-    }
+#[cfg(feature = "std")]
+mod addr;
+#[cfg(feature = "std")]
+mod buffer;
+#[cfg(feature = "std")]
+mod context;
+#[cfg(all(feature = "std", unix))]
+mod descriptor;
+#[cfg(feature = "tokio")]
+mod listener;
+#[cfg(feature = "std")]
+mod positional;
+#[cfg(feature = "std")]
+mod socket;
+#[cfg(feature = "tokio")]
+mod split;
+#[cfg(feature = "std")]
+mod transcript;
+#[cfg(feature = "std")]
+pub use addr::{SocketAddrAny, SocketAddrParseError, SocketAddrs};
+#[cfg(feature = "std")]
+pub use buffer::{ReadBuffer, WriteBuffer};
+#[cfg(feature = "std")]
+pub use context::ErrorContext;
+#[cfg(all(feature = "std", unix))]
+pub use descriptor::{
+    AsReadWriteFds, DescriptorKind, FromDescriptorError, MaybeAsFd, MaybeAsRawFd,
+};
+#[cfg(feature = "tokio")]
+pub use listener::Listener;
+#[cfg(feature = "std")]
+pub use positional::PositionalIo;
+#[cfg(feature = "std")]
+pub use socket::SocketControl;
+#[cfg(feature = "tokio")]
+pub use split::{NativeSplit, ReuniteError};
+#[cfg(feature = "std")]
+pub use transcript::{Direction, Transcript, TranscriptFormat, TranscriptStream};
 
-    #[cfg(windows)]
-    impl IsSupported<&'static dyn std::os::windows::io::AsRawSocket> for () {
-        type Type = std::os::windows::io::OwnedSocket;
-    }
-}
+pub use derive_io_macros::{FmtWrite, PinProject};
 
-#[doc(hidden)]
-#[macro_export]
-macro_rules! __derive_io_read_parse {
-    ( ($($input:tt)*) $generics:tt ($($where:tt)*) ) => {
-        const _: &str = stringify!( generics = $generics, where = $($where)* );
-        $crate::__derive_impl!(__parse_type__ Read $generics ($($where)*) read $($input)*);
-    };
+/// A `Drop` hook that receives a pinned `self`, enabled with
+/// `#[pin(PinnedDrop)]` on a type deriving `PinProject`.
+///
+/// `PinProject` rejects manual `Drop` impls, as they would be able to move out
+/// of `#[pin]` fields.
+pub trait PinnedDrop {
+    fn drop(self: core::pin::Pin<&mut Self>);
 }
 
+#[cfg(feature = "tracing")]
 #[doc(hidden)]
 #[macro_export]
-macro_rules! __derive_io_bufread_parse {
-    ( ($($input:tt)*) $generics:tt ($($where:tt)*) ) => {
-        const _: &str = stringify!( generics = $generics, where = $($where)* );
-        $crate::__derive_impl!(__parse_type__ BufRead $generics ($($where)*) read $($input)*);
+macro_rules! __derive_io_trace {
+    ($($tt:tt)*) => {
+        $crate::__support::tracing::trace!($($tt)*)
     };
 }
 
+#[cfg(not(feature = "tracing"))]
 #[doc(hidden)]
 #[macro_export]
-macro_rules! __derive_io_write_parse {
-    ( ($($input:tt)*) $generics:tt ($($where:tt)*) ) => {
-        const _: &str = stringify!( generics = $generics, where = $($where)* );
-        $crate::__derive_impl!(__parse_type__ Write $generics ($($where)*) write $($input)*);
+macro_rules! __derive_io_trace {
+    ($($tt:tt)*) => {
+        ::core::compile_error!("#[io(trace)] requires the `tracing` feature of `derive-io`")
     };
 }
 
+#[cfg(feature = "axum")]
 #[doc(hidden)]
 #[macro_export]
-macro_rules! __derive_io_async_read_parse {
-    ( ($($input:tt)*) $generics:tt ($($where:tt)*) ) => {
-        const _: &str = stringify!( generics = $generics, where = $($where)* );
-        $crate::__derive_impl!(__parse_type__ AsyncRead $generics ($($where)*) read $($input)*);
-    };
-}
+macro_rules! __derive_io_axum_listener {
+    ([$($generics:tt)*] [$ty:ty] [$($bounds:tt)*]) => {
+        impl<$($generics)*> $crate::__support::axum::serve::Listener for $ty
+        where
+            $ty: $crate::Listener + ::core::marker::Send + 'static,
+            <$ty as $crate::Listener>::Stream: $crate::__support::tokio::io::AsyncRead
+                + $crate::__support::tokio::io::AsyncWrite
+                + ::core::marker::Unpin
+                + ::core::marker::Send
+                + 'static,
+            $($bounds)*
+        {
+            type Io = <$ty as $crate::Listener>::Stream;
+            type Addr = $crate::SocketAddrAny;
 
-#[doc(hidden)]
-#[macro_export]
-macro_rules! __derive_io_async_write_parse {
-    ( ($($input:tt)*) $generics:tt ($($where:tt)*) ) => {
-        const _: &str = stringify!( generics = $generics, where = $($where)* );
-        $crate::__derive_impl!(__parse_type__ AsyncWrite $generics ($($where)*) write $($input)*);
-    };
-}
+            fn accept(
+                &mut self,
+            ) -> impl ::core::future::Future<Output = (Self::Io, Self::Addr)> + ::core::marker::Send
+            {
+                $crate::__support::axum_accept(self)
+            }
 
-#[doc(hidden)]
-#[macro_export]
-macro_rules! __derive_io_as_file_descriptor_parse {
-    ( ($($input:tt)*) $generics:tt ($($where:tt)*) ) => {
-        const _: &str = stringify!( generics = $generics, where = $($where)* );
-        $crate::__derive_impl!(__parse_type__ AsFileDescriptor $generics ($($where)*) descriptor $($input)*);
+            fn local_addr(&self) -> ::std::io::Result<Self::Addr> {
+                $crate::Listener::local_addr(self)
+            }
+        }
     };
 }
 
+/// `axum::serve::Listener` is only implemented with the `axum` feature.
+#[cfg(not(feature = "axum"))]
 #[doc(hidden)]
 #[macro_export]
-macro_rules! __derive_io_as_socket_descriptor_parse {
-    ( ($($input:tt)*) $generics:tt ($($where:tt)*) ) => {
-        const _: &str = stringify!( generics = $generics, where = $($where)* );
-        $crate::__derive_impl!(__parse_type__ AsSocketDescriptor $generics ($($where)*) descriptor $($input)*);
-    };
+macro_rules! __derive_io_axum_listener {
+    ($($tt:tt)*) => {};
 }
 
 #[doc(hidden)]
-#[macro_export]
-macro_rules! __derive_impl {
-    // Parse enum types, identifying annotated fields. Next macro: __process_derive__
-    ( __parse_type__ $generator:ident $generics:tt $where:tt $attr:ident
-        $(#[$eattr:meta])* $vis:vis enum $name:ident {
-            $( $(#[$iattr:meta])* $field:ident
-                $( ( $($(#[$tuple_attr:meta])* $tuple_type:ty),* $(,)?) )?
-                $( { $($(#[$struct_attr:meta])* $struct_name:ident : $struct_type:ty),* $(,)? } )?
-            ),*
-            $(,)?
-        }
-    ) => {
-        $crate::__support::find_annotated_multi!(
-            ($crate::__derive_impl)
-            (__process_derive__ $generator $attr $generics $where enum $name)
-            $attr
-            {
-                compile_error!(concat!("No #[", stringify!($attr), "] field found"));
-            }
-            $(
-                (
-                    (Self::$field)
-                    $(
-                        $(
-                            (($([$tuple_attr])*)
-                            (type $tuple_type))
-                        )*
-                    )?
-                    $(
-                        $(
-                            (($([$struct_attr])*)
-                            (type $struct_type:($struct_name)))
-                        )*
-                    )?
-                )
-            )*
-        );
-    };
-
-    // Parse named structs, identifying annotated fields. Next macro: __process_derive__
-    ( __parse_type__ $generator:ident $generics:tt $where:tt $attr:ident
-        $(#[$sattr:meta])* $vis:vis struct $name:ident { $( $(#[$fattr:meta])* $fvis:vis $fname:ident : $ftype:ty ),* $(,)? }
-    ) => {
-        $crate::__support::find_annotated_multi!(
-            ($crate::__derive_impl)
-            (__process_derive__ $generator $attr $generics $where struct $name)
-            $attr
-            {
-                compile_error!(concat!("No #[", stringify!($attr), "] field found"));
-            }
-            ((Self)
-                $( (($([$fattr])*) (type $ftype: ($fname))) )*
-                (($([$sattr])*) (: (this)))
-            )
-        );
-    };
-
-    // Parse tuple structs, identifying annotated fields. Next macro: __process_derive__
-    ( __parse_type__ $generator:ident $generics:tt $where:tt $attr:ident
-        $(#[$sattr:meta])* $vis:vis struct $name:ident ( $( $(#[$fattr:meta])* $fvis:vis $ftype:ty ),* $(,)? );
-    ) => {
-        $crate::__support::find_annotated_multi!(
-            ($crate::__derive_impl)
-            (__process_derive__ $generator $attr $generics $where struct $name)
-            $attr
-            {
-                compile_error!(concat!("No #[", stringify!($attr), "] field found"));
-            }
-            ((Self)
-                $( (($([$fattr])*) (type $ftype)) )*
-            )
-        );
-    };
-
-    // Process the identified annotated fields. Next macro: __generate__ $generator
-    // Note that the input here is:
-    //   (case) index [attr] (type : name)
-    ( (__process_derive__ $generator:ident $attr:ident $generics:tt $where:tt $type:ident $name:ident) (
-        $( ( ($case:path) $index:literal $fattr:tt ( $( type $ftype:ty )? $( : ($fname:ident) )? ) ) )*
-    )) => {
-        const _: &str = stringify!( $type $name {
-            $(
-                # $fattr ($case) => $crate::__derive_impl!(__expand__ $attr ($case) $index $($ftype)? $(: $fname)?)
-            )*
-        });
-
-        $crate::__derive_impl!(__generate__ $generator $attr $generics $where ($($($ftype)?)*)
-            $type $name {
-                $(
-                    # $fattr ($case) => $crate::__derive_impl!(__expand__ $attr ($case) $index $($ftype)? $(: $fname)?)
-                )*
-            }
-        );
-    };
+pub mod __support {
+    use core::pin::Pin;
 
-    // Generate the impl block for Read. Next macro: __impl__
-    ( __generate__ Read $this:ident $generics:tt $where:tt $ftypes:tt $type:ident $name:ident $struct:tt) => {
-        $crate::__derive_impl!(__impl__ ::std::io::Read : $name $generics $where $ftypes #[read] {
-            fn read(&mut self, buf: &mut [u8]) -> ::std::io::Result<usize> {
-                let $this = self;
-                $crate::__derive_impl!(__foreach__ mut $this (::std::io::Read read($this, buf)) $struct)
-            }
-        });
+    #[cfg(feature = "std")]
+    pub use crate::buffer::{
+        buffered_consume, buffered_fill_buf, buffered_flush_buf, buffered_read, buffered_write,
     };
+    #[cfg(feature = "tokio")]
+    pub use crate::buffer::{
+        buffered_poll_fill_buf, buffered_poll_flush_buf, buffered_poll_read, buffered_poll_write,
+    };
+    #[cfg(feature = "std")]
+    pub use crate::context::AddContext;
+    #[cfg(all(feature = "std", unix))]
+    pub use crate::descriptor::probe_descriptor;
+    #[cfg(feature = "axum")]
+    pub use crate::listener::axum_accept;
+    #[cfg(feature = "std")]
+    pub use crate::socket::unsupported;
+    #[cfg(feature = "tokio")]
+    pub use crate::split::reunite_generic;
+    #[cfg(feature = "std")]
+    pub use crate::transcript::{record_transcript, transcript_error};
+
+    pub use crate::__derive_io_axum_listener as axum_listener;
+    pub use crate::__derive_io_trace as trace;
+    #[cfg(feature = "axum")]
+    pub use axum;
+    #[cfg(feature = "embedded-io")]
+    pub use embedded_io;
+    #[cfg(feature = "embedded-io-async")]
+    pub use embedded_io_async;
+    #[cfg(feature = "futures")]
+    pub use futures_core;
+    #[cfg(feature = "futures")]
+    pub use futures_sink;
+    #[cfg(feature = "hyper")]
+    pub use hyper;
+    #[cfg(feature = "mio")]
+    pub use mio;
+    #[cfg(feature = "monoio")]
+    pub use monoio;
+    #[cfg(feature = "tokio")]
+    pub use tokio;
+    #[cfg(feature = "tracing")]
+    pub use tracing;
+
+    /// Implemented by `#[derive(PinProject)]` to certify that the type upholds
+    /// the structural pinning guarantees for its `#[pin]` fields.
+    ///
+    /// # Safety
+    ///
+    /// The type must not be `Unpin` unless all `#[pin]` fields are `Unpin`, and
+    /// must not move out of `#[pin]` fields in `Drop`.
+    #[diagnostic::on_unimplemented(
+        message = "`{Self}` has `#[pin]` fields but does not `#[derive(PinProject)]`",
+        label = "add `#[derive(derive_io::PinProject)]` to this type"
+    )]
+    pub unsafe trait PinProjected {}
+
+    /// Asserts that a type delegating to `#[pin]` fields derives `PinProject`.
+    #[inline(always)]
+    pub fn assert_pin_projected<T: PinProjected + ?Sized>() {}
+
+    /// Implemented by the `Write` derives for `#[write(flush_on_drop)]`, and
+    /// called from the type's `Drop` impl (which is generated by
+    /// `#[derive(PinProject)]` if the type has `#[pin]` fields).
+    pub trait FlushOnDrop {
+        fn flush_on_drop(self: Pin<&mut Self>);
+    }
 
-    // Generate the impl block for BufRead. Next macro: __impl__
-    ( __generate__ BufRead $this:ident $generics:tt $where:tt $ftypes:tt $type:ident $name:ident $struct:tt) => {
-        $crate::__derive_impl!(__impl__ ::std::io::BufRead : $name $generics $where $ftypes #[read] {
-            fn fill_buf(&mut self) -> ::std::io::Result<&[u8]> {
-                let $this = self;
-                $crate::__derive_impl!(__foreach__ mut $this (::std::io::BufRead fill_buf($this)) $struct)
-            }
-
-            fn consume(&mut self, amt: usize) {
-                let $this = self;
-                $crate::__derive_impl!(__foreach__ mut $this (::std::io::BufRead consume($this, amt)) $struct)
-            }
-
-            // Not yet stable!
-            // fn has_data_left(&mut self) -> ::std::io::Result<bool> {
-            //     let $this = self;
-            //     $crate::__derive_impl!(__foreach__ mut $this (::std::io::BufRead has_data_left($this)) $struct)
-            // }
-
-            fn read_until(&mut self, byte: u8, buf: &mut Vec<u8>) -> ::std::io::Result<usize> {
-                let $this = self;
-                $crate::__derive_impl!(__foreach__ mut $this (::std::io::BufRead read_until($this, byte, buf)) $struct)
-            }
-
-            fn skip_until(&mut self, byte: u8) -> ::std::io::Result<usize> {
-                let $this = self;
-                $crate::__derive_impl!(__foreach__ mut $this (::std::io::BufRead skip_until($this, byte)) $struct)
-            }
+    /// Reports a failed flush on drop without an error sink, as a `tracing`
+    /// event if the `tracing` feature is enabled.
+    #[cfg(feature = "std")]
+    pub fn flush_on_drop_error(err: std::io::Error) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(error = %err, "derive-io flush on drop failed");
+        #[cfg(not(feature = "tracing"))]
+        let _ = err;
+    }
 
-            fn read_line(&mut self, buf: &mut String) -> ::std::io::Result<usize> {
-                let $this = self;
-                $crate::__derive_impl!(__foreach__ mut $this (::std::io::BufRead read_line($this, buf)) $struct)
-            }
+    /// A waker that does nothing, for polling the flush exactly once in
+    /// `Drop` (`Waker::noop` requires Rust 1.85).
+    pub fn noop_waker() -> core::task::Waker {
+        use core::task::{RawWaker, RawWakerVTable, Waker};
 
-            // Unimplemented because we cannot construct our own `Split`
-            // fn split(self, byte: u8) -> ::std::io::Split<Self> {
-            //     let $this = self;
-            //     $crate::__derive_impl!(__foreach__ take $this (::std::io::BufRead split($this, byte)) $struct)
-            // }
-
-            // Unimplemented because we cannot construct our own `Lines`
-            // fn lines(self) -> ::std::io::Lines<Self> {
-            //     let $this = self;
-            //     $crate::__derive_impl!(__foreach__ take $this (::std::io::BufRead lines($this)) $struct)
-            // }
-        });
-    };
+        const VTABLE: RawWakerVTable = RawWakerVTable::new(|_| RAW, |_| {}, |_| {}, |_| {});
+        const RAW: RawWaker = RawWaker::new(core::ptr::null(), &VTABLE);
 
-    // Generate the impl block for Write. Next macro: __impl__
-    ( __generate__ Write $this:ident $generics:tt $where:tt $ftypes:tt $type:ident $name:ident $struct:tt) => {
-        $crate::__derive_impl!(__impl__ ::std::io::Write : $name $generics $where $ftypes #[write] {
-            fn write(&mut self, buf: &[u8]) -> ::std::io::Result<usize> {
-                let $this = self;
-                $crate::__derive_impl!(__foreach__ mut $this (::std::io::Write write($this, buf)) $struct)
-            }
-            fn flush(&mut self) -> ::std::io::Result<()> {
-                let $this = self;
-                $crate::__derive_impl!(__foreach__ mut $this (::std::io::Write flush($this)) $struct)
-            }
-        });
-    };
-
-    // Generate the impl block for AsyncRead. Next macro: __impl__
-    ( __generate__ AsyncRead $this:ident $generics:tt $where:tt $ftypes:tt $type:ident $name:ident $struct:tt) => {
-        $crate::__derive_impl!(__impl__ ::tokio::io::AsyncRead : $name $generics $where $ftypes #[read] {
-            #[inline]
-            fn poll_read(
-                mut self: ::std::pin::Pin<&mut Self>,
-                cx: &mut ::std::task::Context<'_>,
-                buf: &mut ::tokio::io::ReadBuf<'_>,
-            ) -> ::std::task::Poll<::std::io::Result<()>> {
-                let $this = self;
-                $crate::__derive_impl!(__foreach_pin__ mut $this (::tokio::io::AsyncRead poll_read($this, cx, buf)) $struct)
-            }
-        });
-    };
-
-    // Generate the impl block for AsyncWrite. Next macro: __impl__
-    ( __generate__ AsyncWrite $this:ident $generics:tt $where:tt $ftypes:tt $type:ident $name:ident $struct:tt) => {
-        $crate::__derive_impl!(__impl__ ::tokio::io::AsyncWrite : $name $generics $where $ftypes #[write] {
-            #[inline]
-            fn poll_write(
-                self: ::std::pin::Pin<&mut Self>,
-                cx: &mut ::std::task::Context<'_>,
-                buf: &[u8],
-            ) -> ::std::task::Poll<::std::io::Result<usize>> {
-                let $this = self;
-                $crate::__derive_impl!(__foreach_pin__ mut $this (::tokio::io::AsyncWrite poll_write($this, cx, buf)) $struct)
-            }
+        // SAFETY: The vtable functions ignore the (null) data pointer.
+        unsafe { Waker::from_raw(RAW) }
+    }
 
-            #[inline]
-            fn poll_flush(
-                self: ::std::pin::Pin<&mut Self>,
-                cx: &mut ::std::task::Context<'_>,
-            ) -> ::std::task::Poll<::std::io::Result<()>> {
-                let $this = self;
-                $crate::__derive_impl!(__foreach_pin__ mut$this (::tokio::io::AsyncWrite poll_flush($this, cx)) $struct)
-            }
+    /// A successful result for `#[io(trace)]`, which has a byte count if it is
+    /// a `usize`. Other results (ie: `()` or an address) resolve to
+    /// `NoByteCount` by autoref: `(&Counted(result)).byte_count()`.
+    pub struct Counted<'a, T: ?Sized>(pub &'a T);
 
-            #[inline]
-            fn poll_shutdown(
-                self: ::std::pin::Pin<&mut Self>,
-                cx: &mut ::std::task::Context<'_>,
-            ) -> ::std::task::Poll<::std::io::Result<()>> {
-                let $this = self;
-                $crate::__derive_impl!(__foreach_pin__ mut $this (::tokio::io::AsyncWrite poll_shutdown($this, cx)) $struct)
-            }
+    /// The byte count of a `usize` result.
+    pub trait ByteCount {
+        fn byte_count(&self) -> Option<usize>;
+    }
 
-            #[inline]
-            fn is_write_vectored(&self) -> bool {
-                let $this = self;
-                $crate::__derive_impl!(__foreach__ ref $this (::tokio::io::AsyncWrite is_write_vectored($this)) $struct)
-            }
+    impl ByteCount for Counted<'_, usize> {
+        fn byte_count(&self) -> Option<usize> {
+            Some(*self.0)
+        }
+    }
 
-            #[inline]
-            fn poll_write_vectored(
-                self: ::std::pin::Pin<&mut Self>,
-                cx: &mut ::std::task::Context<'_>,
-                bufs: &[::std::io::IoSlice<'_>],
-            ) -> ::std::task::Poll<::std::io::Result<usize>> {
-                let $this = self;
-                $crate::__derive_impl!(__foreach_pin__ mut $this (::tokio::io::AsyncWrite poll_write_vectored($this, cx, bufs)) $struct)
-            }
-        });
-    };
+    /// The lack of a byte count of any other result.
+    pub trait NoByteCount {
+        fn byte_count(&self) -> Option<usize>;
+    }
 
-    // std::os::fd::{AsFd, AsRawFd}, std::os::windows::io::{AsHandle, AsRawHandle}
-    ( __generate__ AsFileDescriptor $this:ident $generics:tt $where:tt $ftypes:tt $type:ident $name:ident $struct:tt) => {
-        #[cfg(unix)]
-        $crate::__derive_impl!(__impl__ ::std::os::fd::AsFd : $name $generics $where $ftypes #[read] {
-            fn as_fd(&self) -> ::std::os::fd::BorrowedFd<'_> {
-                let $this = self;
-                $crate::__derive_impl!(__foreach__ ref $this (::std::os::fd::AsFd as_fd($this)) $struct)
-            }
-        });
-        #[cfg(unix)]
-        $crate::__derive_impl!(__impl__ ::std::os::fd::AsRawFd : $name $generics $where $ftypes #[read] {
-            fn as_raw_fd(&self) -> ::std::os::fd::RawFd {
-                let $this = self;
-                $crate::__derive_impl!(__foreach__ ref $this (::std::os::fd::AsRawFd as_raw_fd($this)) $struct)
-            }
-        });
-        #[cfg(windows)]
-        $crate::__derive_impl!(__impl__ ::std::os::windows::io::AsRawHandle : $name $generics $where $ftypes #[read] {
-            fn as_raw_handle(&self) -> ::std::os::windows::io::RawHandle {
-                let $this = self;
-                $crate::__derive_impl!(__foreach__ ref $this (::std::os::windows::io::AsRawHandle as_raw_handle($this)) $struct)
-            }
-        });
-        #[cfg(windows)]
-        $crate::__derive_impl!(__impl__ ::std::os::windows::io::AsHandle : $name $generics $where $ftypes #[read] {
-            fn as_handle(&self) -> ::std::os::windows::io::BorrowedHandle<'_> {
-                let $this = self;
-                $crate::__derive_impl!(__foreach__ ref $this (::std::os::windows::io::AsHandle as_handle($this)) $struct)
-            }
-        });
-    };
+    impl<T: ?Sized> NoByteCount for &Counted<'_, T> {
+        fn byte_count(&self) -> Option<usize> {
+            None
+        }
+    }
 
-    // std::os::fd::{AsFd, AsRawFd}, std::os::windows::io::{AsSocket, AsRawSocket}
-    ( __generate__ AsSocketDescriptor $this:ident $generics:tt $where:tt $ftypes:tt $type:ident $name:ident $struct:tt) => {
-        #[cfg(unix)]
-        $crate::__derive_impl!(__impl__ ::std::os::fd::AsFd : $name $generics $where $ftypes #[read] {
-            fn as_fd(&self) -> ::std::os::fd::BorrowedFd<'_> {
-                let $this = self;
-                $crate::__derive_impl!(__foreach__ ref $this (::std::os::fd::AsFd as_fd($this)) $struct)
+    /// Calls `f` with the first `n` bytes of `bufs`, one slice at a time.
+    #[cfg(feature = "std")]
+    pub fn inspect_io_slices(
+        bufs: &[std::io::IoSlice<'_>],
+        mut n: usize,
+        mut f: impl FnMut(&[u8]),
+    ) {
+        for buf in bufs {
+            if n == 0 {
+                break;
             }
-        });
-        #[cfg(unix)]
-        $crate::__derive_impl!(__impl__ ::std::os::fd::AsRawFd : $name $generics $where $ftypes #[read] {
-            fn as_raw_fd(&self) -> ::std::os::fd::RawFd {
-                let $this = self;
-                $crate::__derive_impl!(__foreach__ ref $this (::std::os::fd::AsRawFd as_raw_fd($this)) $struct)
-            }
-        });
-        #[cfg(windows)]
-        $crate::__derive_impl!(__impl__ ::std::os::windows::io::AsSocket : $name $generics $where $ftypes #[read] {
-            fn as_socket(&self) -> ::std::os::windows::io::BorrowedSocket<'_> {
-                let $this = self;
-                $crate::__derive_impl!(__foreach__ ref $this (::std::os::windows::io::AsSocket as_socket($this)) $struct)
-            }
-        });
-        #[cfg(windows)]
-        $crate::__derive_impl!(__impl__ ::std::os::windows::io::AsRawSocket : $name $generics $where $ftypes #[read] {
-            fn as_raw_socket(&self) -> ::std::os::windows::io::RawSocket {
-                let $this = self;
-                $crate::__derive_impl!(__foreach__ ref $this (::std::os::windows::io::AsRawSocket as_raw_socket($this)) $struct)
-            }
-        });
-    };
+            let len = n.min(buf.len());
+            f(&buf[..len]);
+            n -= len;
+        }
+    }
 
-    // Duplicate the $generics block. Next macro: __impl_2__
-    ( __impl__ $trait:path : $name:ident $generics:tt ($($where:tt)*) ($($ftype:path)*) #[$attr:ident] $block:tt) => {
-        $crate::__derive_impl!(__impl_2__ $trait : $name $generics $generics ($($where)*) ($($ftype)*) #[$attr] $block);
-    };
+    /// The first `n` bytes of an owned buffer returned by a write.
+    #[cfg(feature = "monoio")]
+    pub fn rent_bytes<T: monoio::buf::IoBuf>(buf: &T, n: usize) -> &[u8] {
+        // SAFETY: the first `bytes_init` bytes of an `IoBuf` are initialized
+        unsafe { std::slice::from_raw_parts(buf.read_ptr(), n.min(buf.bytes_init())) }
+    }
 
-    // Final macro. Generate the impl block.
-    ( __impl_2__ $trait:path : $name:ident $generics:tt ( $( ($($generic:tt)*) ),* ) ($($where:tt)*) ($($ftype:path)*) #[$attr:ident] $block:tt) => {
-        impl <$($($generic)*),*> $trait for $name <$($($generic)*),*>
-            where
-                // Add a where clause for each stream type. If it contains a generic, constrain it otherwise
-                // use a placeholder type that implements the trait for certain.
-                $(
-                    $crate::__support::type_has_generic!(
-                        ($ftype)
-                        $generics
-                        ($ftype)
-                        (<() as $crate::__support::IsSupported::<&'static dyn $trait>>::Type)
-                    ) : $trait,
-                )*
-                $($where)*
-        $block
-    };
+    /// Coerces a receiver to the receiver expected by a duck-typed method:
+    /// `&self`, `&mut self`, `self: Pin<&mut Self>` or `self: Pin<&Self>`.
+    ///
+    /// Coercing a pinned receiver to an unpinned one (or vice versa) requires
+    /// `Unpin`.
+    pub trait DuckCoerce<T> {
+        fn duck_coerce(self) -> T;
+    }
 
-    // Expand a self access pattern.
-    ( __expand__ $this:ident ($case:path) $index:literal : this) => {
-        {
-            $this
+    impl<'a, T: ?Sized> DuckCoerce<&'a T> for &'a T {
+        #[inline(always)]
+        fn duck_coerce(self) -> &'a T {
+            self
         }
-     };
+    }
 
-    // Expand a named field to an access pattern.
-    ( __expand__ $this:ident ($case:path) $index:literal $ftype:ty : $fname:tt) => {
-        {
-            let $case { $fname, .. } = $this else {
-                unreachable!()
-            };
-            $fname
+    impl<'a, T: ?Sized + Unpin> DuckCoerce<Pin<&'a T>> for &'a T {
+        #[inline(always)]
+        fn duck_coerce(self) -> Pin<&'a T> {
+            Pin::new(self)
         }
-     };
+    }
 
-    // Expand a tuple field to an access pattern.
-    ( __expand__ $this:ident ($case:path) $index:literal $ftype:ty) => {
-        {
-            let $crate::__support::repeat_in_parenthesis!(($case) $index (_,) ($this, .. )) = $this else {
-                unreachable!()
-            };
-            $this
+    impl<'a, T: ?Sized> DuckCoerce<&'a mut T> for &'a mut T {
+        #[inline(always)]
+        fn duck_coerce(self) -> &'a mut T {
+            self
         }
-    };
+    }
 
-    ( __foreach__ $refmut:tt $this:ident $fn:tt {$(
-        # $attr:tt ($case:path) => $access:expr
-    )*}) =>{
-        {
-            match $this {
-                $( $case {..} => {
-                    $crate::__derive_impl!(__validate_macro__ # $attr);
-                    $crate::__support::if_meta!(
-                        as_ref
-                        $attr
-                        ({
-                            let $this = $access.as_ref();
-                            $crate::__derive_impl!(__foreach_inner__ $refmut # $attr $fn)
-                        })
-                        ($crate::__support::if_meta!(
-                            deref
-                            $attr
-                            ({
-                                let $this = ::std::ops::Deref::deref($access);
-                                $crate::__derive_impl!(__foreach_inner__ $refmut # $attr $fn)
-                            })
-                            ($crate::__support::if_meta!(
-                                duck
-                                $attr
-                                ({
-                                    let $this = $access;
-                                    $crate::__derive_impl!(__foreach_inner_duck__ unpin $refmut # $attr $fn)
-                                })
-                                ({
-                                    let $this = $access;
-                                    $crate::__derive_impl!(__foreach_inner__ $refmut # $attr $fn)
-                                })
-                            ))
-                        ))
-                    )
-                } )*
-            }
+    impl<'a, T: ?Sized> DuckCoerce<&'a T> for &'a mut T {
+        #[inline(always)]
+        fn duck_coerce(self) -> &'a T {
+            self
         }
-    };
+    }
 
-    ( __foreach_pin__ $refmut:tt $this:ident $fn:tt {$(
-        # $attr:tt ($case:path) => $access:expr
-    )*}) =>{
-        {
-            match &*$this {
-                $( $case {..} => {
-                    $crate::__derive_impl!(__validate_macro__ # $attr);
-                    $crate::__support::if_meta!(
-                        as_ref
-                        $attr
-                        ({
-                            // NOTE: as_ref requires Unpin for safety
-                            let mut $this = $this.get_mut();
-                            let mut $this = ::std::pin::Pin::new($access);
-                            let $this = ::std::pin::Pin::new($this.get_mut().as_mut());
-                            $crate::__derive_impl!(__foreach_inner__ $refmut # $attr $fn)
-                        })
-                        ($crate::__support::if_meta!(
-                            deref
-                            $attr
-                            ({
-                                // NOTE: as_mut requires Unpin for safety
-                                let mut $this = $this.get_mut();
-                                let mut $this = ::std::ops::DerefMut::deref_mut($access);
-                                let $this = ::std::pin::Pin::new($this);
-                                $crate::__derive_impl!(__foreach_inner__ $refmut # $attr $fn)
-                            })
-                            ($crate::__support::if_meta!(
-                                duck
-                                $attr
-                                ({
-                                    // NOTE: duck typing requires Unpin for safety
-                                    let mut $this = $this.get_mut();
-                                    let mut $this = ::std::pin::Pin::new($access);
-                                    $crate::__derive_impl!(__foreach_inner_duck__ pin $refmut # $attr $fn)
-                                })
-                                ({
-                                    // SAFETY: we are mapping this pin to a
-                                    // nested field which must be `Unpin`.
-                                    // Because we are delegating to methods that
-                                    // never uses mutable references that aren't
-                                    // pinned, this is safe.
-                                    let mut $this = (unsafe { $this.get_unchecked_mut() });
-                                    let mut $this = (unsafe { ::std::pin::Pin::new_unchecked($access) });
-                                    $crate::__derive_impl!(__foreach_inner__ $refmut # $attr $fn)
-                                })
-                            ))
-                        ))
-                    )
-                } )*
-            }
+    impl<'a, T: ?Sized + Unpin> DuckCoerce<Pin<&'a mut T>> for &'a mut T {
+        #[inline(always)]
+        fn duck_coerce(self) -> Pin<&'a mut T> {
+            Pin::new(self)
         }
-    };
+    }
 
-    ( __foreach_inner__ $refmut:tt # $attr:tt ( $( :: $fn_part:ident )+ $fn_final:ident ( $($arg:expr),* ) ) ) => {
-        // needle, haystack, default
-        {
-            $crate::__support::extract_meta!(
-                $fn_final
-                $attr
-                ($(::$fn_part)+ :: $fn_final )
-            ) ($($arg),*)
+    impl<'a, T: ?Sized + Unpin> DuckCoerce<Pin<&'a T>> for &'a mut T {
+        #[inline(always)]
+        fn duck_coerce(self) -> Pin<&'a T> {
+            Pin::new(self)
         }
-    };
+    }
 
-    ( __foreach_inner_duck__ unpin ref # $attr:tt ( $( :: $fn_part:ident )+ $fn_final:ident ( $arg0:expr $(, $arg:expr)* ) ) ) => {
-        // needle, haystack, default
-        {
-            $crate::__support::extract_meta!(
-                $fn_final
-                $attr
-                ( Self :: $fn_final( ($arg0) $(, $arg)*) )
-            )
+    impl<'a, T: ?Sized> DuckCoerce<Pin<&'a mut T>> for Pin<&'a mut T> {
+        #[inline(always)]
+        fn duck_coerce(self) -> Pin<&'a mut T> {
+            self
         }
-    };
+    }
 
-    ( __foreach_inner_duck__ unpin mut # $attr:tt ( $( :: $fn_part:ident )+ $fn_final:ident ( $arg0:expr $(, $arg:expr)* ) ) ) => {
-        // needle, haystack, default
-        {
-            $crate::__support::extract_meta!(
-                $fn_final
-                $attr
-                ( Self :: $fn_final( ($arg0) $(, $arg)*) )
-            )
+    impl<'a, T: ?Sized + Unpin> DuckCoerce<&'a mut T> for Pin<&'a mut T> {
+        #[inline(always)]
+        fn duck_coerce(self) -> &'a mut T {
+            self.get_mut()
         }
-    };
+    }
 
-    ( __foreach_inner_duck__ $pin:tt ref # $attr:tt ( $( :: $fn_part:ident )+ $fn_final:ident ( $arg0:expr $(, $arg:expr)* ) ) ) => {
-        {
-            // I don't think anyone uses these...
-            unimplemented!("pin ref duck type is not yet supported");
+    impl<'a, T: ?Sized> DuckCoerce<&'a T> for Pin<&'a mut T> {
+        #[inline(always)]
+        fn duck_coerce(self) -> &'a T {
+            self.into_ref().get_ref()
         }
-    };
-
-    ( __foreach_inner_duck__ $pin:tt mut # $attr:tt ( $( :: $fn_part:ident )+ $fn_final:ident ( $arg0:expr $(, $arg:expr)* ) ) ) => {
-        {
-            // Choose the correct pointer for the receiver via trait.
-            #[allow(non_camel_case_types)]
-            trait __Derive_Io_Coerce<T, U> {
-                fn __derive_io_coerce(this: T) -> U;
-            }
-            impl<'a, T> __Derive_Io_Coerce<::std::pin::Pin<&'a mut T>, &'a mut T> for ::std::pin::Pin<&'a mut T> where T: Unpin {
-                fn __derive_io_coerce(this: ::std::pin::Pin<&'a mut T>) -> &'a mut T {
-                    this.get_mut()
-                }
-            }
-            impl<'a, T> __Derive_Io_Coerce<::std::pin::Pin<&'a mut T>, ::std::pin::Pin<&'a mut T>> for ::std::pin::Pin<&'a mut T> {
-                fn __derive_io_coerce(this: ::std::pin::Pin<&'a mut T>) -> ::std::pin::Pin<&'a mut T> {
-                    this
-                }
-            }
-
-            let self_type = $arg0;
-            let self_type = <::std::pin::Pin<&mut Self> as __Derive_Io_Coerce<_, _>>::__derive_io_coerce(self_type);
-
-            // needle, haystack, default
-            let callable = $crate::__support::extract_meta!(
-                $fn_final
-                $attr
-                (Self :: $fn_final)
-            );
+    }
 
-            callable(self_type $(, $arg)*)
+    impl<'a, T: ?Sized> DuckCoerce<Pin<&'a T>> for Pin<&'a mut T> {
+        #[inline(always)]
+        fn duck_coerce(self) -> Pin<&'a T> {
+            self.into_ref()
         }
-    };
-
-    ( __validate_macro__ #[read]) => {
-    };
-
-    ( __validate_macro__ #[read($( as_ref )? $(,)? $( deref )? $(,)? $( duck )? $(,)? $( poll_read=$poll_read:ident )? )]) => {
-    };
-
-    ( __validate_macro__ #[write]) => {
-    };
-
-    ( __validate_macro__ #[write($($key:ident $(=$value:ident)?),* $(,)?)]) => {
-        $crate::__derive_impl!(__validate_macro_deep__ #[write($($key $(=$value)?),*)]);
-    };
-
-    ( __validate_macro_deep__ #[write(
-        $( as_ref )? $(,)?
-        $( deref )? $(,)?
-        $( duck )? $(,)?
-        $( poll_write=$poll_write:ident )? $(,)?
-        $( poll_flush=$poll_flush:ident )? $(,)?
-        $( poll_shutdown=$poll_shutdown:ident )? $(,)?
-        $( is_write_vectored=$is_write_vectored:ident )? $(,)?
-        $( poll_write_vectored=$poll_write_vectored:ident )?
-    )]) => {
-    };
-
-    ( __validate_macro_deep__ # $($rest:tt)*) => {
-        compile_error!(concat!("Invalid #", stringify!($($rest)*), " attribute"));
-    };
-
-    ( __validate_macro__ #[descriptor]) => {
-    };
+    }
 
-    ( __validate_macro__ #[descriptor(as_ref)]) => {
-    };
+    impl<'a, T: ?Sized> DuckCoerce<Pin<&'a T>> for Pin<&'a T> {
+        #[inline(always)]
+        fn duck_coerce(self) -> Pin<&'a T> {
+            self
+        }
+    }
 
-    ( __validate_macro__ #[descriptor(deref)]) => {
-    };
+    impl<'a, T: ?Sized> DuckCoerce<&'a T> for Pin<&'a T> {
+        #[inline(always)]
+        fn duck_coerce(self) -> &'a T {
+            self.get_ref()
+        }
+    }
 
-    ( __validate_macro__ # $attr:tt) => {
-        compile_error!(concat!("Invalid #", stringify!($attr), " attribute"));
-    };
+    /// `Unpin` if and only if `T` is `Unpin`. The lifetime prevents the
+    /// `Unpin` bound from being a trivial (and potentially false) bound.
+    pub struct PinnedFields<'a, T: ?Sized>(core::marker::PhantomData<&'a ()>, T);
 }
@@ -0,0 +1,86 @@
+use std::future::Future;
+use std::io;
+use std::task::{Context, Poll};
+
+use crate::SocketAddrAny;
+
+/// Accepts connections, reporting addresses as a [`SocketAddrAny`] so that
+/// listeners of different transports can be used interchangeably.
+pub trait Listener {
+    /// The stream of an accepted connection.
+    type Stream;
+
+    /// Polls to accept a new connection, returning the stream and the peer's
+    /// address.
+    fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<io::Result<(Self::Stream, SocketAddrAny)>>;
+
+    /// Returns the local address that this listener is bound to.
+    fn local_addr(&self) -> io::Result<SocketAddrAny>;
+
+    /// Accepts a new connection, returning the stream and the peer's address.
+    fn accept(&self) -> impl Future<Output = io::Result<(Self::Stream, SocketAddrAny)>> {
+        std::future::poll_fn(move |cx| self.poll_accept(cx))
+    }
+}
+
+impl Listener for tokio::net::TcpListener {
+    type Stream = tokio::net::TcpStream;
+
+    fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<io::Result<(Self::Stream, SocketAddrAny)>> {
+        tokio::net::TcpListener::poll_accept(self, cx)
+            .map_ok(|(stream, addr)| (stream, addr.into()))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddrAny> {
+        tokio::net::TcpListener::local_addr(self).map(Into::into)
+    }
+}
+
+#[cfg(unix)]
+impl Listener for tokio::net::UnixListener {
+    type Stream = tokio::net::UnixStream;
+
+    fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<io::Result<(Self::Stream, SocketAddrAny)>> {
+        tokio::net::UnixListener::poll_accept(self, cx)
+            .map_ok(|(stream, addr)| (stream, addr.into()))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddrAny> {
+        tokio::net::UnixListener::local_addr(self).map(Into::into)
+    }
+}
+
+impl<T: Listener + ?Sized> Listener for Box<T> {
+    type Stream = T::Stream;
+
+    fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<io::Result<(Self::Stream, SocketAddrAny)>> {
+        (**self).poll_accept(cx)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddrAny> {
+        (**self).local_addr()
+    }
+}
+
+/// Accepts a connection for `axum::serve::Listener`, which cannot fail.
+/// Errors other than a connection error are retried after a second, as axum
+/// does for its own listeners.
+#[cfg(feature = "axum")]
+pub async fn axum_accept<L: Listener + ?Sized>(listener: &mut L) -> (L::Stream, SocketAddrAny) {
+    loop {
+        let listener = &mut *listener;
+        match std::future::poll_fn(move |cx| listener.poll_accept(cx)).await {
+            Ok(accepted) => return accepted,
+            Err(e) => {
+                if !matches!(
+                    e.kind(),
+                    io::ErrorKind::ConnectionRefused
+                        | io::ErrorKind::ConnectionAborted
+                        | io::ErrorKind::ConnectionReset
+                ) {
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::io;
+
+/// Reads and writes at a given offset, without using or moving a cursor.
+///
+/// This is a portable counterpart to `std::os::unix::fs::FileExt`. Writes take
+/// `&mut self`, so that in-memory stores (ie: `Vec<u8>`) can implement it
+/// alongside `File`.
+pub trait PositionalIo {
+    /// Reads bytes starting at `offset`, returning the number of bytes read.
+    /// Reading at or past the end returns `Ok(0)`.
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize>;
+
+    /// Writes bytes starting at `offset`, returning the number of bytes
+    /// written.
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> io::Result<usize>;
+
+    /// Reads exactly `buf.len()` bytes starting at `offset`.
+    fn read_exact_at(&self, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.read_at(buf, offset) {
+                Ok(0) => break,
+                Ok(n) => {
+                    buf = &mut buf[n..];
+                    offset += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if buf.is_empty() {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "failed to fill whole buffer",
+            ))
+        }
+    }
+
+    /// Writes all of `buf` starting at `offset`.
+    fn write_all_at(&mut self, mut buf: &[u8], mut offset: u64) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write_at(buf, offset) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write whole buffer",
+                    ))
+                }
+                Ok(n) => {
+                    buf = &buf[n..];
+                    offset += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+impl PositionalIo for std::fs::File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        std::os::unix::fs::FileExt::read_at(self, buf, offset)
+    }
+
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        std::os::unix::fs::FileExt::write_at(self, buf, offset)
+    }
+}
+
+/// On Windows, the file's cursor is moved past the bytes read or written.
+#[cfg(windows)]
+impl PositionalIo for std::fs::File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        std::os::windows::fs::FileExt::seek_read(self, buf, offset)
+    }
+
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        std::os::windows::fs::FileExt::seek_write(self, buf, offset)
+    }
+}
+
+/// Writes past the end of the slice write nothing.
+impl PositionalIo for [u8] {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let src = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| self.get(offset..))
+            .unwrap_or_default();
+        let n = buf.len().min(src.len());
+        buf[..n].copy_from_slice(&src[..n]);
+        Ok(n)
+    }
+
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        let dst = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| self.get_mut(offset..))
+            .unwrap_or_default();
+        let n = buf.len().min(dst.len());
+        dst[..n].copy_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Writes past the end of the vector extend it, filling any gap with zeros.
+impl PositionalIo for Vec<u8> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        self.as_slice().read_at(buf, offset)
+    }
+
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        let end = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| offset.checked_add(buf.len()))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "offset is too large"))?;
+        if end > self.len() {
+            self.resize(end, 0);
+        }
+        self.as_mut_slice().write_at(buf, offset)
+    }
+}
+
+impl<T: PositionalIo + ?Sized> PositionalIo for Box<T> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        (**self).read_at(buf, offset)
+    }
+
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        (**self).write_at(buf, offset)
+    }
+
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        (**self).read_exact_at(buf, offset)
+    }
+
+    fn write_all_at(&mut self, buf: &[u8], offset: u64) -> io::Result<()> {
+        (**self).write_all_at(buf, offset)
+    }
+}
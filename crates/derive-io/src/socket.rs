@@ -0,0 +1,228 @@
+use std::io;
+use std::net::Shutdown;
+use std::time::Duration;
+
+/// The control operations of a socket, besides reading and writing.
+///
+/// Each method returns an error of kind [`io::ErrorKind::Unsupported`] by
+/// default, so that sockets only implement the operations that they support
+/// (ie: `set_nodelay` is only supported by TCP streams). This is implemented
+/// for the sockets of std and tokio, and can be derived for enums of them
+/// with `#[derive(SocketControl)]`.
+pub trait SocketControl {
+    /// Sets `TCP_NODELAY`, which disables Nagle's algorithm.
+    fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        _ = nodelay;
+        Err(unsupported("set_nodelay"))
+    }
+
+    /// Returns the value of `TCP_NODELAY`.
+    fn nodelay(&self) -> io::Result<bool> {
+        Err(unsupported("nodelay"))
+    }
+
+    /// Sets `IP_TTL`, the time-to-live of outgoing packets.
+    fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        _ = ttl;
+        Err(unsupported("set_ttl"))
+    }
+
+    /// Shuts down the read half, the write half or both halves of the
+    /// connection.
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        _ = how;
+        Err(unsupported("shutdown"))
+    }
+
+    /// Creates a new handle to the same socket.
+    fn try_clone(&self) -> io::Result<Self>
+    where
+        Self: Sized,
+    {
+        Err(unsupported("try_clone"))
+    }
+
+    /// Moves the socket into or out of non-blocking mode.
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        _ = nonblocking;
+        Err(unsupported("set_nonblocking"))
+    }
+
+    /// Sets the timeout of blocking reads, or removes it with `None`.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        _ = timeout;
+        Err(unsupported("set_read_timeout"))
+    }
+
+    /// Sets the timeout of blocking writes, or removes it with `None`.
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        _ = timeout;
+        Err(unsupported("set_write_timeout"))
+    }
+
+    /// Returns and clears the pending error of the socket (`SO_ERROR`).
+    fn take_error(&self) -> io::Result<Option<io::Error>> {
+        Err(unsupported("take_error"))
+    }
+
+    /// Borrows the socket as a `socket2::SockRef`, for options that are not
+    /// covered by this trait.
+    #[cfg(all(feature = "socket2", unix))]
+    fn sock_ref(&self) -> socket2::SockRef<'_>
+    where
+        Self: Sized + std::os::fd::AsFd,
+    {
+        socket2::SockRef::from(self)
+    }
+
+    /// Borrows the socket as a `socket2::SockRef`, for options that are not
+    /// covered by this trait.
+    #[cfg(all(feature = "socket2", windows))]
+    fn sock_ref(&self) -> socket2::SockRef<'_>
+    where
+        Self: Sized + std::os::windows::io::AsSocket,
+    {
+        socket2::SockRef::from(self)
+    }
+}
+
+/// The error of an operation that the socket does not support.
+pub fn unsupported(operation: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("`{operation}` is not supported by this socket"),
+    )
+}
+
+/// Implements the given `SocketControl` methods with the inherent methods of
+/// the same name.
+macro_rules! socket_control {
+    ($(#[$cfg:meta])* $ty:ty { $($method:ident),* $(,)? }) => {
+        $(#[$cfg])*
+        impl SocketControl for $ty {
+            $(socket_control!(@method $ty, $method);)*
+        }
+    };
+    (@method $ty:ty, set_nodelay) => {
+        fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+            <$ty>::set_nodelay(self, nodelay)
+        }
+    };
+    (@method $ty:ty, nodelay) => {
+        fn nodelay(&self) -> io::Result<bool> {
+            <$ty>::nodelay(self)
+        }
+    };
+    (@method $ty:ty, set_ttl) => {
+        fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+            <$ty>::set_ttl(self, ttl)
+        }
+    };
+    (@method $ty:ty, shutdown) => {
+        fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+            <$ty>::shutdown(self, how)
+        }
+    };
+    (@method $ty:ty, try_clone) => {
+        fn try_clone(&self) -> io::Result<Self> {
+            <$ty>::try_clone(self)
+        }
+    };
+    (@method $ty:ty, set_nonblocking) => {
+        fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+            <$ty>::set_nonblocking(self, nonblocking)
+        }
+    };
+    (@method $ty:ty, set_read_timeout) => {
+        fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+            <$ty>::set_read_timeout(self, timeout)
+        }
+    };
+    (@method $ty:ty, set_write_timeout) => {
+        fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+            <$ty>::set_write_timeout(self, timeout)
+        }
+    };
+    (@method $ty:ty, take_error) => {
+        fn take_error(&self) -> io::Result<Option<io::Error>> {
+            <$ty>::take_error(self)
+        }
+    };
+}
+
+socket_control!(std::net::TcpStream {
+    set_nodelay,
+    nodelay,
+    set_ttl,
+    shutdown,
+    try_clone,
+    set_nonblocking,
+    set_read_timeout,
+    set_write_timeout,
+    take_error,
+});
+
+socket_control!(std::net::UdpSocket {
+    set_ttl,
+    try_clone,
+    set_nonblocking,
+    set_read_timeout,
+    set_write_timeout,
+    take_error,
+});
+
+socket_control!(
+    #[cfg(unix)]
+    std::os::unix::net::UnixStream {
+        shutdown,
+        try_clone,
+        set_nonblocking,
+        set_read_timeout,
+        set_write_timeout,
+        take_error,
+    }
+);
+
+socket_control!(
+    #[cfg(unix)]
+    std::os::unix::net::UnixDatagram {
+        shutdown,
+        try_clone,
+        set_nonblocking,
+        set_read_timeout,
+        set_write_timeout,
+        take_error,
+    }
+);
+
+// tokio sockets are always non-blocking, so they do not support timeouts
+socket_control!(
+    #[cfg(feature = "tokio")]
+    tokio::net::TcpStream {
+        set_nodelay,
+        nodelay,
+        set_ttl,
+        take_error,
+    }
+);
+
+socket_control!(
+    #[cfg(feature = "tokio")]
+    tokio::net::UdpSocket {
+        set_ttl,
+        take_error,
+    }
+);
+
+socket_control!(
+    #[cfg(all(unix, feature = "tokio"))]
+    tokio::net::UnixStream { take_error }
+);
+
+socket_control!(
+    #[cfg(all(unix, feature = "tokio"))]
+    tokio::net::UnixDatagram {
+        shutdown,
+        take_error,
+    }
+);
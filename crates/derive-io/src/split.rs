@@ -0,0 +1,109 @@
+use std::error::Error;
+use std::fmt;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadHalf, WriteHalf};
+
+/// Splits a stream into read and write halves without locking, as the
+/// `split` and `into_split` methods of tokio's `TcpStream` and `UnixStream`
+/// do.
+pub trait NativeSplit: Sized {
+    /// The read half borrowed by [`split`](NativeSplit::split).
+    type ReadHalf<'a>: AsyncRead + Unpin
+    where
+        Self: 'a;
+    /// The write half borrowed by [`split`](NativeSplit::split).
+    type WriteHalf<'a>: AsyncWrite + Unpin
+    where
+        Self: 'a;
+    /// The read half returned by [`into_split`](NativeSplit::into_split).
+    type OwnedReadHalf: AsyncRead + Unpin;
+    /// The write half returned by [`into_split`](NativeSplit::into_split).
+    type OwnedWriteHalf: AsyncWrite + Unpin;
+
+    /// Splits the stream into halves that borrow it.
+    fn split(&mut self) -> (Self::ReadHalf<'_>, Self::WriteHalf<'_>);
+
+    /// Splits the stream into halves that can be moved independently.
+    fn into_split(self) -> (Self::OwnedReadHalf, Self::OwnedWriteHalf);
+
+    /// Joins the halves returned by [`into_split`](NativeSplit::into_split),
+    /// returning them in the error if they are not from the same stream.
+    #[allow(clippy::type_complexity)]
+    fn reunite(
+        read: Self::OwnedReadHalf,
+        write: Self::OwnedWriteHalf,
+    ) -> Result<Self, ReuniteError<Self::OwnedReadHalf, Self::OwnedWriteHalf>>;
+}
+
+/// The halves passed to [`NativeSplit::reunite`], which were not split from
+/// the same stream.
+#[derive(Debug)]
+pub struct ReuniteError<R, W>(pub R, pub W);
+
+impl<R, W> fmt::Display for ReuniteError<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("tried to reunite halves that are not from the same stream")
+    }
+}
+
+impl<R: fmt::Debug, W: fmt::Debug> Error for ReuniteError<R, W> {}
+
+impl NativeSplit for tokio::net::TcpStream {
+    type ReadHalf<'a> = tokio::net::tcp::ReadHalf<'a>;
+    type WriteHalf<'a> = tokio::net::tcp::WriteHalf<'a>;
+    type OwnedReadHalf = tokio::net::tcp::OwnedReadHalf;
+    type OwnedWriteHalf = tokio::net::tcp::OwnedWriteHalf;
+
+    fn split(&mut self) -> (Self::ReadHalf<'_>, Self::WriteHalf<'_>) {
+        tokio::net::TcpStream::split(self)
+    }
+
+    fn into_split(self) -> (Self::OwnedReadHalf, Self::OwnedWriteHalf) {
+        tokio::net::TcpStream::into_split(self)
+    }
+
+    fn reunite(
+        read: Self::OwnedReadHalf,
+        write: Self::OwnedWriteHalf,
+    ) -> Result<Self, ReuniteError<Self::OwnedReadHalf, Self::OwnedWriteHalf>> {
+        read.reunite(write)
+            .map_err(|tokio::net::tcp::ReuniteError(read, write)| ReuniteError(read, write))
+    }
+}
+
+#[cfg(unix)]
+impl NativeSplit for tokio::net::UnixStream {
+    type ReadHalf<'a> = tokio::net::unix::ReadHalf<'a>;
+    type WriteHalf<'a> = tokio::net::unix::WriteHalf<'a>;
+    type OwnedReadHalf = tokio::net::unix::OwnedReadHalf;
+    type OwnedWriteHalf = tokio::net::unix::OwnedWriteHalf;
+
+    fn split(&mut self) -> (Self::ReadHalf<'_>, Self::WriteHalf<'_>) {
+        tokio::net::UnixStream::split(self)
+    }
+
+    fn into_split(self) -> (Self::OwnedReadHalf, Self::OwnedWriteHalf) {
+        tokio::net::UnixStream::into_split(self)
+    }
+
+    fn reunite(
+        read: Self::OwnedReadHalf,
+        write: Self::OwnedWriteHalf,
+    ) -> Result<Self, ReuniteError<Self::OwnedReadHalf, Self::OwnedWriteHalf>> {
+        read.reunite(write)
+            .map_err(|tokio::net::unix::ReuniteError(read, write)| ReuniteError(read, write))
+    }
+}
+
+/// Joins the halves of `tokio::io::split`, for `#[split(generic)]` fields.
+#[allow(clippy::type_complexity)]
+pub fn reunite_generic<T: Unpin>(
+    read: ReadHalf<T>,
+    write: WriteHalf<T>,
+) -> Result<T, ReuniteError<ReadHalf<T>, WriteHalf<T>>> {
+    if read.is_pair_of(&write) {
+        Ok(read.unsplit(write))
+    } else {
+        Err(ReuniteError(read, write))
+    }
+}
@@ -0,0 +1,459 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The direction of the bytes in a [`Transcript`] record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Bytes read from the stream.
+    Read,
+    /// Bytes written to the stream.
+    Write,
+}
+
+/// The file format of a [`Transcript`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TranscriptFormat {
+    /// A text hexdump, starting with a `# derive-io transcript` line. Each
+    /// record is a header line of the form
+    /// `<direction> <stream> <seconds>.<micros> <length>`, where `<direction>`
+    /// is `<` for reads and `>` for writes, `<stream>` is the id of the
+    /// [`TranscriptStream`] and the timestamp is relative to the Unix epoch,
+    /// followed by one indented line per 16 bytes:
+    ///
+    /// ```text
+    /// > 0 1760832000.000000 4
+    ///   00000000  70 69 6e 67                                       |ping|
+    /// ```
+    Hexdump,
+    /// A pcapng capture with one synthetic TCP flow per [`TranscriptStream`],
+    /// between `10.0.0.1:<49152 + stream id>` (this side) and `10.0.0.2:80`
+    /// (the peer). Each flow starts with a three-way handshake before its
+    /// first segment. Writes are sent by this side and reads by the peer.
+    Pcapng,
+}
+
+/// A recording of the bytes read from and written to streams, for
+/// `#[io(transcript = <path>)]`. The bytes of each stream are recorded through
+/// its own [`TranscriptStream`].
+///
+/// Each record is written to the underlying writer as soon as it is made, so
+/// the transcript is complete even if the process exits without dropping it.
+pub struct Transcript {
+    inner: Mutex<Inner>,
+    /// The path of the file, to name it in errors.
+    path: Option<PathBuf>,
+    /// The number of streams started, which is shared by every transcript
+    /// opened at the same path so that the ids are unique within the file.
+    streams: Arc<AtomicU32>,
+}
+
+struct Inner {
+    writer: Box<dyn Write + Send>,
+    format: TranscriptFormat,
+    ip_id: u16,
+}
+
+impl Transcript {
+    /// Creates a transcript file at `path`. The format is pcapng if the file
+    /// has a `pcapng` extension, and a hexdump otherwise.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::open(
+            path.as_ref(),
+            File::create(path.as_ref())?,
+            Default::default(),
+        )
+    }
+
+    /// Creates a transcript that writes records to `writer` in the given
+    /// format.
+    pub fn new(writer: impl Write + Send + 'static, format: TranscriptFormat) -> io::Result<Self> {
+        Self::start(writer, format, None, Default::default())
+    }
+
+    fn open(path: &Path, file: File, streams: Arc<AtomicU32>) -> io::Result<Self> {
+        let format = if path.extension().is_some_and(|ext| ext == "pcapng") {
+            TranscriptFormat::Pcapng
+        } else {
+            TranscriptFormat::Hexdump
+        };
+        Self::start(file, format, Some(path.to_owned()), streams)
+    }
+
+    fn start(
+        writer: impl Write + Send + 'static,
+        format: TranscriptFormat,
+        path: Option<PathBuf>,
+        streams: Arc<AtomicU32>,
+    ) -> io::Result<Self> {
+        let mut inner = Inner {
+            writer: Box::new(writer),
+            format,
+            ip_id: 0,
+        };
+        inner.start()?;
+        Ok(Self {
+            inner: Mutex::new(inner),
+            path,
+            streams,
+        })
+    }
+
+    /// The format of the transcript.
+    pub fn format(&self) -> TranscriptFormat {
+        self.lock().format
+    }
+
+    /// Starts recording a new stream, with the next stream id.
+    pub fn stream(self: &Arc<Self>) -> TranscriptStream {
+        let flow = Flow {
+            transcript: self.clone(),
+            id: self.streams.fetch_add(1, Ordering::Relaxed),
+            started: AtomicBool::new(false),
+            seq: Default::default(),
+        };
+        TranscriptStream {
+            flow: OnceLock::from(flow),
+        }
+    }
+
+    /// Records `bytes` of a stream in the given direction, timestamped with the
+    /// current time.
+    fn record(&self, flow: &Flow, direction: Direction, bytes: &[u8]) -> io::Result<()> {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut inner = self.lock();
+        match inner.format {
+            TranscriptFormat::Hexdump => {
+                let mut out = format!(
+                    "{} {} {}.{:06} {}\n",
+                    match direction {
+                        Direction::Read => '<',
+                        Direction::Write => '>',
+                    },
+                    flow.id,
+                    time.as_secs(),
+                    time.subsec_micros(),
+                    bytes.len()
+                );
+                hexdump(&mut out, bytes);
+                inner.writer.write_all(out.as_bytes())
+            }
+            TranscriptFormat::Pcapng => {
+                let micros = time.as_micros() as u64;
+                if !flow.started.load(Ordering::Relaxed) {
+                    inner.segment(flow, micros, Direction::Write, SYN, &[])?;
+                    inner.segment(flow, micros, Direction::Read, SYN | ACK, &[])?;
+                    inner.segment(flow, micros, Direction::Write, ACK, &[])?;
+                    flow.started.store(true, Ordering::Relaxed);
+                }
+                // Keep each segment within the maximum IPv4 packet size. Empty
+                // reads and writes (ie: end of file) have no segment.
+                for chunk in bytes.chunks(MAX_SEGMENT) {
+                    inner.segment(flow, micros, direction, PSH | ACK, chunk)?;
+                }
+                Ok(())
+            }
+        }
+        .map_err(|err| match &self.path {
+            Some(path) => io::Error::new(
+                err.kind(),
+                format!("failed to record transcript {path:?}: {err}"),
+            ),
+            None => err,
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        // A panic while recording leaves at worst a truncated record
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl std::fmt::Debug for Transcript {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Transcript")
+            .field("path", &self.path)
+            .field("format", &self.format())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The recording of a single stream in a [`Transcript`], for the
+/// `#[io(transcript)]` field of a type with `#[io(transcript = <path>)]`.
+///
+/// A default `TranscriptStream` is attached to the transcript at the path the
+/// first time it records bytes, so that the path is only evaluated once per
+/// stream.
+#[derive(Default)]
+pub struct TranscriptStream {
+    flow: OnceLock<Flow>,
+}
+
+struct Flow {
+    transcript: Arc<Transcript>,
+    id: u32,
+    /// Whether the TCP handshake of a pcapng flow has been written.
+    started: AtomicBool,
+    /// The next sequence numbers of this side and of the peer. These are only
+    /// accessed with the transcript locked.
+    seq: [AtomicU32; 2],
+}
+
+impl TranscriptStream {
+    /// Creates a stream that is not yet attached to a transcript.
+    pub const fn new() -> Self {
+        Self {
+            flow: OnceLock::new(),
+        }
+    }
+
+    /// The id of the stream within its transcript, if it is attached to one.
+    pub fn id(&self) -> Option<u32> {
+        self.flow.get().map(|flow| flow.id)
+    }
+
+    /// Records `bytes` in the given direction, timestamped with the current
+    /// time. Fails if the stream is not attached to a transcript.
+    pub fn record(&self, direction: Direction, bytes: &[u8]) -> io::Result<()> {
+        let flow = self.flow.get().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotConnected,
+                "transcript stream is not attached to a transcript",
+            )
+        })?;
+        flow.transcript.record(flow, direction, bytes)
+    }
+}
+
+impl std::fmt::Debug for TranscriptStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("TranscriptStream");
+        if let Some(flow) = self.flow.get() {
+            debug
+                .field("transcript", &flow.transcript.path)
+                .field("id", &flow.id);
+        }
+        debug.finish_non_exhaustive()
+    }
+}
+
+fn hexdump(out: &mut String, bytes: &[u8]) {
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        let _ = write!(out, "  {:08x} ", line * 16);
+        for i in 0..16 {
+            if i == 8 {
+                out.push(' ');
+            }
+            match chunk.get(i) {
+                Some(byte) => _ = write!(out, " {byte:02x}"),
+                None => out.push_str("   "),
+            }
+        }
+        out.push_str("  |");
+        out.extend(chunk.iter().map(|&byte| {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            }
+        }));
+        out.push_str("|\n");
+    }
+}
+
+const LOCAL: [u8; 4] = [10, 0, 0, 1];
+/// The local port of the first stream. Each stream has its own port, cycling
+/// through the dynamic port range.
+const LOCAL_PORT: u16 = 49152;
+const PEER: ([u8; 4], u16) = ([10, 0, 0, 2], 80);
+const MAX_SEGMENT: usize = 65535 - 40;
+/// `LINKTYPE_RAW`: packets begin with an IPv4 header.
+const LINKTYPE_RAW: u16 = 101;
+
+const SYN: u8 = 0x02;
+const PSH: u8 = 0x08;
+const ACK: u8 = 0x10;
+
+impl Inner {
+    /// Writes the file header.
+    fn start(&mut self) -> io::Result<()> {
+        match self.format {
+            TranscriptFormat::Hexdump => self.writer.write_all(b"# derive-io transcript\n"),
+            TranscriptFormat::Pcapng => {
+                // Section Header Block
+                let mut shb = Vec::new();
+                shb.extend(0x1a2b3c4d_u32.to_le_bytes());
+                shb.extend(1_u16.to_le_bytes());
+                shb.extend(0_u16.to_le_bytes());
+                shb.extend((-1_i64).to_le_bytes());
+                self.block(0x0a0d0d0a, &shb)?;
+                // Interface Description Block, with the default microsecond
+                // timestamp resolution
+                let mut idb = Vec::new();
+                idb.extend(LINKTYPE_RAW.to_le_bytes());
+                idb.extend(0_u16.to_le_bytes());
+                idb.extend(0_u32.to_le_bytes());
+                self.block(1, &idb)
+            }
+        }
+    }
+
+    fn block(&mut self, kind: u32, body: &[u8]) -> io::Result<()> {
+        let len = (12 + body.len().next_multiple_of(4)) as u32;
+        let mut block = Vec::with_capacity(len as usize);
+        block.extend(kind.to_le_bytes());
+        block.extend(len.to_le_bytes());
+        block.extend(body);
+        block.resize(len as usize - 4, 0);
+        block.extend(len.to_le_bytes());
+        self.writer.write_all(&block)
+    }
+
+    /// Writes a TCP segment of a stream from this side (for writes) or from the
+    /// peer (for reads) in an Enhanced Packet Block.
+    fn segment(
+        &mut self,
+        flow: &Flow,
+        micros: u64,
+        direction: Direction,
+        flags: u8,
+        payload: &[u8],
+    ) -> io::Result<()> {
+        let local = (LOCAL, LOCAL_PORT + (flow.id % 16384) as u16);
+        let (from, to, (src, sport), (dst, dport)) = match direction {
+            Direction::Write => (0, 1, local, PEER),
+            Direction::Read => (1, 0, PEER, local),
+        };
+        let seq = flow.seq[from].load(Ordering::Relaxed);
+        let ack = if flags & ACK != 0 {
+            flow.seq[to].load(Ordering::Relaxed)
+        } else {
+            0
+        };
+        // SYN consumes one sequence number
+        let advance = payload.len() as u32 + u32::from(flags & SYN != 0);
+        flow.seq[from].store(seq.wrapping_add(advance), Ordering::Relaxed);
+        self.ip_id = self.ip_id.wrapping_add(1);
+
+        let mut packet = Vec::with_capacity(40 + payload.len());
+        packet.extend([0x45, 0]);
+        packet.extend(((40 + payload.len()) as u16).to_be_bytes());
+        packet.extend(self.ip_id.to_be_bytes());
+        packet.extend([0x40, 0, 64, 6, 0, 0]);
+        packet.extend(src);
+        packet.extend(dst);
+        let ip_checksum = checksum(0, &packet);
+        packet[10..12].copy_from_slice(&ip_checksum.to_be_bytes());
+
+        packet.extend(sport.to_be_bytes());
+        packet.extend(dport.to_be_bytes());
+        packet.extend(seq.to_be_bytes());
+        packet.extend(ack.to_be_bytes());
+        packet.extend([0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+        packet.extend(payload);
+        let mut pseudo = Vec::with_capacity(12);
+        pseudo.extend(src);
+        pseudo.extend(dst);
+        pseudo.extend([0, 6]);
+        pseudo.extend(((20 + payload.len()) as u16).to_be_bytes());
+        let tcp_checksum = checksum(sum(0, &pseudo), &packet[20..]);
+        packet[36..38].copy_from_slice(&tcp_checksum.to_be_bytes());
+
+        let mut epb = Vec::with_capacity(20 + packet.len());
+        epb.extend(0_u32.to_le_bytes());
+        epb.extend(((micros >> 32) as u32).to_le_bytes());
+        epb.extend((micros as u32).to_le_bytes());
+        epb.extend((packet.len() as u32).to_le_bytes());
+        epb.extend((packet.len() as u32).to_le_bytes());
+        epb.extend(packet);
+        self.block(6, &epb)
+    }
+}
+
+/// The ones' complement sum of `bytes` as big-endian 16-bit words.
+fn sum(mut sum: u32, bytes: &[u8]) -> u32 {
+    for word in bytes.chunks(2) {
+        sum += u32::from(u16::from_be_bytes([word[0], *word.get(1).unwrap_or(&0)]));
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum
+}
+
+/// The Internet checksum of `bytes`, continuing from the partial `initial` sum.
+fn checksum(initial: u32, bytes: &[u8]) -> u16 {
+    !(sum(initial, bytes) as u16)
+}
+
+/// Returns the transcript for `#[io(transcript = <path>)]`, which is created
+/// the first time the path is used and then shared by every stream that uses
+/// the same path. It is closed once every stream is dropped, and appended to if
+/// the path is used again. A transcript that cannot be created is retried on
+/// the next use.
+fn transcript(path: &Path) -> io::Result<Arc<Transcript>> {
+    type Transcripts = HashMap<PathBuf, (Weak<Transcript>, Arc<AtomicU32>)>;
+    static TRANSCRIPTS: OnceLock<Mutex<Transcripts>> = OnceLock::new();
+    let mut transcripts = TRANSCRIPTS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    let entry = transcripts.get(path);
+    if let Some(transcript) = entry.and_then(|(transcript, _)| transcript.upgrade()) {
+        return Ok(transcript);
+    }
+    let transcript = match entry {
+        // Continue the stream ids of the earlier transcript in the same file
+        Some((_, streams)) => {
+            let file = OpenOptions::new().append(true).open(path)?;
+            Transcript::open(path, file, streams.clone())?
+        }
+        None => Transcript::create(path)?,
+    };
+    let transcript = Arc::new(transcript);
+    transcripts.insert(
+        path.to_owned(),
+        (Arc::downgrade(&transcript), transcript.streams.clone()),
+    );
+    Ok(transcript)
+}
+
+/// Records `bytes` of the stream of an `#[io(transcript)]` field, attaching it
+/// to the transcript at `path` if it is not yet attached. The error names the
+/// path, as it is passed to the `#[io(on_error)]` hook.
+pub fn record_transcript<P: AsRef<Path>>(
+    stream: &TranscriptStream,
+    path: impl FnOnce() -> P,
+    direction: Direction,
+    bytes: &[u8],
+) -> io::Result<()> {
+    if stream.flow.get().is_none() {
+        let path = path();
+        let path = path.as_ref();
+        let transcript = transcript(path).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("failed to record transcript {path:?}: {err}"),
+            )
+        })?;
+        // If another thread attached the stream first, this stream id is unused
+        if let Some(flow) = transcript.stream().flow.into_inner() {
+            _ = stream.flow.set(flow);
+        }
+    }
+    stream.record(direction, bytes)
+}
+
+/// Reports an error of [`record_transcript`] without an `#[io(on_error)]`
+/// hook, as a `tracing` event if the `tracing` feature is enabled.
+pub fn transcript_error(err: &io::Error) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(error = %err, "derive-io transcript error");
+    #[cfg(not(feature = "tracing"))]
+    let _ = err;
+}
@@ -0,0 +1,205 @@
+//! Mock peripherals that implement the `embedded-io` traits directly.
+
+use core::fmt;
+
+use embedded_io::{BufRead, ErrorKind, ErrorType, Read, Seek, SeekFrom, Write};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct UartError;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SpiError;
+
+impl fmt::Display for UartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("UART overrun")
+    }
+}
+
+impl fmt::Display for SpiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SPI bus fault")
+    }
+}
+
+impl core::error::Error for UartError {}
+impl core::error::Error for SpiError {}
+
+impl embedded_io::Error for UartError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::OutOfMemory
+    }
+}
+
+impl embedded_io::Error for SpiError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+/// A UART in loopback mode: bytes written are read back, and writing more
+/// than the FIFO holds overruns it.
+#[derive(Default)]
+pub struct Uart {
+    fifo: [u8; 16],
+    len: usize,
+}
+
+impl ErrorType for Uart {
+    type Error = UartError;
+}
+
+impl Read for Uart {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, UartError> {
+        let n = buf.len().min(self.len);
+        buf[..n].copy_from_slice(&self.fifo[..n]);
+        self.fifo.copy_within(n..self.len, 0);
+        self.len -= n;
+        Ok(n)
+    }
+}
+
+impl Write for Uart {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, UartError> {
+        if self.len + buf.len() > self.fifo.len() {
+            return Err(UartError);
+        }
+        self.fifo[self.len..self.len + buf.len()].copy_from_slice(buf);
+        self.len += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), UartError> {
+        Ok(())
+    }
+}
+
+impl embedded_io_async::Read for Uart {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, UartError> {
+        Read::read(self, buf)
+    }
+}
+
+impl embedded_io_async::Write for Uart {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, UartError> {
+        Write::write(self, buf)
+    }
+
+    async fn flush(&mut self) -> Result<(), UartError> {
+        Write::flush(self)
+    }
+}
+
+/// An SPI device that reads back `0xff` and fails on writes.
+pub struct Spi;
+
+impl ErrorType for Spi {
+    type Error = SpiError;
+}
+
+impl Read for Spi {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, SpiError> {
+        buf.fill(0xff);
+        Ok(buf.len())
+    }
+}
+
+impl Write for Spi {
+    fn write(&mut self, _buf: &[u8]) -> Result<usize, SpiError> {
+        Err(SpiError)
+    }
+
+    fn flush(&mut self) -> Result<(), SpiError> {
+        Ok(())
+    }
+}
+
+impl embedded_io_async::Read for Spi {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, SpiError> {
+        Read::read(self, buf)
+    }
+}
+
+impl embedded_io_async::Write for Spi {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, SpiError> {
+        Write::write(self, buf)
+    }
+
+    async fn flush(&mut self) -> Result<(), SpiError> {
+        Write::flush(self)
+    }
+}
+
+/// Memory-mapped flash, which reads and seeks through a fixed image.
+pub struct Flash {
+    image: &'static [u8],
+    pos: usize,
+}
+
+impl Flash {
+    pub fn new(image: &'static [u8]) -> Self {
+        Self { image, pos: 0 }
+    }
+}
+
+impl ErrorType for Flash {
+    type Error = ErrorKind;
+}
+
+impl Read for Flash {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, ErrorKind> {
+        let n = BufRead::fill_buf(self)?.len().min(buf.len());
+        buf[..n].copy_from_slice(&self.image[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl BufRead for Flash {
+    fn fill_buf(&mut self) -> Result<&[u8], ErrorKind> {
+        Ok(&self.image[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt;
+    }
+}
+
+impl Seek for Flash {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, ErrorKind> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(offset) => (self.image.len() as u64).checked_add_signed(offset),
+            SeekFrom::Current(offset) => (self.pos as u64).checked_add_signed(offset),
+        };
+        match pos {
+            Some(pos) if pos <= self.image.len() as u64 => {
+                self.pos = pos as usize;
+                Ok(pos)
+            }
+            _ => Err(ErrorKind::InvalidInput),
+        }
+    }
+}
+
+impl embedded_io_async::Read for Flash {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ErrorKind> {
+        Read::read(self, buf)
+    }
+}
+
+impl embedded_io_async::BufRead for Flash {
+    async fn fill_buf(&mut self) -> Result<&[u8], ErrorKind> {
+        BufRead::fill_buf(self)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        BufRead::consume(self, amt)
+    }
+}
+
+impl embedded_io_async::Seek for Flash {
+    async fn seek(&mut self, pos: SeekFrom) -> Result<u64, ErrorKind> {
+        Seek::seek(self, pos)
+    }
+}
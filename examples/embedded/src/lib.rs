@@ -0,0 +1,149 @@
+//! `embedded-io` examples, demonstrating the derives in a `no_std` crate.
+#![no_std]
+
+mod devices;
+
+use core::fmt;
+use core::future::Future;
+use core::pin::pin;
+use core::task::{Context, Poll, Waker};
+
+use derive_io::{
+    EmbeddedAsyncBufRead, EmbeddedAsyncRead, EmbeddedAsyncSeek, EmbeddedAsyncWrite,
+    EmbeddedBufRead, EmbeddedErrorType, EmbeddedRead, EmbeddedSeek, EmbeddedWrite,
+};
+use embedded_io::{BufRead, ErrorKind, ErrorType, Read, Seek, SeekFrom, Write};
+
+use crate::devices::{Flash, Spi, SpiError, Uart, UartError};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BusError {
+    Uart(UartError),
+    Spi(SpiError),
+}
+
+impl From<UartError> for BusError {
+    fn from(err: UartError) -> Self {
+        Self::Uart(err)
+    }
+}
+
+impl From<SpiError> for BusError {
+    fn from(err: SpiError) -> Self {
+        Self::Spi(err)
+    }
+}
+
+impl fmt::Display for BusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Uart(err) => err.fmt(f),
+            Self::Spi(err) => err.fmt(f),
+        }
+    }
+}
+
+impl core::error::Error for BusError {}
+
+impl embedded_io::Error for BusError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Uart(err) => embedded_io::Error::kind(err),
+            Self::Spi(err) => embedded_io::Error::kind(err),
+        }
+    }
+}
+
+/// [`Bus`] - Tests an enum over peripherals with different error types, which
+/// are unified with `#[io(error)]`.
+#[derive(EmbeddedErrorType, EmbeddedRead, EmbeddedWrite, EmbeddedAsyncRead, EmbeddedAsyncWrite)]
+#[io(error = BusError)]
+pub enum Bus {
+    Uart(
+        #[read]
+        #[write]
+        Uart,
+    ),
+    Spi(
+        #[read]
+        #[write]
+        Spi,
+    ),
+}
+
+/// [`Storage`] - Tests a generic struct that takes its error type from the
+/// field, with buffered reads and seeking.
+#[derive(
+    EmbeddedErrorType,
+    EmbeddedRead,
+    EmbeddedBufRead,
+    EmbeddedSeek,
+    EmbeddedAsyncRead,
+    EmbeddedAsyncBufRead,
+    EmbeddedAsyncSeek,
+)]
+pub struct Storage<F> {
+    #[read]
+    #[seek]
+    flash: F,
+}
+
+/// Polls a future that never waits to completion.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    match future
+        .as_mut()
+        .poll(&mut Context::from_waker(Waker::noop()))
+    {
+        Poll::Ready(output) => output,
+        Poll::Pending => unreachable!("the mock peripherals never wait"),
+    }
+}
+
+pub fn run() {
+    let mut bus = Bus::Uart(Uart::default());
+    bus.write_all(b"ping").unwrap();
+    let mut buf = [0; 4];
+    bus.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"ping");
+    assert_eq!(bus.write_all(&[0; 32]), Err(BusError::Uart(UartError)));
+
+    let mut bus = Bus::Spi(Spi);
+    bus.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, [0xff; 4]);
+    assert_eq!(bus.write(b"ping"), Err(BusError::Spi(SpiError)));
+
+    let mut bus = Bus::Uart(Uart::default());
+    block_on(async {
+        use embedded_io_async::{Read, Write};
+        Write::write_all(&mut bus, b"pong").await.unwrap();
+        Read::read_exact(&mut bus, &mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+    });
+
+    let mut storage = Storage {
+        flash: Flash::new(b"header\npayload"),
+    };
+    let _: <Storage<Flash> as ErrorType>::Error = ErrorKind::Other;
+    assert_eq!(storage.fill_buf().unwrap(), b"header\npayload");
+    storage.consume(7);
+    storage.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"payl");
+    assert_eq!(storage.stream_position(), Ok(11));
+    storage.rewind().unwrap();
+    assert_eq!(storage.seek(SeekFrom::End(-4)), Ok(10));
+    assert_eq!(storage.seek_relative(-20), Err(ErrorKind::InvalidInput));
+
+    block_on(async {
+        use embedded_io_async::{BufRead, Read, Seek};
+        Seek::seek(&mut storage, SeekFrom::Start(0)).await.unwrap();
+        assert_eq!(
+            BufRead::fill_buf(&mut storage).await.unwrap(),
+            b"header\npayload"
+        );
+        BufRead::consume(&mut storage, 7);
+        Read::read_exact(&mut storage, &mut buf).await.unwrap();
+        assert_eq!(&buf, b"payl");
+        assert_eq!(Seek::stream_position(&mut storage).await, Ok(11));
+    });
+}
@@ -0,0 +1,3 @@
+pub fn main() {
+    embedded_example::run()
+}
@@ -0,0 +1,144 @@
+//! hyper examples, serving HTTP/1.1 over derived `hyper::rt::{Read, Write}`
+//! connections without wrapping them in `TokioIo`.
+
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bytes::Bytes;
+use derive_io::{HyperRead, HyperWrite, PinProject};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::client::conn::http1 as client;
+use hyper::server::conn::http1 as server;
+use hyper::service::service_fn;
+use hyper::{Request, Response};
+use hyper_util::rt::TokioIo;
+use tokio::net::{TcpListener, TcpStream};
+
+/// [`Connection`] - Tests an enum of connections that hyper serves directly,
+/// so the handler can still see which kind of connection it is.
+#[derive(HyperRead, HyperWrite)]
+pub enum Connection {
+    Tcp(
+        #[read]
+        #[write]
+        TokioIo<TcpStream>,
+    ),
+    #[cfg(unix)]
+    Unix(
+        #[read]
+        #[write]
+        TokioIo<tokio::net::UnixStream>,
+    ),
+}
+
+impl Connection {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Connection::Tcp(_) => "tcp",
+            #[cfg(unix)]
+            Connection::Unix(_) => "unix",
+        }
+    }
+}
+
+/// [`CountingConnection`] - Tests a structurally pinned, generic connection
+/// that counts the bytes written with a hook.
+#[derive(HyperRead, HyperWrite, PinProject)]
+pub struct CountingConnection<S> {
+    #[pin]
+    #[read]
+    #[write(inspect = Self::count)]
+    pub stream: S,
+    pub written: AtomicUsize,
+}
+
+impl<S> CountingConnection<S> {
+    fn count(&self, bytes: &[u8]) {
+        self.written.fetch_add(bytes.len(), Ordering::Relaxed);
+    }
+}
+
+async fn hello(
+    kind: &'static str,
+    request: Request<Incoming>,
+) -> Result<Response<Full<Bytes>>, Infallible> {
+    let body = format!("{} {} over {kind}", request.method(), request.uri().path());
+    Ok(Response::new(Full::new(Bytes::from(body))))
+}
+
+/// Serves one HTTP/1.1 connection, answering each request with its method,
+/// path and the kind of the connection.
+async fn serve(connection: Connection) {
+    let kind = connection.kind();
+    server::Builder::new()
+        .serve_connection(connection, service_fn(move |request| hello(kind, request)))
+        .await
+        .unwrap();
+}
+
+/// Sends a `GET` request for `path` and returns the response body.
+async fn get<T>(connection: T, path: &str) -> String
+where
+    T: hyper::rt::Read + hyper::rt::Write + Send + Unpin + 'static,
+{
+    let (mut sender, conn) = client::handshake(connection).await.unwrap();
+    tokio::spawn(conn);
+    let request = Request::get(path)
+        .header("host", "localhost")
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    let response = sender.send_request(request).await.unwrap();
+    assert!(response.status().is_success());
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[tokio::main]
+pub async fn run() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            tokio::spawn(serve(Connection::Tcp(TokioIo::new(socket))));
+        }
+    });
+
+    eprint!("test Connection::Tcp ... ");
+    let stream = TcpStream::connect(address).await.unwrap();
+    let body = get(Connection::Tcp(TokioIo::new(stream)), "/tcp").await;
+    assert_eq!(body, "GET /tcp over tcp");
+    eprintln!(" OK");
+
+    #[cfg(unix)]
+    {
+        eprint!("test Connection::Unix ... ");
+        let (client, server) = tokio::net::UnixStream::pair().unwrap();
+        tokio::spawn(serve(Connection::Unix(TokioIo::new(server))));
+        let body = get(Connection::Unix(TokioIo::new(client)), "/unix").await;
+        assert_eq!(body, "GET /unix over unix");
+        eprintln!(" OK");
+    }
+
+    eprint!("test CountingConnection ... ");
+    let stream = TcpStream::connect(address).await.unwrap();
+    let connection = Box::pin(CountingConnection {
+        stream: TokioIo::new(stream),
+        written: AtomicUsize::new(0),
+    });
+    let (mut sender, conn) = client::handshake(connection).await.unwrap();
+    // Keep the connection, to check the count once the exchange is complete
+    let conn = tokio::spawn(conn.without_shutdown());
+    let request = Request::get("/counted")
+        .header("host", "localhost")
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    let response = sender.send_request(request).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body, "GET /counted over tcp");
+    drop(sender);
+    let parts = conn.await.unwrap().unwrap();
+    assert!(parts.io.written.load(Ordering::Relaxed) > 0);
+    eprintln!(" OK");
+}
@@ -0,0 +1,3 @@
+pub fn main() {
+    hyper_example::run()
+}
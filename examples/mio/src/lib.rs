@@ -0,0 +1,116 @@
+//! mio examples, registering derived event sources with a `mio::Poll`.
+
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream as StdUnixStream;
+use std::time::Duration;
+
+use derive_io::{AsFileDescriptor, MioSource};
+use mio::event::Source;
+use mio::net::{TcpListener, TcpStream, UnixStream};
+use mio::{Events, Interest, Poll, Token};
+
+/// [`Connection`] - Tests an enum of mio sources, including a blocking std
+/// stream that is registered by its raw descriptor.
+#[derive(MioSource, AsFileDescriptor)]
+pub enum Connection {
+    Tcp(#[descriptor] TcpStream),
+    Unix(#[descriptor] UnixStream),
+    Std(#[descriptor(source_fd)] StdUnixStream),
+}
+
+/// [`Registered`] - Tests a generic source that carries its token.
+#[derive(MioSource)]
+pub struct Registered<S> {
+    #[descriptor]
+    pub source: S,
+    pub token: Token,
+}
+
+/// Waits for a readable event for `token`.
+fn wait_readable(poll: &mut Poll, token: Token) {
+    let mut events = Events::with_capacity(8);
+    for _ in 0..100 {
+        poll.poll(&mut events, Some(Duration::from_millis(100)))
+            .unwrap();
+        if events
+            .iter()
+            .any(|event| event.token() == token && event.is_readable())
+        {
+            return;
+        }
+    }
+    panic!("no readable event for {token:?}");
+}
+
+/// Registers `connection`, makes `peer` write to it and checks that it
+/// becomes readable, then deregisters it.
+fn check(poll: &mut Poll, name: &str, mut connection: Connection, mut peer: impl Write) {
+    eprint!("test {name} ... ");
+    let token = Token(1);
+    connection
+        .register(poll.registry(), token, Interest::READABLE)
+        .unwrap();
+    peer.write_all(b"ping").unwrap();
+    wait_readable(poll, token);
+    connection
+        .reregister(
+            poll.registry(),
+            token,
+            Interest::READABLE | Interest::WRITABLE,
+        )
+        .unwrap();
+    connection.deregister(poll.registry()).unwrap();
+    eprintln!(" OK");
+}
+
+pub fn run() {
+    let mut poll = Poll::new().unwrap();
+
+    let mut listener = TcpListener::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let address = listener.local_addr().unwrap();
+    poll.registry()
+        .register(&mut listener, Token(0), Interest::READABLE)
+        .unwrap();
+    let client = std::net::TcpStream::connect(address).unwrap();
+    wait_readable(&mut poll, Token(0));
+    let (server, _) = listener.accept().unwrap();
+    check(
+        &mut poll,
+        "Connection::Tcp",
+        Connection::Tcp(server),
+        client,
+    );
+
+    let (a, b) = UnixStream::pair().unwrap();
+    check(
+        &mut poll,
+        "Connection::Unix",
+        Connection::Unix(a),
+        StdUnixStream::from(b),
+    );
+
+    let (a, b) = StdUnixStream::pair().unwrap();
+    check(&mut poll, "Connection::Std", Connection::Std(a), b);
+
+    eprint!("test Registered ... ");
+    let (a, mut b) = StdUnixStream::pair().unwrap();
+    a.set_nonblocking(true).unwrap();
+    let mut registered = Registered {
+        source: Connection::Unix(UnixStream::from_std(a)),
+        token: Token(2),
+    };
+    let token = registered.token;
+    registered
+        .register(poll.registry(), token, Interest::READABLE)
+        .unwrap();
+    b.write_all(b"ping").unwrap();
+    wait_readable(&mut poll, token);
+    let Connection::Unix(stream) = &mut registered.source else {
+        unreachable!();
+    };
+    let mut buf = [0; 4];
+    stream.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"ping");
+    registered.deregister(poll.registry()).unwrap();
+    eprintln!(" OK");
+}
@@ -0,0 +1,181 @@
+//! monoio examples, demonstrating the completion-based derives. The runtime
+//! uses io_uring where the kernel supports it, and epoll otherwise.
+
+use std::cell::{Cell, RefCell};
+use std::io;
+
+use derive_io::{AsyncReadRent, AsyncWriteRent, ErrorContext};
+use monoio::buf::IoBuf;
+use monoio::io::{AsyncReadRent, AsyncReadRentExt, AsyncWriteRent, AsyncWriteRentExt};
+use monoio::net::{TcpListener, TcpStream, UnixStream};
+
+/// [`Connection`] - Tests an enum of connections, with the failing variant
+/// and field named in errors.
+#[derive(AsyncReadRent, AsyncWriteRent)]
+#[io(context)]
+pub enum Connection {
+    Tcp(
+        #[read]
+        #[write]
+        TcpStream,
+    ),
+    Unix(
+        #[read]
+        #[write]
+        UnixStream,
+    ),
+}
+
+/// [`CountingStream`] - Tests a generic struct that counts the bytes written
+/// and records errors with hooks.
+#[derive(AsyncReadRent, AsyncWriteRent)]
+#[io(on_error = Self::failed)]
+pub struct CountingStream<S> {
+    #[read]
+    #[write(inspect = Self::count)]
+    pub stream: S,
+    pub written: RefCell<Vec<u8>>,
+    pub errors: Cell<usize>,
+}
+
+impl<S> CountingStream<S> {
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            written: RefCell::default(),
+            errors: Cell::default(),
+        }
+    }
+
+    fn count(&self, bytes: &[u8]) {
+        self.written.borrow_mut().extend_from_slice(bytes);
+    }
+
+    fn failed(&self, _: &io::Error) {
+        self.errors.set(self.errors.get() + 1);
+    }
+}
+
+/// [`Unsupported`] - A stream that fails every call, to test error hooks.
+pub struct Unsupported;
+
+fn unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "unsupported")
+}
+
+impl AsyncReadRent for Unsupported {
+    async fn read<T: monoio::buf::IoBufMut>(&mut self, buf: T) -> monoio::BufResult<usize, T> {
+        (Err(unsupported()), buf)
+    }
+
+    async fn readv<T: monoio::buf::IoVecBufMut>(&mut self, buf: T) -> monoio::BufResult<usize, T> {
+        (Err(unsupported()), buf)
+    }
+}
+
+impl AsyncWriteRent for Unsupported {
+    async fn write<T: monoio::buf::IoBuf>(&mut self, buf: T) -> monoio::BufResult<usize, T> {
+        (Err(unsupported()), buf)
+    }
+
+    async fn writev<T: monoio::buf::IoVecBuf>(&mut self, buf: T) -> monoio::BufResult<usize, T> {
+        (Err(unsupported()), buf)
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        Err(unsupported())
+    }
+}
+
+/// Writes `message` and reads back the echoed reply.
+async fn echo(stream: &mut (impl AsyncReadRent + AsyncWriteRent), message: &str) -> String {
+    let (result, _) = stream.write_all(message.as_bytes().to_vec()).await;
+    result.unwrap();
+    stream.flush().await.unwrap();
+    let (result, buf) = stream.read_exact(vec![0; message.len()]).await;
+    result.unwrap();
+    String::from_utf8(buf).unwrap()
+}
+
+/// Echoes everything read from `stream` until it is closed.
+async fn serve(mut stream: impl AsyncReadRent + AsyncWriteRent) {
+    let mut buf = vec![0; 1024];
+    loop {
+        let (result, read) = stream.read(buf).await;
+        match result {
+            Ok(0) | Err(_) => return,
+            Ok(n) => {
+                let (result, written) = stream.write_all(read.slice(..n)).await;
+                if result.is_err() {
+                    return;
+                }
+                buf = written.into_inner();
+            }
+        }
+    }
+}
+
+pub fn run() {
+    let mut runtime = monoio::RuntimeBuilder::<monoio::FusionDriver>::new()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        monoio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                monoio::spawn(serve(Connection::Tcp(stream)));
+            }
+        });
+
+        eprint!("test Connection::Tcp ... ");
+        let mut tcp = Connection::Tcp(TcpStream::connect(address).await.unwrap());
+        assert_eq!(echo(&mut tcp, "hello tcp").await, "hello tcp");
+        tcp.shutdown().await.unwrap();
+        eprintln!(" OK");
+
+        eprint!("test Connection::Unix ... ");
+        let (client, server) = UnixStream::pair().unwrap();
+        monoio::spawn(serve(Connection::Unix(server)));
+        let mut unix = Connection::Unix(client);
+        assert_eq!(echo(&mut unix, "hello unix").await, "hello unix");
+        eprintln!(" OK");
+
+        eprint!("test Connection context ... ");
+        // Writing after a shutdown fails
+        unix.shutdown().await.unwrap();
+        let (result, _) = unix.write(b"closed".to_vec()).await;
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        let context = err
+            .get_ref()
+            .unwrap()
+            .downcast_ref::<ErrorContext>()
+            .unwrap();
+        assert_eq!(context.variant(), Some("Unix"));
+        assert_eq!(context.field(), Some("0"));
+        eprintln!(" OK");
+
+        eprint!("test CountingStream ... ");
+        let stream = TcpStream::connect(address).await.unwrap();
+        let mut counting = CountingStream::new(stream);
+        assert_eq!(echo(&mut counting, "counted").await, "counted");
+        assert_eq!(counting.written.borrow().as_slice(), b"counted");
+        assert_eq!(counting.errors.get(), 0);
+        eprintln!(" OK");
+
+        eprint!("test CountingStream errors ... ");
+        let mut failing = CountingStream::new(Unsupported);
+        let (result, _) = failing.write(b"lost".to_vec()).await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Unsupported);
+        assert!(failing.flush().await.is_err());
+        assert!(failing.written.borrow().is_empty());
+        assert_eq!(failing.errors.get(), 2);
+        eprintln!(" OK");
+    });
+}
@@ -0,0 +1,3 @@
+pub fn main() {
+    monoio_example::run()
+}
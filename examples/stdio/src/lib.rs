@@ -1,14 +1,22 @@
+use std::cell::Cell;
 use std::io::{BufRead, BufReader};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use derive_io::{AsFileDescriptor, BufRead, Read, Write};
+use derive_io::{
+    AsFileDescriptor, AsReadWriteFds, AsSocketDescriptor, BufRead, ErrorContext, FileExt, FmtWrite,
+    FromDescriptor, IntoFileDescriptor, PinProject, PositionalIo, Read, ReadBuffer, SocketAddrs,
+    SocketControl, TranscriptStream, Write, WriteBuffer,
+};
 
 /// [`StdioStreams`] - Tests structs with separate read and write stream halves.
-#[derive(Read, Write, AsFileDescriptor)]
+#[derive(Read, Write, AsFileDescriptor, AsReadWriteFds)]
 struct StdioStreams {
     #[read]
-    #[descriptor]
+    #[descriptor(read)]
     stdin: std::io::Stdin,
     #[write]
+    #[descriptor(write)]
     stdout: std::io::Stdout,
 }
 
@@ -34,6 +42,542 @@ struct GenericBufRead<S>(
     S,
 );
 
+/// [`DuckFile`] - Tests duck typing for the sync traits, with a mix of
+/// receivers.
+#[derive(Read, BufRead, Write, AsFileDescriptor)]
+#[duck(
+    read, fill_buf, consume, read_until, skip_until, read_line, write, flush, as_fd
+)]
+#[read(duck)]
+#[write(duck)]
+#[descriptor(duck)]
+struct DuckFile {
+    file: BufReader<std::fs::File>,
+    written: usize,
+}
+
+#[deny(unused)]
+impl DuckFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        std::io::Read::read(&mut self.file, buf)
+    }
+
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.file.fill_buf()
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().file.consume(amt)
+    }
+
+    fn read_until(&mut self, byte: u8, buf: &mut Vec<u8>) -> std::io::Result<usize> {
+        self.file.read_until(byte, buf)
+    }
+
+    fn skip_until(&mut self, byte: u8) -> std::io::Result<usize> {
+        self.file.skip_until(byte)
+    }
+
+    fn read_line(&mut self, buf: &mut String) -> std::io::Result<usize> {
+        self.file.read_line(buf)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.written += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(self: Pin<&mut Self>) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn as_fd(self: Pin<&Self>) -> std::os::fd::BorrowedFd<'_> {
+        use std::os::fd::AsFd;
+        self.get_ref().file.get_ref().as_fd()
+    }
+
+    #[cfg(windows)]
+    fn as_handle(&self) -> std::os::windows::io::BorrowedHandle<'_> {
+        use std::os::windows::io::AsHandle;
+        self.file.get_ref().as_handle()
+    }
+}
+
+/// [`BufferedFile`] - Tests buffered reads from a raw file and buffered writes
+/// to a separate sink.
+#[derive(Read, BufRead, Write, AsFileDescriptor)]
+struct BufferedFile {
+    #[read]
+    #[descriptor]
+    file: std::fs::File,
+    #[buffer]
+    read_buffer: ReadBuffer,
+    #[write(buffered)]
+    sink: Vec<u8>,
+    #[buffer(write)]
+    write_buffer: WriteBuffer,
+}
+
+/// [`FlushOnDrop`] - Tests flushing the write buffer to a borrowed sink when
+/// dropped.
+#[derive(Write)]
+struct FlushOnDrop<'a> {
+    #[write(buffered, flush_on_drop)]
+    sink: &'a mut Vec<u8>,
+    #[buffer(write)]
+    write_buffer: WriteBuffer,
+}
+
+/// [`ProjectedFlushOnDrop`] - Tests `PinProject` owning the flush on drop of a
+/// type without `#[pin]` fields.
+#[derive(Write, PinProject)]
+struct ProjectedFlushOnDrop<'a> {
+    #[write(buffered, flush_on_drop)]
+    sink: &'a mut Vec<u8>,
+    #[buffer(write)]
+    write_buffer: WriteBuffer,
+}
+
+/// [`FailingFlush`] - Tests reporting a failed flush on drop, and the
+/// `on_error` hook.
+#[derive(Write)]
+#[write(duck, flush_on_drop = report_flush_error)]
+#[io(on_error = FailingFlush::on_error)]
+struct FailingFlush;
+
+impl FailingFlush {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Err(std::io::Error::other("flush failed"))
+    }
+}
+
+impl FailingFlush {
+    fn on_error(&self, _error: &std::io::Error) {
+        HOOK_ERRORS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// [`CountingReader`] - Tests the `inspect` hook.
+#[derive(Read, BufRead)]
+struct CountingReader {
+    #[read(inspect = CountingReader::on_read)]
+    file: BufReader<std::fs::File>,
+    read: Cell<Vec<u8>>,
+}
+
+impl CountingReader {
+    fn on_read(&self, bytes: &[u8]) {
+        let mut read = self.read.take();
+        read.extend_from_slice(bytes);
+        self.read.set(read);
+    }
+}
+
+/// [`ContextStreams`] - Tests error mapping and error context.
+#[derive(Read, Write)]
+#[io(context)]
+#[allow(unused)]
+enum ContextStreams {
+    File(
+        #[read]
+        #[write(map_err = ContextStreams::map_write_err)]
+        std::fs::File,
+    ),
+    Stdio {
+        #[read]
+        stdin: std::io::Stdin,
+        #[write]
+        stdout: std::io::Stdout,
+    },
+}
+
+impl ContextStreams {
+    fn map_write_err(err: std::io::Error) -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::PermissionDenied, err)
+    }
+}
+
+/// [`Transcribed`] - Tests recording a transcript of the bytes read and
+/// written, with a stream id per value.
+#[derive(Read, Write)]
+#[io(transcript = transcript_path())]
+struct Transcribed {
+    #[read]
+    #[write]
+    cursor: std::io::Cursor<Vec<u8>>,
+    #[io(transcript)]
+    transcript: TranscriptStream,
+}
+
+fn transcript_path() -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("derive-io-stdio-{}.transcript", std::process::id()))
+}
+
+/// [`LateTranscribed`] - Tests reporting transcript errors to the `on_error`
+/// hook, and creating the transcript once its directory exists.
+#[derive(Write)]
+#[io(transcript = late_transcript_dir().join("transcript"))]
+#[io(on_error = LateTranscribed::on_error)]
+struct LateTranscribed {
+    #[write]
+    sink: Vec<u8>,
+    #[io(transcript)]
+    transcript: TranscriptStream,
+    errors: Cell<usize>,
+}
+
+impl LateTranscribed {
+    fn on_error(&self, _error: &std::io::Error) {
+        self.errors.set(self.errors.get() + 1);
+    }
+}
+
+fn late_transcript_dir() -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("derive-io-stdio-{}", std::process::id()))
+}
+
+/// [`TextWriter`] - Tests `fmt::Write` delegation, both to `fmt::Write` fields
+/// and to `io::Write` fields that store the last `io::Error`.
+#[derive(FmtWrite)]
+enum TextWriter {
+    String(#[fmt_write] String),
+    Io {
+        #[fmt_write(from_io = last_error)]
+        writer: std::fs::File,
+        last_error: Option<std::io::Error>,
+    },
+}
+
+/// [`TextBuffer`] - Tests `fmt::Write` for a type that implements `io::Write`
+/// itself.
+#[derive(Write, FmtWrite)]
+#[fmt_write(from_io)]
+struct TextBuffer {
+    #[write]
+    buffer: Vec<u8>,
+}
+
+/// [`PageStore`] - Tests positional I/O over either a file or an in-memory
+/// store.
+#[derive(PositionalIo)]
+#[io(context)]
+enum PageStore {
+    File(#[positional] std::fs::File),
+    Memory(#[positional] Vec<u8>),
+}
+
+/// [`SharedFile`] - Tests `FileExt` delegation through a `deref` field, with an
+/// `inspect` hook.
+#[cfg(unix)]
+#[derive(FileExt)]
+struct SharedFile {
+    #[positional(deref, inspect = SharedFile::count)]
+    file: std::sync::Arc<std::fs::File>,
+    transferred: Cell<usize>,
+}
+
+#[cfg(unix)]
+impl SharedFile {
+    fn count(&self, bytes: &[u8]) {
+        self.transferred.set(self.transferred.get() + bytes.len());
+    }
+}
+
+/// [`OwnedStreams`] - Tests moving the descriptor out of an enum, dropping the
+/// other fields.
+#[cfg(unix)]
+#[derive(IntoFileDescriptor)]
+enum OwnedStreams {
+    File {
+        #[descriptor]
+        file: std::fs::File,
+        #[allow(unused)]
+        path: std::path::PathBuf,
+    },
+    #[allow(unused)]
+    Unix(#[descriptor] std::os::unix::net::UnixStream),
+}
+
+/// [`FileHandle`] - Tests conversions to and from a descriptor for a
+/// single-field wrapper.
+#[cfg(unix)]
+#[derive(IntoFileDescriptor, AsFileDescriptor)]
+struct FileHandle(#[descriptor(from)] std::fs::File);
+
+/// [`ChildInput`] - Tests a single-field wrapper without `#[descriptor(from)]`,
+/// whose field cannot be built from a descriptor.
+#[cfg(unix)]
+#[derive(IntoFileDescriptor)]
+struct ChildInput(#[descriptor] std::process::ChildStdin);
+
+/// [`Inherited`] - Tests choosing the variant for an inherited descriptor by
+/// its kind.
+#[cfg(unix)]
+#[derive(FromDescriptor, Read, Debug)]
+enum Inherited {
+    #[descriptor(kind = unix_stream)]
+    Unix(#[read] std::os::unix::net::UnixStream),
+    #[descriptor(kind = file | pipe)]
+    File(#[read] std::fs::File),
+}
+
+/// [`ForeignHandle`] - A stand-in for a C library handle that only exposes its
+/// descriptor as an integer.
+#[cfg(unix)]
+struct ForeignHandle(std::fs::File);
+
+#[cfg(unix)]
+impl ForeignHandle {
+    fn fd(&self) -> std::os::raw::c_int {
+        std::os::fd::AsRawFd::as_raw_fd(&self.0)
+    }
+}
+
+/// [`RawStreams`] - Tests delegating to raw descriptors that do not implement
+/// `AsFd`.
+#[cfg(unix)]
+#[derive(AsFileDescriptor)]
+enum RawStreams {
+    Raw(#[descriptor(unsafe(raw))] std::os::fd::RawFd),
+    Foreign(#[descriptor(unsafe(raw_via = fd))] ForeignHandle),
+}
+
+/// [`MaybeRawStream`] - Tests that a raw descriptor of `-1` is absent, where
+/// `AsFd` panics.
+#[cfg(unix)]
+#[derive(derive_io::MaybeAsFd)]
+struct MaybeRawStream(#[descriptor(unsafe(raw))] std::os::fd::RawFd);
+
+/// [`SocketStreams`] - Tests converting the addresses of each transport into a
+/// `SocketAddrAny`, and forwarding the socket operations that each supports.
+#[derive(SocketAddrs, SocketControl)]
+enum SocketStreams {
+    Tcp(#[descriptor] std::net::TcpStream),
+    Udp(#[descriptor(duck)] std::net::UdpSocket),
+    #[cfg(unix)]
+    Unix(#[descriptor] std::os::unix::net::UnixStream),
+}
+
+/// [`TaggedSocket`] - Tests that a socket with other fields cannot be cloned,
+/// and setting other options with `socket2`.
+#[derive(SocketControl, AsSocketDescriptor)]
+struct TaggedSocket {
+    #[descriptor]
+    socket: std::net::UdpSocket,
+    #[allow(unused)]
+    tag: &'static str,
+}
+
+static HOOK_ERRORS: AtomicUsize = AtomicUsize::new(0);
+static FLUSH_ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+fn report_flush_error(err: std::io::Error) {
+    assert_eq!(err.to_string(), "flush failed");
+    FLUSH_ERRORS.fetch_add(1, Ordering::SeqCst);
+}
+
+// `io::Write` is not in scope here, as `TextBuffer` implements both traits.
+fn check_fmt_write() {
+    use std::fmt::Write;
+
+    let mut text = TextWriter::String(String::new());
+    write!(text, "{}-{}", 1, 2).unwrap();
+    text.write_char('!').unwrap();
+    text.write_str("?").unwrap();
+    let TextWriter::String(string) = &text else {
+        unreachable!();
+    };
+    assert_eq!(string, "1-2!?");
+
+    // Writing to a file opened for reading fails
+    let mut text = TextWriter::Io {
+        writer: std::fs::File::open("Cargo.toml").unwrap(),
+        last_error: None,
+    };
+    assert_eq!(write!(text, "{}", 1), Err(std::fmt::Error));
+    let TextWriter::Io { last_error, .. } = &mut text else {
+        unreachable!();
+    };
+    assert!(last_error.take().is_some());
+    assert_eq!(text.write_char('!'), Err(std::fmt::Error));
+
+    let mut text = TextBuffer { buffer: vec![] };
+    write!(text, "{}-{}", 1, 2).unwrap();
+    text.write_char('é').unwrap();
+    assert_eq!(text.buffer, "1-2é".as_bytes());
+}
+
+fn check_positional_io() {
+    let path = std::env::temp_dir().join(format!("derive-io-stdio-{}.pages", std::process::id()));
+    let file = std::fs::File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+    let stores = [
+        PageStore::File(file.try_clone().unwrap()),
+        PageStore::Memory(vec![]),
+    ];
+    for mut store in stores {
+        let mut page = [0; 4];
+        store.write_all_at(b"page", 4096).unwrap();
+        store.read_exact_at(&mut page, 4096).unwrap();
+        assert_eq!(&page, b"page");
+        assert_eq!(store.read_at(&mut page, 1024).unwrap(), 4);
+        assert_eq!(page, [0; 4]);
+        assert_eq!(store.read_at(&mut page, 4100).unwrap(), 0);
+
+        let err = store.read_exact_at(&mut page, 4098).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        let context = err
+            .get_ref()
+            .unwrap()
+            .downcast_ref::<ErrorContext>()
+            .unwrap();
+        assert_eq!(context.ty(), "PageStore");
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileExt;
+
+        let shared = SharedFile {
+            file: std::sync::Arc::new(file),
+            transferred: Cell::new(0),
+        };
+        let mut page = [0; 4];
+        assert_eq!(shared.write_at(b"PAGE", 4096).unwrap(), 4);
+        shared.read_exact_at(&mut page, 4096).unwrap();
+        assert_eq!(&page, b"PAGE");
+        assert_eq!(shared.read_at(&mut page, 4098).unwrap(), 2);
+        // `read_exact_at` is not inspected, as it only returns `()`
+        assert_eq!(shared.transferred.get(), 6);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+fn check_socket_addrs() {
+    use derive_io::SocketAddrAny;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let tcp =
+        SocketStreams::Tcp(std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap());
+    let (_, peer) = listener.accept().unwrap();
+    assert_eq!(tcp.local_addr().unwrap(), SocketAddrAny::Inet(peer));
+    assert_eq!(
+        tcp.peer_addr().unwrap(),
+        SocketAddrAny::Inet(listener.local_addr().unwrap())
+    );
+
+    let udp = std::net::UdpSocket::bind("[::1]:0").unwrap();
+    let local = udp.local_addr().unwrap();
+    let udp = SocketStreams::Udp(udp);
+    assert_eq!(udp.local_addr().unwrap(), SocketAddrAny::Inet(local));
+    assert_eq!(
+        udp.peer_addr().unwrap_err().kind(),
+        std::io::ErrorKind::NotConnected
+    );
+
+    #[cfg(unix)]
+    {
+        let (stream, _peer) = std::os::unix::net::UnixStream::pair().unwrap();
+        let unix = SocketStreams::Unix(stream);
+        assert_eq!(unix.local_addr().unwrap(), SocketAddrAny::Unnamed);
+        assert_eq!(unix.peer_addr().unwrap(), SocketAddrAny::Unnamed);
+    }
+
+    for (addr, text) in [
+        (SocketAddrAny::Inet(local), format!("{local}")),
+        (
+            SocketAddrAny::Path("/tmp/a.sock".into()),
+            "unix:/tmp/a.sock".into(),
+        ),
+        (
+            SocketAddrAny::Abstract(br"a\b".to_vec()),
+            r"unix:@a\\b".into(),
+        ),
+        (
+            SocketAddrAny::Abstract(vec![0, b'\n', 0xff]),
+            "unix:@\\x00\\n\\xff".into(),
+        ),
+        (SocketAddrAny::Unnamed, "unix:".into()),
+    ] {
+        assert_eq!(addr.to_string(), text);
+        assert_eq!(text.parse::<SocketAddrAny>().unwrap(), addr);
+    }
+    // Relative paths are not confused with abstract names
+    let addr = SocketAddrAny::Path("@a.sock".into());
+    assert_eq!(addr.to_string(), "unix:./@a.sock");
+    assert_eq!(
+        addr.to_string().parse::<SocketAddrAny>().unwrap(),
+        SocketAddrAny::Path("./@a.sock".into())
+    );
+    "localhost:80".parse::<SocketAddrAny>().unwrap_err();
+    "unix:@\\x0".parse::<SocketAddrAny>().unwrap_err();
+}
+
+fn check_socket_control() {
+    use std::io::ErrorKind;
+    use std::time::Duration;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let tcp =
+        SocketStreams::Tcp(std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap());
+    let (mut peer, _) = listener.accept().unwrap();
+    tcp.set_nodelay(true).unwrap();
+    assert!(tcp.nodelay().unwrap());
+    tcp.set_ttl(42).unwrap();
+    tcp.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+    tcp.set_write_timeout(None).unwrap();
+    assert!(tcp.take_error().unwrap().is_none());
+
+    let clone = tcp.try_clone().unwrap();
+    assert!(matches!(clone, SocketStreams::Tcp(_)));
+    assert_eq!(clone.local_addr().unwrap(), tcp.local_addr().unwrap());
+    clone.shutdown(std::net::Shutdown::Write).unwrap();
+    assert_eq!(std::io::Read::read(&mut peer, &mut [0; 1]).unwrap(), 0);
+
+    let udp = SocketStreams::Udp(std::net::UdpSocket::bind("127.0.0.1:0").unwrap());
+    udp.set_nonblocking(true).unwrap();
+    udp.set_ttl(42).unwrap();
+    assert!(matches!(udp.try_clone().unwrap(), SocketStreams::Udp(_)));
+    let err = udp.set_nodelay(true).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+    assert_eq!(
+        err.to_string(),
+        "`set_nodelay` is not supported by this socket"
+    );
+
+    #[cfg(unix)]
+    {
+        let (stream, _peer) = std::os::unix::net::UnixStream::pair().unwrap();
+        let unix = SocketStreams::Unix(stream);
+        assert_eq!(unix.nodelay().unwrap_err().kind(), ErrorKind::Unsupported);
+        assert_eq!(unix.set_ttl(42).unwrap_err().kind(), ErrorKind::Unsupported);
+        unix.shutdown(std::net::Shutdown::Both).unwrap();
+    }
+
+    let tagged = TaggedSocket {
+        socket: std::net::UdpSocket::bind("127.0.0.1:0").unwrap(),
+        tag: "tagged",
+    };
+    tagged.set_nonblocking(true).unwrap();
+    let Err(err) = tagged.try_clone() else {
+        panic!("expected an error");
+    };
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+    // Options that are not covered by the trait are set with socket2
+    tagged.sock_ref().set_broadcast(true).unwrap();
+    assert!(tagged.sock_ref().broadcast().unwrap());
+}
+
 pub fn run() {
     use std::io::{Read, Write};
 
@@ -44,6 +588,13 @@ pub fn run() {
 
     let mut buf = [0; 1];
     _ = streams.write(&buf).unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::fd::AsRawFd;
+        assert_eq!(streams.as_raw_fd(), streams.stdin.as_raw_fd());
+        assert_eq!(streams.read_fd().as_raw_fd(), streams.stdin.as_raw_fd());
+        assert_eq!(streams.write_fd().as_raw_fd(), streams.stdout.as_raw_fd());
+    }
 
     let mut file: Generic<std::fs::File> =
         Generic::File(std::fs::File::open("Cargo.toml").unwrap());
@@ -56,4 +607,250 @@ pub fn run() {
     let mut s = String::new();
     let mut file = GenericBufRead(BufReader::new(std::fs::File::open("Cargo.toml").unwrap()));
     _ = file.read_line(&mut s).unwrap();
+
+    let mut duck = DuckFile {
+        file: BufReader::new(std::fs::File::open("Cargo.toml").unwrap()),
+        written: 0,
+    };
+    let mut line = String::new();
+    _ = duck.read_line(&mut line).unwrap();
+    assert_eq!(line, "[package]\n");
+    _ = duck.read(&mut buf).unwrap();
+    duck.consume(1);
+    duck.write_all(b"hello").unwrap();
+    duck.flush().unwrap();
+    assert_eq!(duck.written, 5);
+    #[cfg(unix)]
+    {
+        use std::os::fd::{AsFd, AsRawFd};
+        assert_eq!(duck.as_raw_fd(), duck.file.get_ref().as_raw_fd());
+        assert_eq!(duck.as_fd().as_raw_fd(), duck.file.get_ref().as_raw_fd());
+    }
+
+    let mut buffered = BufferedFile {
+        file: std::fs::File::open("Cargo.toml").unwrap(),
+        read_buffer: ReadBuffer::new(),
+        sink: vec![],
+        write_buffer: WriteBuffer::with_capacity(8),
+    };
+    let mut line = String::new();
+    _ = buffered.read_line(&mut line).unwrap();
+    assert_eq!(line, "[package]\n");
+    assert!(buffered.read_buffer.buffer().starts_with(b"name"));
+    _ = buffered.read(&mut buf).unwrap();
+    assert_eq!(&buf, b"n");
+    buffered.write_all(b"hello").unwrap();
+    assert!(buffered.sink.is_empty());
+    buffered.write_all(b", world").unwrap();
+    assert_eq!(buffered.sink, b"hello");
+    buffered.flush().unwrap();
+    assert_eq!(buffered.sink, b"hello, world");
+    #[cfg(unix)]
+    {
+        use std::os::fd::AsRawFd;
+        assert_eq!(buffered.as_raw_fd(), buffered.file.as_raw_fd());
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd};
+
+        let file = std::fs::File::open("Cargo.toml").unwrap();
+        let fd = file.as_raw_fd();
+        let owned = OwnedFd::from(OwnedStreams::File {
+            file,
+            path: "Cargo.toml".into(),
+        });
+        assert_eq!(owned.as_raw_fd(), fd);
+        let handle = FileHandle::from(owned);
+        assert_eq!(handle.as_raw_fd(), fd);
+        let fd = handle.into_raw_fd();
+        // SAFETY: the descriptor was released by `into_raw_fd`
+        let mut handle = unsafe { FileHandle::from_raw_fd(fd) };
+        assert_eq!(handle.0.read(&mut buf).unwrap(), 1);
+
+        let mut child = std::process::Command::new("cat")
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        let input = ChildInput(child.stdin.take().unwrap());
+        let mut pipe = std::fs::File::from(OwnedFd::from(input));
+        pipe.write_all(b"ping").unwrap();
+        // Closing the pipe ends the input of `cat`
+        drop(pipe);
+        assert!(child.wait().unwrap().success());
+    }
+
+    #[cfg(unix)]
+    {
+        use derive_io::{MaybeAsFd, MaybeAsRawFd};
+        use std::os::fd::{AsFd, AsRawFd};
+
+        let file = std::fs::File::open("Cargo.toml").unwrap();
+        let raw = RawStreams::Raw(file.as_raw_fd());
+        assert_eq!(raw.as_raw_fd(), file.as_raw_fd());
+        assert_eq!(raw.as_fd().as_raw_fd(), file.as_raw_fd());
+        let maybe = MaybeRawStream(file.as_raw_fd());
+        assert_eq!(maybe.try_as_raw_fd(), Some(file.as_raw_fd()));
+        assert_eq!(
+            maybe.try_as_fd().map(|fd| fd.as_raw_fd()),
+            Some(file.as_raw_fd())
+        );
+        let closed = MaybeRawStream(-1);
+        assert!(closed.try_as_fd().is_none());
+        assert!(closed.try_as_raw_fd().is_none());
+        let closed = RawStreams::Raw(-1);
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(|_| {}));
+        let panic = std::panic::catch_unwind(|| _ = closed.as_fd()).unwrap_err();
+        std::panic::set_hook(hook);
+        assert_eq!(
+            *panic.downcast_ref::<&str>().unwrap(),
+            "derive-io: the raw descriptor of field `Raw.0` is -1, which cannot be borrowed"
+        );
+        let foreign = RawStreams::Foreign(ForeignHandle(file));
+        let RawStreams::Foreign(handle) = &foreign else {
+            unreachable!()
+        };
+        assert_eq!(foreign.as_raw_fd(), handle.fd());
+        assert_eq!(foreign.as_fd().as_raw_fd(), handle.fd());
+    }
+
+    #[cfg(unix)]
+    {
+        use derive_io::DescriptorKind;
+        use std::os::fd::{AsFd, OwnedFd};
+
+        let (stream, mut peer) = std::os::unix::net::UnixStream::pair().unwrap();
+        let mut inherited = Inherited::try_from(OwnedFd::from(stream)).unwrap();
+        assert!(matches!(inherited, Inherited::Unix(_)));
+        peer.write_all(b"x").unwrap();
+        assert_eq!(inherited.read(&mut buf).unwrap(), 1);
+
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        let mut inherited = Inherited::try_from(OwnedFd::from(reader)).unwrap();
+        assert!(matches!(inherited, Inherited::File(_)));
+        writer.write_all(b"y").unwrap();
+        assert_eq!(inherited.read(&mut buf).unwrap(), 1);
+        assert_eq!(&buf, b"y");
+
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let err = Inherited::try_from(OwnedFd::from(socket)).unwrap_err();
+        assert_eq!(err.kind(), Some(DescriptorKind::Udp));
+        let err = std::io::Error::from(err);
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+
+        // A listening socket is not a stream, unlike the connections it accepts
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        assert_eq!(
+            DescriptorKind::probe(stream.as_fd()).unwrap(),
+            DescriptorKind::Tcp
+        );
+        let err = Inherited::try_from(OwnedFd::from(listener)).unwrap_err();
+        assert_eq!(err.kind(), Some(DescriptorKind::TcpListener));
+
+        let path =
+            std::env::temp_dir().join(format!("derive-io-stdio-{}.sock", std::process::id()));
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let err = Inherited::try_from(OwnedFd::from(listener)).unwrap_err();
+        assert_eq!(err.kind(), Some(DescriptorKind::UnixListener));
+    }
+
+    check_socket_addrs();
+    check_socket_control();
+
+    let mut sink = vec![];
+    let mut flush_on_drop = FlushOnDrop {
+        sink: &mut sink,
+        write_buffer: WriteBuffer::new(),
+    };
+    flush_on_drop.write_all(b"hello").unwrap();
+    assert!(flush_on_drop.sink.is_empty());
+    drop(flush_on_drop);
+    assert_eq!(sink, b"hello");
+
+    let mut sink = vec![];
+    let mut projected = ProjectedFlushOnDrop {
+        sink: &mut sink,
+        write_buffer: WriteBuffer::new(),
+    };
+    projected.write_all(b"hello").unwrap();
+    assert!(projected.sink.is_empty());
+    drop(projected);
+    assert_eq!(sink, b"hello");
+
+    let mut failing = FailingFlush;
+    Write::flush(&mut failing).unwrap_err();
+    assert_eq!(HOOK_ERRORS.load(Ordering::SeqCst), 1);
+    drop(failing);
+    assert_eq!(FLUSH_ERRORS.load(Ordering::SeqCst), 1);
+    assert_eq!(HOOK_ERRORS.load(Ordering::SeqCst), 2);
+
+    let mut counting = CountingReader {
+        file: BufReader::new(std::fs::File::open("Cargo.toml").unwrap()),
+        read: Cell::default(),
+    };
+    let mut buf = [0; 9];
+    counting.read_exact(&mut buf).unwrap();
+    assert_eq!(counting.read.take(), b"[package]");
+
+    // Writing to a file opened for reading fails
+    let mut context = ContextStreams::File(std::fs::File::open("Cargo.toml").unwrap());
+    let err = context.write(b"hello").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+    assert!(err.to_string().starts_with("ContextStreams::File.0: "));
+    let context = err
+        .get_ref()
+        .unwrap()
+        .downcast_ref::<ErrorContext>()
+        .unwrap();
+    assert_eq!(context.ty(), "ContextStreams");
+    assert_eq!(context.variant(), Some("File"));
+    assert_eq!(context.field(), Some("0"));
+
+    check_fmt_write();
+    check_positional_io();
+
+    let mut transcribed = Transcribed {
+        cursor: std::io::Cursor::new(b"pong".to_vec()),
+        transcript: TranscriptStream::new(),
+    };
+    let mut other = Transcribed {
+        cursor: std::io::Cursor::new(vec![]),
+        transcript: TranscriptStream::new(),
+    };
+    let mut buf = [0; 4];
+    transcribed.read_exact(&mut buf).unwrap();
+    other.write_all(b"ping").unwrap();
+    transcribed.write_all(b"ping").unwrap();
+    assert_eq!(transcribed.transcript.id(), Some(0));
+    assert_eq!(other.transcript.id(), Some(1));
+    let transcript = std::fs::read_to_string(transcript_path()).unwrap();
+    std::fs::remove_file(transcript_path()).unwrap();
+    let lines = transcript.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], "# derive-io transcript");
+    assert!(lines[1].starts_with("< 0 ") && lines[1].ends_with(" 4"));
+    assert_eq!(lines[2], format!("  00000000  70 6f 6e 67{:39}|pong|", ""));
+    assert!(lines[3].starts_with("> 1 ") && lines[3].ends_with(" 4"));
+    assert_eq!(lines[4], format!("  00000000  70 69 6e 67{:39}|ping|", ""));
+    assert!(lines[5].starts_with("> 0 ") && lines[5].ends_with(" 4"));
+
+    let mut late = LateTranscribed {
+        sink: vec![],
+        transcript: TranscriptStream::new(),
+        errors: Cell::new(0),
+    };
+    late.write_all(b"ping").unwrap();
+    late.write_all(b"ping").unwrap();
+    assert_eq!(late.errors.get(), 2);
+    std::fs::create_dir(late_transcript_dir()).unwrap();
+    late.write_all(b"pong").unwrap();
+    assert_eq!(late.errors.get(), 2);
+    assert_eq!(late.sink, b"pingpingpong");
+    let transcript = std::fs::read_to_string(late_transcript_dir().join("transcript")).unwrap();
+    std::fs::remove_dir_all(late_transcript_dir()).unwrap();
+    assert_eq!(transcript.lines().count(), 3);
 }
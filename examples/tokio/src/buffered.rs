@@ -0,0 +1,98 @@
+use derive_io::{AsSocketDescriptor, AsyncBufRead, AsyncRead, AsyncWrite, ReadBuffer, WriteBuffer};
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use tokio::net::TcpStream;
+
+#[cfg(unix)]
+use tokio::net::UnixStream;
+
+/// [`BufferedStreams`] - Tests buffered reads and writes over raw streams,
+/// which keep direct access to the stream and its descriptor.
+#[derive(AsyncRead, AsyncBufRead, AsyncWrite, AsSocketDescriptor)]
+#[allow(unused)]
+pub enum BufferedStreams {
+    Tcp {
+        #[read]
+        #[write(buffered)]
+        #[descriptor]
+        stream: TcpStream,
+        #[buffer]
+        read_buffer: ReadBuffer,
+        #[buffer(write)]
+        write_buffer: WriteBuffer,
+    },
+    #[cfg(unix)]
+    Unix {
+        #[read]
+        #[write(buffered)]
+        #[descriptor]
+        stream: UnixStream,
+        #[buffer]
+        read_buffer: ReadBuffer,
+        #[buffer(write)]
+        write_buffer: WriteBuffer,
+    },
+}
+
+/// [`BufferedSink`] - Tests flushing the write buffer when dropped.
+#[derive(AsyncWrite)]
+pub struct BufferedSink<'a> {
+    #[write(buffered, flush_on_drop)]
+    sink: &'a mut Vec<u8>,
+    #[buffer(write)]
+    write_buffer: WriteBuffer,
+}
+
+impl<'a> BufferedSink<'a> {
+    pub fn new(sink: &'a mut Vec<u8>) -> Self {
+        Self {
+            sink,
+            write_buffer: WriteBuffer::new(),
+        }
+    }
+}
+
+impl BufferedStreams {
+    pub fn new_tcp(stream: TcpStream) -> Self {
+        Self::Tcp {
+            stream,
+            read_buffer: ReadBuffer::new(),
+            write_buffer: WriteBuffer::new(),
+        }
+    }
+
+    pub fn write_buffer(&self) -> &WriteBuffer {
+        match self {
+            Self::Tcp { write_buffer, .. } => write_buffer,
+            #[cfg(unix)]
+            Self::Unix { write_buffer, .. } => write_buffer,
+        }
+    }
+}
+
+/// [`PendingFlush`] - Tests that a flush on drop that does not complete is
+/// reported to the error sink.
+#[derive(AsyncWrite)]
+#[write(duck, flush_on_drop = PendingFlush::report)]
+pub struct PendingFlush;
+
+/// The kind of the last error reported by [`PendingFlush`].
+pub static PENDING_FLUSH_ERROR: Mutex<Option<std::io::ErrorKind>> = Mutex::new(None);
+
+impl PendingFlush {
+    fn poll_write(&mut self, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(&mut self, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Pending
+    }
+
+    fn poll_shutdown(&mut self, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn report(err: std::io::Error) {
+        *PENDING_FLUSH_ERROR.lock().unwrap() = Some(err.kind());
+    }
+}
@@ -4,6 +4,11 @@ use std::{
     task::{Context, Poll},
 };
 
+#[cfg(unix)]
+use std::os::fd::{AsFd, BorrowedFd};
+
+#[cfg(unix)]
+use derive_io::AsFileDescriptor;
 use derive_io::{AsyncRead, AsyncWrite};
 use tokio::{
     io::{AsyncRead, AsyncWrite, ReadBuf},
@@ -16,6 +21,7 @@ use tokio::{
 /// implements poll_read and poll_write, but not as the proper trait.
 ///
 /// Duck typing supports coercing of the receiver to the correct type.
+#[cfg_attr(unix, derive(AsFileDescriptor))]
 #[derive(AsyncRead, AsyncWrite)]
 #[duck(
     poll_read,
@@ -23,10 +29,12 @@ use tokio::{
     poll_flush,
     poll_shutdown,
     poll_write_vectored,
-    is_write_vectored
+    is_write_vectored,
+    as_fd
 )]
 #[read(duck)]
 #[write(duck)]
+#[descriptor(duck)]
 pub struct DuckType {
     inner: TcpStream,
 }
@@ -68,4 +76,69 @@ impl DuckType {
     fn is_write_vectored(&self) -> bool {
         self.inner.is_write_vectored()
     }
+
+    #[cfg(unix)]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.inner.as_fd()
+    }
+}
+
+/// [`PinnedDuckType`] - Tests duck typing with pinned receivers: `self:
+/// Pin<&mut Self>` and `self: Pin<&Self>`.
+#[derive(AsyncRead, AsyncWrite)]
+#[duck(
+    poll_read,
+    poll_write,
+    poll_flush,
+    poll_shutdown,
+    poll_write_vectored,
+    is_write_vectored
+)]
+#[read(duck)]
+#[write(duck)]
+pub struct PinnedDuckType {
+    inner: TcpStream,
+}
+
+#[deny(unused)]
+impl PinnedDuckType {
+    pub fn new(inner: TcpStream) -> Self {
+        Self { inner }
+    }
+
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(self: Pin<&Self>) -> bool {
+        self.inner.is_write_vectored()
+    }
 }
@@ -0,0 +1,56 @@
+use derive_io::{PinProject, Sink, Stream};
+use tokio::net::TcpStream;
+use tokio_util::codec::{Framed, LinesCodec};
+
+#[cfg(unix)]
+use tokio::net::UnixStream;
+
+/// [`FramedStreams`] - Tests `Stream` and `Sink` delegation to framed
+/// transports.
+#[derive(Stream, Sink)]
+#[allow(unused)]
+pub enum FramedStreams {
+    Tcp(
+        #[stream]
+        #[sink]
+        Framed<TcpStream, LinesCodec>,
+    ),
+    #[cfg(unix)]
+    Unix(
+        #[stream]
+        #[sink]
+        Framed<UnixStream, LinesCodec>,
+    ),
+}
+
+/// [`FramedGeneric`] - Tests `Stream` and `Sink` with generic fields, which
+/// must agree on the `Item` and `Error` types.
+#[derive(Stream, Sink)]
+#[allow(unused)]
+pub enum FramedGeneric<S, T> {
+    S(
+        #[stream]
+        #[sink]
+        S,
+    ),
+    T(
+        #[stream]
+        #[sink]
+        T,
+    ),
+}
+
+/// [`PinnedFramed`] - Tests `Stream` and `Sink` delegation to a pinned field.
+#[derive(Stream, Sink, PinProject)]
+pub struct PinnedFramed<S> {
+    #[stream]
+    #[sink]
+    #[pin]
+    framed: S,
+}
+
+impl<S> PinnedFramed<S> {
+    pub fn new(framed: S) -> Self {
+        Self { framed }
+    }
+}
@@ -64,3 +64,28 @@ pub struct GenericUnrelated2<T> {
     #[allow(unused)]
     t: T,
 }
+
+impl<T> GenericUnrelated2<T> {
+    pub fn new(stream: TcpStream, t: T) -> Self {
+        Self { stream, t }
+    }
+}
+
+/// [`GenericConst`] - Tests generic structs with const generic parameters and defaults.
+#[derive(AsyncRead, AsyncWrite)]
+pub struct GenericConst<S, const N: usize = 16> {
+    #[read]
+    #[write]
+    stream: S,
+    #[allow(unused)]
+    header: [u8; N],
+}
+
+impl<S, const N: usize> GenericConst<S, N> {
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            header: [0; N],
+        }
+    }
+}
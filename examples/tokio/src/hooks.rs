@@ -0,0 +1,69 @@
+use std::cell::Cell;
+
+use derive_io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+
+#[derive(Default)]
+pub struct Counters {
+    pub read: Cell<usize>,
+    pub written: Cell<usize>,
+    pub pending: Cell<usize>,
+    pub errors: Cell<usize>,
+}
+
+/// [`CountingStreams`] - Tests per-connection byte, `Pending` and error
+/// counters using the I/O hooks.
+#[derive(AsyncRead, AsyncWrite)]
+#[io(on_pending = CountingStreams::on_pending, on_error = CountingStreams::on_error)]
+#[allow(unused)]
+pub enum CountingStreams {
+    Tcp {
+        #[read(inspect = CountingStreams::on_read)]
+        #[write(inspect = CountingStreams::on_write)]
+        stream: TcpStream,
+        counters: Counters,
+    },
+    Split {
+        #[read(inspect = CountingStreams::on_read)]
+        read: tokio::net::tcp::OwnedReadHalf,
+        // Writes are not counted for split streams
+        #[write]
+        write: tokio::net::tcp::OwnedWriteHalf,
+        counters: Counters,
+    },
+}
+
+impl CountingStreams {
+    pub fn new_tcp(stream: TcpStream) -> Self {
+        Self::Tcp {
+            stream,
+            counters: Counters::default(),
+        }
+    }
+
+    pub fn counters(&self) -> &Counters {
+        match self {
+            Self::Tcp { counters, .. } | Self::Split { counters, .. } => counters,
+        }
+    }
+
+    fn on_read(&self, bytes: &[u8]) {
+        let read = &self.counters().read;
+        read.set(read.get() + bytes.len());
+    }
+
+    fn on_write(&self, bytes: &[u8]) {
+        let written = &self.counters().written;
+        written.set(written.get() + bytes.len());
+    }
+
+    fn on_pending(&self) {
+        let pending = &self.counters().pending;
+        pending.set(pending.get() + 1);
+    }
+
+    fn on_error(&self, _error: &std::io::Error) {
+        let errors = &self.counters().errors;
+        errors.set(errors.get() + 1);
+    }
+}
@@ -4,32 +4,60 @@
 //! Each example demonstrates different patterns and use cases for the derive macros.
 
 mod as_ref;
+mod buffered;
 mod complex_stream;
 mod deref;
 mod duck_type;
+mod framed;
 mod generic_enums;
 mod generic_structs;
+mod hooks;
+mod listener;
+mod maybe_fd;
 mod named_structs;
+// Never constructed: the overrides always return `Pending`
+#[allow(unused)]
 mod override_example;
+mod pin_project;
+mod split;
 mod tokio_streams;
+mod traced;
+mod transcript;
 mod tuple_structs;
 
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use derive_io::TranscriptStream;
+use futures::{SinkExt, StreamExt};
+
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
+use tokio_util::codec::{Framed, LinesCodec};
 
 use as_ref::AsRefStruct;
 use named_structs::{NamedStruct, ReadWriteStruct};
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_streams::TokioStreams;
-use tuple_structs::TupleStruct;
+use tuple_structs::{TupleStruct, TupleStructPin};
 
+use crate::buffered::{BufferedSink, BufferedStreams, PendingFlush, PENDING_FLUSH_ERROR};
 use crate::complex_stream::ComplexStream;
 use crate::deref::DerefStruct;
-use crate::duck_type::DuckType;
+use crate::duck_type::{DuckType, PinnedDuckType};
+use crate::framed::{FramedGeneric, FramedStreams, PinnedFramed};
 use crate::generic_enums::EnumGeneric;
-use crate::generic_structs::{Generic, Generic2, GenericUnrelated};
+use crate::generic_structs::{
+    Generic, Generic2, GenericConst, GenericUnrelated, GenericUnrelated2,
+};
+use crate::hooks::CountingStreams;
+use crate::listener::TokioListeners;
+use crate::maybe_fd::TestableStream;
+use crate::pin_project::{NotUnpin, PinnedEnum, PinnedStream, UnpinnedDrop};
+use crate::split::{SplitGeneric, SplitStreams};
+use crate::traced::{EventRecorder, TracedStreams};
+use crate::transcript::{read_packets, transcript_path, TranscribedStream};
 
 pub async fn test_stream(test_name: &str, mut stream: impl AsyncRead + AsyncWrite + Unpin) {
     eprint!("test {test_name} ... ");
@@ -62,11 +90,42 @@ pub async fn run() {
     });
 
     let stm = TokioStreams::Tcp(make_tcp_stream(address).await);
+    #[cfg(unix)]
+    {
+        use derive_io::{SocketAddrAny, SocketAddrs};
+
+        assert_eq!(stm.peer_addr().unwrap(), SocketAddrAny::Inet(address));
+        let TokioStreams::Tcp(tcp) = &stm else {
+            unreachable!()
+        };
+        let local = SocketAddrAny::Inet(tcp.local_addr().unwrap());
+        assert_eq!(stm.local_addr().unwrap(), local);
+
+        use derive_io::SocketControl;
+
+        stm.set_nodelay(true).unwrap();
+        assert!(stm.nodelay().unwrap());
+        // tokio streams cannot be cloned
+        let Err(err) = stm.try_clone() else {
+            panic!("expected an error");
+        };
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+
+        let (unix, _peer) = tokio::net::UnixStream::pair().unwrap();
+        let unix = TokioStreams::Unix(unix);
+        assert_eq!(unix.local_addr().unwrap(), SocketAddrAny::Unnamed);
+    }
     test_stream("TokioStreams enum", stm).await;
 
     let stm = TupleStruct::new(0, 0, make_tcp_stream(address).await);
     test_stream("TupleStruct", stm).await;
 
+    let stm = Box::pin(TupleStructPin(
+        make_tcp_stream(address).await,
+        std::marker::PhantomPinned,
+    ));
+    test_stream("TupleStructPin", stm).await;
+
     let stm = NamedStruct::new(make_tcp_stream(address).await);
     test_stream("NamedStruct", stm).await;
 
@@ -80,6 +139,9 @@ pub async fn run() {
     let stm = GenericUnrelated::new(make_tcp_stream(address).await, 0);
     test_stream("GenericUnrelated", stm).await;
 
+    let stm = GenericUnrelated2::new(make_tcp_stream(address).await, "unrelated");
+    test_stream("GenericUnrelated2", stm).await;
+
     let stm: EnumGeneric<tokio::net::TcpStream, tokio::net::TcpStream> =
         EnumGeneric::new_s(make_tcp_stream(address).await);
     test_stream("EnumGeneric", stm).await;
@@ -90,6 +152,9 @@ pub async fn run() {
     let stm: Generic2<tokio::net::TcpStream> = Generic2::new(make_tcp_stream(address).await);
     test_stream("Generic2", stm).await;
 
+    let stm: GenericConst<_> = GenericConst::new(make_tcp_stream(address).await);
+    test_stream("GenericConst", stm).await;
+
     let stm: ComplexStream<'_, _, ()> = ComplexStream::A(make_tcp_stream(address).await, None);
     test_stream("ComplexStream #1", stm).await;
 
@@ -99,12 +164,301 @@ pub async fn run() {
     );
     test_stream("ComplexStream #2", stm).await;
 
-    let stm = DuckType::new(make_tcp_stream(address).await);
+    let tcp = make_tcp_stream(address).await;
+    #[cfg(unix)]
+    let fd = std::os::fd::AsRawFd::as_raw_fd(&tcp);
+    let stm = DuckType::new(tcp);
+    #[cfg(unix)]
+    assert_eq!(std::os::fd::AsRawFd::as_raw_fd(&stm), fd);
     test_stream("DuckType", stm).await;
 
+    let stm = PinnedDuckType::new(make_tcp_stream(address).await);
+    test_stream("PinnedDuckType", stm).await;
+
+    let tcp = make_tcp_stream(address).await;
+    #[cfg(unix)]
+    let fd = std::os::fd::AsRawFd::as_raw_fd(&tcp);
+    let stm = TestableStream::Tcp(tcp);
+    #[cfg(unix)]
+    {
+        use derive_io::{MaybeAsFd, MaybeAsRawFd};
+
+        assert_eq!(stm.try_as_raw_fd(), Some(fd));
+        let borrowed = stm.try_as_fd().unwrap();
+        assert_eq!(std::os::fd::AsRawFd::as_raw_fd(&borrowed), fd);
+        assert!(TestableStream::Duplex(tokio::io::duplex(64).0)
+            .try_as_fd()
+            .is_none());
+        assert!(TestableStream::Cursor(Default::default())
+            .try_as_raw_fd()
+            .is_none());
+    }
+    test_stream("TestableStream", stm).await;
+
     let stm = DerefStruct::new(make_tcp_stream(address).await);
     test_stream("DerefStruct", stm).await;
 
+    let dropped = Arc::new(AtomicBool::new(false));
+    let stm = Box::pin(PinnedStream::new(
+        make_tcp_stream(address).await,
+        dropped.clone(),
+    ));
+    test_stream("PinnedStream", stm).await;
+    assert!(dropped.load(Ordering::SeqCst));
+
+    // Not boxed, as `test_stream` requires `Unpin`
+    let dropped = Arc::new(AtomicBool::new(false));
+    let stm = UnpinnedDrop::new(make_tcp_stream(address).await, dropped.clone());
+    test_stream("UnpinnedDrop", stm).await;
+    assert!(dropped.load(Ordering::SeqCst));
+
+    let stm = Box::pin(PinnedEnum::<TcpStream>::Pinned(NotUnpin::new(
+        make_tcp_stream(address).await,
+    )));
+    test_stream("PinnedEnum #1", stm).await;
+
+    let stm = Box::pin(PinnedEnum::Unpinned(make_tcp_stream(address).await));
+    test_stream("PinnedEnum #2", stm).await;
+
+    let tcp = make_tcp_stream(address).await;
+    #[cfg(unix)]
+    let fd = std::os::fd::AsRawFd::as_raw_fd(&tcp);
+    let mut stm = BufferedStreams::new_tcp(tcp);
+    #[cfg(unix)]
+    assert_eq!(std::os::fd::AsRawFd::as_raw_fd(&stm), fd);
+    let mut line = String::new();
+    stm.read_line(&mut line).await.unwrap();
+    assert_eq!(line, "Hello, world!");
+    stm.write_all(b"Hello, ").await.unwrap();
+    stm.write_all(b"buffer!").await.unwrap();
+    assert_eq!(stm.write_buffer().buffer(), b"Hello, buffer!");
+    stm.flush().await.unwrap();
+    assert!(stm.write_buffer().buffer().is_empty());
+    test_stream("BufferedStreams", stm).await;
+
+    let mut stm = CountingStreams::new_tcp(make_tcp_stream(address).await);
+    stm.write_all(b"ping").await.unwrap();
+    let mut buf = vec![];
+    stm.read_to_end(&mut buf).await.unwrap();
+    assert_eq!(stm.counters().read.get(), buf.len());
+    assert_eq!(stm.counters().written.get(), 4);
+    assert_eq!(stm.counters().errors.get(), 0);
+    test_stream("CountingStreams", stm).await;
+
+    let recorder = EventRecorder::default();
+    let (reader, writer) = make_tcp_stream(address).await.into_split();
+    let mut stm = TracedStreams::Split {
+        read: reader,
+        write: writer,
+    };
+    {
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+        stm.write_all(b"ping").await.unwrap();
+        let mut buf = vec![];
+        stm.read_to_end(&mut buf).await.unwrap();
+    }
+    let events = recorder.events.lock().unwrap().clone();
+    assert_eq!(
+        events[0],
+        r#"ty="TracedStreams" variant="Split" field="write" method="poll_write" poll="ready" bytes=4"#
+    );
+    assert!(events.contains(
+        &r#"ty="TracedStreams" variant="Split" field="read" method="poll_read" poll="ready" bytes=13"#
+            .to_string()
+    ));
+    test_stream("TracedStreams", stm).await;
+
+    let mut sink = vec![];
+    let mut stm = BufferedSink::new(&mut sink);
+    stm.write_all(b"Hello, drop!").await.unwrap();
+    drop(stm);
+    assert_eq!(sink, b"Hello, drop!");
+
+    drop(PendingFlush);
+    assert_eq!(
+        *PENDING_FLUSH_ERROR.lock().unwrap(),
+        Some(std::io::ErrorKind::WouldBlock)
+    );
+
+    let mut stm = TranscribedStream {
+        stream: make_tcp_stream(address).await,
+        transcript: TranscriptStream::new(),
+    };
+    let mut other = TranscribedStream {
+        stream: make_tcp_stream(address).await,
+        transcript: TranscriptStream::new(),
+    };
+    stm.write_all(b"ping").await.unwrap();
+    other.write_all(b"pong").await.unwrap();
+    let mut buf = vec![];
+    stm.read_to_end(&mut buf).await.unwrap();
+    // The server handles one connection at a time
+    test_stream("TranscribedStream", stm).await;
+    let mut buf = vec![];
+    other.read_to_end(&mut buf).await.unwrap();
+    let pcapng = std::fs::read(transcript_path()).unwrap();
+    std::fs::remove_file(transcript_path()).unwrap();
+    let packets = read_packets(&pcapng);
+    // Each stream is a separate flow, starting with a handshake that has no
+    // payload and fresh sequence numbers
+    for (port, written) in [(49152, b"ping"), (49153, b"pong")] {
+        let flow = (packets.iter())
+            .filter(|packet| packet.local_port == port)
+            .collect::<Vec<_>>();
+        assert!(flow[..3].iter().all(|packet| packet.payload.is_empty()));
+        assert!(flow[..2].iter().all(|packet| packet.seq == 0));
+        let payload = |from: [u8; 4]| {
+            (flow.iter())
+                .filter(|packet| packet.src == from)
+                .flat_map(|packet| packet.payload.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(payload([10, 0, 0, 1]), written);
+        assert_eq!(payload([10, 0, 0, 2]), b"Hello, world!");
+    }
+    drop(other);
+
+    eprint!("test FramedStreams ... ");
+    let framed = Framed::new(make_tcp_stream(address).await, LinesCodec::new());
+    let mut stm = FramedStreams::Tcp(framed);
+    stm.send("ping").await.unwrap();
+    assert_eq!(stm.next().await.unwrap().unwrap(), "Hello, world!");
+    assert!(stm.next().await.is_none());
+    drop(stm);
+    eprintln!(" OK");
+
+    eprint!("test FramedGeneric ... ");
+    let framed = Framed::new(make_tcp_stream(address).await, LinesCodec::new());
+    let mut stm = FramedGeneric::<_, Framed<TcpStream, LinesCodec>>::S(framed);
+    stm.send("ping").await.unwrap();
+    assert_eq!(stm.next().await.unwrap().unwrap(), "Hello, world!");
+    drop(stm);
+    eprintln!(" OK");
+
+    eprint!("test PinnedFramed ... ");
+    let framed = Framed::new(make_tcp_stream(address).await, LinesCodec::new());
+    let mut stm = Box::pin(PinnedFramed::new(framed));
+    assert_eq!(futures::Stream::size_hint(&stm), (0, None));
+    stm.send("ping").await.unwrap();
+    SinkExt::<&str>::close(&mut stm).await.unwrap();
+    assert_eq!(stm.next().await.unwrap().unwrap(), "Hello, world!");
+    eprintln!(" OK");
+
+    eprint!("test TokioListeners ... ");
+    {
+        use derive_io::{Listener, SocketAddrAny};
+
+        let listener = TokioListeners::Tcp(TcpListener::bind("127.0.0.1:0").await.unwrap());
+        let SocketAddrAny::Inet(local) = listener.local_addr().unwrap() else {
+            panic!("expected an inet address");
+        };
+        let client = make_tcp_stream(local).await;
+        let (stm, peer) = listener.accept().await.unwrap();
+        assert_eq!(peer, SocketAddrAny::Inet(client.local_addr().unwrap()));
+        assert!(matches!(stm, TokioStreams::Tcp(_)));
+        #[cfg(unix)]
+        {
+            use std::os::fd::AsRawFd;
+
+            let TokioListeners::Tcp(tcp) = &listener else {
+                unreachable!()
+            };
+            assert_eq!(listener.as_raw_fd(), tcp.as_raw_fd());
+
+            let path = std::env::temp_dir().join(format!("derive-io-{}.sock", std::process::id()));
+            _ = std::fs::remove_file(&path);
+            let listener = TokioListeners::Unix(tokio::net::UnixListener::bind(&path).unwrap());
+            assert_eq!(
+                listener.local_addr().unwrap(),
+                SocketAddrAny::Path(path.clone())
+            );
+            let _client = tokio::net::UnixStream::connect(&path).await.unwrap();
+            let (stm, peer) = listener.accept().await.unwrap();
+            assert_eq!(peer, SocketAddrAny::Unnamed);
+            assert!(matches!(stm, TokioStreams::Unix(_)));
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        // Serve axum over the listener enum
+        let listener = TokioListeners::Tcp(TcpListener::bind("127.0.0.1:0").await.unwrap());
+        let SocketAddrAny::Inet(local) = listener.local_addr().unwrap() else {
+            panic!("expected an inet address");
+        };
+        let app = axum::Router::new().route("/", axum::routing::get(|| async { "Hello, axum!" }));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let mut client = make_tcp_stream(local).await;
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(response.ends_with("Hello, axum!"), "{response}");
+    }
+    eprintln!(" OK");
+
+    eprint!("test SplitStreams ... ");
+    {
+        use derive_io::NativeSplit;
+        use split::{SplitGenericOwnedReadHalf, SplitStreamsOwnedReadHalf};
+
+        let mut stm = SplitStreams::Tcp(make_tcp_stream(address).await);
+        let (mut read, mut write) = stm.split();
+        let mut buf = [0; 13];
+        read.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"Hello, world!");
+        write.write_all(b"ping").await.unwrap();
+        drop(stm);
+
+        let stm = SplitStreams::Tcp(make_tcp_stream(address).await);
+        let (mut read, write) = stm.into_split();
+        assert!(matches!(read, SplitStreamsOwnedReadHalf::Tcp(_)));
+        read.read_exact(&mut buf).await.unwrap();
+        let stm = SplitStreams::reunite(read, write);
+        assert!(matches!(stm, Ok(SplitStreams::Tcp(_))));
+        drop(stm);
+
+        // Halves of different streams are handed back
+        let (read, _) = SplitStreams::Tcp(make_tcp_stream(address).await).into_split();
+        let (_, write) = SplitStreams::Tcp(make_tcp_stream(address).await).into_split();
+        let derive_io::ReuniteError(read, write) =
+            SplitStreams::reunite(read, write).err().unwrap();
+        let (other_read, other_write) =
+            SplitStreams::Tcp(make_tcp_stream(address).await).into_split();
+        assert!(SplitStreams::reunite(read, other_write).is_err());
+        assert!(SplitStreams::reunite(other_read, write).is_err());
+
+        // `DuplexStream` falls back to `tokio::io::split`
+        let (client, mut server) = tokio::io::duplex(64);
+        let mut stm = SplitStreams::Duplex(client);
+        let (mut read, mut write) = stm.split();
+        write.write_all(b"ping").await.unwrap();
+        server.write_all(b"pong").await.unwrap();
+        let mut buf = [0; 4];
+        read.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+        server.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+
+        let (read, write) = stm.into_split();
+        let stm = SplitStreams::reunite(read, write);
+        assert!(matches!(stm, Ok(SplitStreams::Duplex(_))));
+
+        // Halves of different variants are handed back
+        let (read, _) = SplitStreams::Tcp(make_tcp_stream(address).await).into_split();
+        let (_, write) = SplitStreams::Duplex(tokio::io::duplex(64).0).into_split();
+        assert!(SplitStreams::reunite(read, write).is_err());
+
+        let stm = SplitGeneric::<TcpStream, tokio::io::DuplexStream>::Native(
+            make_tcp_stream(address).await,
+        );
+        let (read, write) = stm.into_split();
+        assert!(matches!(read, SplitGenericOwnedReadHalf::Native(_)));
+        assert!(SplitGeneric::reunite(read, write).is_ok());
+    }
+    eprintln!(" OK");
+
     eprintln!();
     eprintln!("All tests completed successfully!");
     eprintln!();
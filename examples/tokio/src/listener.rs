@@ -0,0 +1,16 @@
+use derive_io::Listener;
+use tokio::net::TcpListener;
+
+#[cfg(unix)]
+use tokio::net::UnixListener;
+
+use crate::tokio_streams::TokioStreams;
+
+/// [`TokioListeners`] - Tests accepting connections across listener types,
+/// wrapping each stream in the matching [`TokioStreams`] variant.
+#[derive(Listener)]
+pub enum TokioListeners {
+    Tcp(#[listener(stream = TokioStreams::Tcp)] TcpListener),
+    #[cfg(unix)]
+    Unix(#[listener(stream = TokioStreams::Unix)] UnixListener),
+}
@@ -0,0 +1,27 @@
+use derive_io::{AsyncRead, AsyncWrite};
+use tokio::io::DuplexStream;
+use tokio::net::TcpStream;
+
+/// [`TestableStream`] - Tests fallible descriptor access for enums with
+/// in-memory variants, which have no descriptor.
+#[cfg_attr(unix, derive(derive_io::MaybeAsFd))]
+#[derive(AsyncRead, AsyncWrite)]
+pub enum TestableStream {
+    Tcp(
+        #[read]
+        #[write]
+        #[descriptor]
+        TcpStream,
+    ),
+    Duplex(
+        #[read]
+        #[write]
+        DuplexStream,
+    ),
+    #[descriptor(none)]
+    Cursor(
+        #[read]
+        #[write]
+        std::io::Cursor<Vec<u8>>,
+    ),
+}
@@ -0,0 +1,94 @@
+use std::marker::PhantomPinned;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use derive_io::{AsyncRead, AsyncWrite, PinProject, PinnedDrop};
+use tokio::net::TcpStream;
+
+/// [`NotUnpin`] - A stream that is `!Unpin` because of a structurally pinned
+/// [`PhantomPinned`] field.
+#[derive(AsyncRead, AsyncWrite, PinProject)]
+pub struct NotUnpin {
+    #[pin]
+    #[read]
+    #[write]
+    stream: TcpStream,
+    #[pin]
+    _pinned: PhantomPinned,
+}
+
+impl NotUnpin {
+    pub fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            _pinned: PhantomPinned,
+        }
+    }
+}
+
+/// [`PinnedStream`] - Tests delegation to a `!Unpin` field marked `#[pin]`,
+/// with a `PinnedDrop` hook and a flush on drop.
+#[derive(AsyncRead, AsyncWrite, PinProject)]
+#[pin(PinnedDrop)]
+pub struct PinnedStream {
+    #[pin]
+    #[read]
+    #[write(flush_on_drop)]
+    inner: NotUnpin,
+    dropped: Arc<AtomicBool>,
+}
+
+impl PinnedStream {
+    pub fn new(stream: TcpStream, dropped: Arc<AtomicBool>) -> Self {
+        Self {
+            inner: NotUnpin::new(stream),
+            dropped,
+        }
+    }
+}
+
+impl PinnedDrop for PinnedStream {
+    fn drop(self: Pin<&mut Self>) {
+        self.dropped.store(true, Ordering::SeqCst);
+    }
+}
+
+/// [`UnpinnedDrop`] - Tests a `PinnedDrop` hook on a type without `#[pin]`
+/// fields, which is always `Unpin`.
+#[derive(AsyncRead, AsyncWrite, PinProject)]
+#[pin(PinnedDrop)]
+pub struct UnpinnedDrop {
+    #[read]
+    #[write]
+    stream: TcpStream,
+    dropped: Arc<AtomicBool>,
+}
+
+impl UnpinnedDrop {
+    pub fn new(stream: TcpStream, dropped: Arc<AtomicBool>) -> Self {
+        Self { stream, dropped }
+    }
+}
+
+impl PinnedDrop for UnpinnedDrop {
+    fn drop(self: Pin<&mut Self>) {
+        self.dropped.store(true, Ordering::SeqCst);
+    }
+}
+
+/// [`PinnedEnum`] - Tests enums mixing `#[pin]` and `Unpin` variants.
+#[derive(AsyncRead, AsyncWrite, PinProject)]
+pub enum PinnedEnum<S> {
+    Pinned(
+        #[pin]
+        #[read]
+        #[write]
+        NotUnpin,
+    ),
+    Unpinned(
+        #[read]
+        #[write]
+        S,
+    ),
+}
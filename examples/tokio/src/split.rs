@@ -0,0 +1,25 @@
+use derive_io::Split;
+use tokio::io::DuplexStream;
+use tokio::net::TcpStream;
+
+#[cfg(unix)]
+use tokio::net::UnixStream;
+
+/// [`SplitStreams`] - Tests splitting an enum into read and write halves,
+/// using the native split of each stream and the generic split for
+/// [`DuplexStream`].
+#[derive(Split)]
+pub enum SplitStreams {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+    Duplex(#[split(generic)] DuplexStream),
+}
+
+/// [`SplitGeneric`] - Tests splitting an enum with generic streams.
+#[derive(Split)]
+#[allow(unused)]
+pub enum SplitGeneric<S, D> {
+    Native(S),
+    Generic(#[split(generic)] D),
+}
@@ -1,4 +1,4 @@
-use derive_io::{AsSocketDescriptor, AsyncRead, AsyncWrite};
+use derive_io::{AsSocketDescriptor, AsyncRead, AsyncWrite, SocketAddrs, SocketControl};
 use tokio::net::TcpStream;
 
 #[cfg(unix)]
@@ -7,9 +7,9 @@ use tokio::net::UnixStream;
 /// [`TokioStreams`] - Tests multi-variant enums with different stream types and
 /// platform-specific variants.
 ///
-/// Also demonstrates conditional derivation: `AsSocketDescriptor` is derived on
-/// unix only.
-#[cfg_attr(unix, derive(AsSocketDescriptor))]
+/// Also demonstrates conditional derivation: `AsSocketDescriptor`,
+/// `SocketAddrs` and `SocketControl` are derived on unix only.
+#[cfg_attr(unix, derive(AsSocketDescriptor, SocketAddrs, SocketControl))]
 #[derive(AsyncRead, AsyncWrite)]
 #[allow(unused)]
 pub enum TokioStreams {
@@ -0,0 +1,66 @@
+use std::sync::{Arc, Mutex};
+
+use derive_io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+/// [`TracedStreams`] - Tests `#[io(trace)]` events for a multi-variant enum.
+#[derive(AsyncRead, AsyncWrite)]
+#[io(trace)]
+#[allow(unused)]
+pub enum TracedStreams {
+    Tcp(
+        #[read]
+        #[write]
+        TcpStream,
+    ),
+    Split {
+        #[read]
+        read: tokio::net::tcp::OwnedReadHalf,
+        #[write]
+        write: tokio::net::tcp::OwnedWriteHalf,
+    },
+}
+
+/// A subscriber that records the fields of each event as a string.
+#[derive(Clone, Default)]
+pub struct EventRecorder {
+    pub events: Arc<Mutex<Vec<String>>>,
+}
+
+struct FieldRecorder(String);
+
+impl Visit for FieldRecorder {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        self.0.push_str(&format!("{}={:?}", field.name(), value));
+    }
+}
+
+impl Subscriber for EventRecorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = FieldRecorder(String::new());
+        event.record(&mut fields);
+        self.events.lock().unwrap().push(fields.0);
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
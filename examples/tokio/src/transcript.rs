@@ -0,0 +1,59 @@
+use derive_io::{AsyncRead, AsyncWrite, TranscriptStream};
+use tokio::net::TcpStream;
+
+/// [`TranscribedStream`] - Tests recording a pcapng transcript of the bytes
+/// read and written, with a TCP flow per stream.
+#[derive(AsyncRead, AsyncWrite)]
+#[io(transcript = transcript_path())]
+pub struct TranscribedStream {
+    #[read]
+    #[write]
+    pub stream: TcpStream,
+    #[io(transcript)]
+    pub transcript: TranscriptStream,
+}
+
+pub fn transcript_path() -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("derive-io-tokio-{}.pcapng", std::process::id()))
+}
+
+/// A TCP packet of a pcapng transcript.
+pub struct Packet {
+    pub src: [u8; 4],
+    /// The port of this side, whether it is the source or the destination.
+    pub local_port: u16,
+    pub seq: u32,
+    pub payload: Vec<u8>,
+}
+
+/// Returns each packet in a pcapng transcript.
+pub fn read_packets(pcapng: &[u8]) -> Vec<Packet> {
+    let u32_at = |pos: usize| u32::from_le_bytes(pcapng[pos..pos + 4].try_into().unwrap());
+    assert_eq!(u32_at(0), 0x0a0d0d0a);
+    assert_eq!(u32_at(8), 0x1a2b3c4d);
+    let mut packets = vec![];
+    let mut pos = 0;
+    while pos < pcapng.len() {
+        let (kind, len) = (u32_at(pos), u32_at(pos + 4) as usize);
+        if kind == 6 {
+            let captured = u32_at(pos + 20) as usize;
+            let packet = &pcapng[pos + 28..pos + 28 + captured];
+            assert_eq!(packet[0], 0x45);
+            assert_eq!(packet[9], 6);
+            let src: [u8; 4] = packet[12..16].try_into().unwrap();
+            let port = |pos: usize| u16::from_be_bytes([packet[pos], packet[pos + 1]]);
+            packets.push(Packet {
+                src,
+                local_port: if src == [10, 0, 0, 1] {
+                    port(20)
+                } else {
+                    port(22)
+                },
+                seq: u32::from_be_bytes(packet[24..28].try_into().unwrap()),
+                payload: packet[40..].to_vec(),
+            });
+        }
+        pos += len;
+    }
+    packets
+}